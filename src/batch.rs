@@ -0,0 +1,118 @@
+//! Titles for many ranges at once, e.g. to backfill a database of old PRs
+//!
+//! `--ranges-file` reads one range per line, either `base..branch` or a base
+//! and a branch separated by whitespace, such as two SHAs. Blank lines and
+//! lines starting with `#` are skipped.
+
+use crate::{
+    git::{GitRepo, RevisionRange},
+    pipeline::generate_branch_title,
+    GeneratorConfig, Result,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The title generated for one line of a ranges file, or why there is none
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangeTitle {
+    /// Line of the ranges file, counting from 1
+    pub line: usize,
+    /// The range as written in the file
+    pub range: String,
+    pub title: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Generate a title for each range listed in the file at `path`
+///
+/// Only reading the file can fail; an invalid range or a failed generation
+/// is reported in that range's `error` and the remaining ranges still run.
+pub async fn generate_ranges_file_titles(
+    repo: &GitRepo,
+    path: &Path,
+    config: &GeneratorConfig,
+) -> Result<Vec<RangeTitle>> {
+    let text = fs::read_to_string(path)?;
+    let mut titles = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let spec = line.trim();
+        if spec.is_empty() || spec.starts_with('#') {
+            continue;
+        }
+
+        let result = match parse_range_line(spec) {
+            Ok((base, branch)) => generate_branch_title(repo, &base, &branch, config).await,
+            Err(e) => Err(e),
+        };
+        let (title, error) = match result {
+            Ok(title) => (Some(title), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        titles.push(RangeTitle {
+            line: index + 1,
+            range: spec.to_string(),
+            title,
+            error,
+        });
+    }
+
+    Ok(titles)
+}
+
+/// Base and branch of `base..branch`, `base...branch` or `base branch`
+fn parse_range_line(spec: &str) -> Result<(String, String)> {
+    let range = match spec.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [base, branch] => RevisionRange {
+            base: Some(base.to_string()),
+            branch: Some(branch.to_string()),
+        },
+        _ => RevisionRange::parse(spec)?,
+    };
+
+    let head = || "HEAD".to_string();
+    Ok((range.base.unwrap_or_else(head), range.branch.unwrap_or_else(head)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestRepo;
+
+    #[tokio::test]
+    async fn test_three_ranges_give_three_results() {
+        let test_repo = TestRepo::new();
+        let base_sha = test_repo.git(&["rev-parse", "HEAD"]);
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        let fix_sha = test_repo.git(&["rev-parse", "HEAD"]);
+        test_repo.commit_file("search.rs", "index", "feat: add search index for bottles");
+
+        let ranges = test_repo.path().join("ranges.txt");
+        let text = format!(
+            "# backfill\nmain..feature/CRU-310-fix-bottle-stuck\n{} {}\n\nmain..feature/missing\n",
+            base_sha, fix_sha
+        );
+        fs::write(&ranges, text).unwrap();
+
+        let titles = generate_ranges_file_titles(&test_repo.open(), &ranges, &GeneratorConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(titles.len(), 3);
+        assert_eq!(titles.iter().map(|title| title.line).collect::<Vec<_>>(), vec![2, 3, 5]);
+        assert!(titles[0].title.as_deref().unwrap().starts_with("CRU-310"));
+        assert!(titles[1].title.is_some() && titles[1].error.is_none());
+        assert_eq!(titles[2].title, None);
+        assert_eq!(titles[2].error.as_deref(), Some("Branch 'feature/missing' not found"));
+    }
+
+    #[test]
+    fn test_parse_range_line() {
+        assert_eq!(parse_range_line("main..feature/x").unwrap(), ("main".to_string(), "feature/x".to_string()));
+        assert_eq!(parse_range_line("abc123  def456").unwrap(), ("abc123".to_string(), "def456".to_string()));
+        assert_eq!(parse_range_line("main..").unwrap(), ("main".to_string(), "HEAD".to_string()));
+        assert!(parse_range_line("main").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! On-disk cache of generated titles, keyed by context and settings
+//!
+//! Entries are small JSON files named after a hash of the context and the
+//! generation settings. The hash is FNV-1a, which unlike `DefaultHasher` is
+//! the same on every toolchain; a release that adds a setting or context
+//! field at worst turns old entries into misses until they are cleared.
+
+use crate::{context::CleanContext, ml::GeneratedTitle, GeneratorConfig, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Longest context summary stored with an entry
+const SUMMARY_LENGTH: usize = 60;
+
+/// 64-bit FNV-1a offset basis and prime
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// A cached title with a short description of its context
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    #[serde(skip)]
+    pub key: String,
+    pub title: String,
+    /// Backend output before post-processing; entries written before it was
+    /// stored only have the title
+    #[serde(default)]
+    pub raw_title: Option<String>,
+    /// Text truncation removed from the title
+    #[serde(default)]
+    pub removed_suffix: Option<String>,
+    /// Backend that generated the title; unknown for older entries
+    #[serde(default)]
+    pub backend: Option<String>,
+    pub summary: String,
+    /// Unix timestamp in seconds
+    pub created: u64,
+}
+
+impl CacheEntry {
+    /// Seconds since the entry was written
+    pub fn age_secs(&self) -> u64 {
+        now_secs().saturating_sub(self.created)
+    }
+}
+
+/// Directory of cached titles
+pub struct TitleCache {
+    dir: PathBuf,
+}
+
+impl TitleCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Cache in `$XDG_CACHE_HOME/pr-title-generator`, falling back to `~/.cache`
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+        Some(base.join("pr-title-generator"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Key for a context under the settings that affect the title
+    pub fn key(context: &CleanContext, config: &GeneratorConfig) -> String {
+        // The whole context, since titles also depend on fields the prompt
+        // leaves out, such as `issue_ref` or the files' line counts
+        let context = serde_json::to_string(context).unwrap_or_default();
+        // Edits to the pattern file change the title even when its path stays
+        let patterns = config.pattern_file.as_ref().and_then(|path| fs::read(path).ok()).unwrap_or_default();
+        // Debug output covers every setting without listing them by hand;
+        // settings that do not change the cached title are reset first, and
+        // the post-process command runs after the lookup
+        let settings = GeneratorConfig {
+            verbose: false,
+            cache_dir: None,
+            pattern_file: None,
+            post_process_command: None,
+            ..config.clone()
+        };
+        let settings = format!("{:?}", settings);
+        format!("{:016x}", stable_hash(&[context.as_bytes(), &patterns, settings.as_bytes()]))
+    }
+
+    /// Get a cached title, treating unreadable entries as misses
+    pub fn get(&self, key: &str) -> Option<GeneratedTitle> {
+        let text = fs::read_to_string(self.entry_path(key)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&text).ok()?;
+        Some(GeneratedTitle {
+            raw_title: entry.raw_title.unwrap_or_else(|| entry.title.clone()),
+            title: entry.title,
+            truncated: entry.removed_suffix.is_some(),
+            removed_suffix: entry.removed_suffix,
+            backend: entry.backend.unwrap_or_else(|| "unknown".to_string()),
+        })
+    }
+
+    /// Store a title for `key`
+    pub fn put(&self, key: &str, context: &CleanContext, generated: &GeneratedTitle) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let entry = CacheEntry {
+            key: key.to_string(),
+            title: generated.title.clone(),
+            raw_title: Some(generated.raw_title.clone()),
+            removed_suffix: generated.removed_suffix.clone(),
+            backend: Some(generated.backend.clone()),
+            summary: summarize(context),
+            created: now_secs(),
+        };
+        fs::write(self.entry_path(key), serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+
+    /// List entries, newest first; a missing cache directory is empty
+    pub fn list(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+
+        for path in self.entry_paths()? {
+            let Some(key) = path.file_stem().map(|stem| stem.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            if let Ok(entry) = serde_json::from_str::<CacheEntry>(&text) {
+                entries.push(CacheEntry { key, ..entry });
+            }
+        }
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.created));
+        Ok(entries)
+    }
+
+    /// Remove all entries and return how many were removed
+    pub fn clear(&self) -> Result<usize> {
+        let paths = self.entry_paths()?;
+        for path in &paths {
+            fs::remove_file(path)?;
+        }
+        Ok(paths.len())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn entry_paths(&self) -> Result<Vec<PathBuf>> {
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut paths = Vec::new();
+        for entry in read_dir {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+}
+
+/// One-line description of a context: ticket, then description or first commit
+fn summarize(context: &CleanContext) -> String {
+    let subject = context
+        .description
+        .as_deref()
+        .or(context.commits.first().map(String::as_str))
+        .unwrap_or("no commits");
+    let summary = match &context.ticket {
+        Some(ticket) => format!("{} {}", ticket, subject),
+        None => subject.to_string(),
+    };
+
+    summary.chars().take(SUMMARY_LENGTH).collect()
+}
+
+/// FNV-1a hash of `parts`, each preceded by its length so that moving bytes
+/// between parts changes the hash
+fn stable_hash(parts: &[&[u8]]) -> u64 {
+    parts
+        .iter()
+        .flat_map(|part| (part.len() as u64).to_le_bytes().into_iter().chain(part.iter().copied()))
+        .fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME))
+}
+
+/// Format an age in seconds as `42s`, `5m`, `3h` or `2d`
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3_599 => format!("{}m", secs / 60),
+        3_600..=86_399 => format!("{}h", secs / 3_600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn context(description: &str) -> CleanContext {
+        CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            description: Some(description.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn generated(title: &str) -> GeneratedTitle {
+        GeneratedTitle {
+            title: title.to_string(),
+            raw_title: title.to_string(),
+            truncated: false,
+            removed_suffix: None,
+            backend: "pattern".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_list_and_clear_entries() {
+        let dir = TempDir::new().unwrap();
+        let cache = TitleCache::new(dir.path().join("titles"));
+        let config = GeneratorConfig::default();
+
+        let first = context("fix bottle stuck");
+        let second = context("add search index");
+        cache.put(&TitleCache::key(&first, &config), &first, &generated("CRU-310: Fix bottle stuck")).unwrap();
+        cache.put(&TitleCache::key(&second, &config), &second, &generated("CRU-310: Add search index")).unwrap();
+
+        let entries = cache.list().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().any(|entry| entry.summary == "CRU-310 fix bottle stuck"));
+        assert_eq!(
+            cache.get(&TitleCache::key(&first, &config)),
+            Some(generated("CRU-310: Fix bottle stuck"))
+        );
+
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert!(cache.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_missing_cache_dir_is_empty() {
+        let dir = TempDir::new().unwrap();
+        let cache = TitleCache::new(dir.path().join("missing"));
+
+        assert!(cache.list().unwrap().is_empty());
+        assert_eq!(cache.clear().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_key_depends_on_config() {
+        let context = context("fix bottle stuck");
+        let default = TitleCache::key(&context, &GeneratorConfig::default());
+        let suffix = TitleCache::key(
+            &context,
+            &GeneratorConfig::default().with_ticket_placement(crate::TicketPlacement::Suffix),
+        );
+
+        assert_ne!(default, suffix);
+    }
+
+    #[test]
+    fn test_key_follows_pattern_file_contents() {
+        let context = context("fix bottle stuck");
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let config = GeneratorConfig::default().with_pattern_file(file.path());
+        let key = |templates: &str| {
+            fs::write(file.path(), templates).unwrap();
+            TitleCache::key(&context, &config)
+        };
+
+        let repair = key("[actions]\nfix = [\"Repair {domain} {issue}\"]\n");
+        assert_ne!(key("[actions]\nfix = [\"Patch {domain} {issue}\"]\n"), repair);
+        assert_eq!(key("[actions]\nfix = [\"Repair {domain} {issue}\"]\n"), repair);
+    }
+
+    #[test]
+    fn test_stable_hash_is_fixed() {
+        // Keys name files on disk, so the hash must not change between builds
+        assert_eq!(stable_hash(&[b"fix bottle stuck"]), 0x7c79_3e91_a3a2_fae4);
+        assert_eq!(stable_hash(&[]), FNV_OFFSET_BASIS);
+        assert_ne!(stable_hash(&[b"ab", b"c"]), stable_hash(&[b"a", b"bc"]));
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(42), "42s");
+        assert_eq!(format_age(300), "5m");
+        assert_eq!(format_age(7_200), "2h");
+        assert_eq!(format_age(172_800), "2d");
+    }
+}
@@ -1,7 +1,23 @@
 //! Command line interface for the PR title generator
 
-use crate::{GeneratorConfig, Result};
-use clap::{Parser, ValueEnum};
+use crate::{cache::TitleCache, config::{self, ConfigFile, LockFile, Setting}, git::RevisionRange, tickets::{TicketFormat, TicketPrefixes}, CommitOrder, GeneratorConfig, LengthMetric, ListStyle, MergeStyle, Model, PromptPolicy, RankBy, Result, TicketPlacement};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::env;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Branch used for comparison when neither a flag nor CI provides one
+const DEFAULT_BASE_BRANCH: &str = "main";
+
+/// Branch analyzed when neither a flag, a range nor CI names one; resolved
+/// by `GitRepo::branch_name_for`, so a detached HEAD works too
+const DEFAULT_BRANCH: &str = "HEAD";
+
+/// CI variables holding the source branch of a pull/merge request
+const CI_BRANCH_VARS: [&str; 2] = ["GITHUB_HEAD_REF", "CI_MERGE_REQUEST_SOURCE_BRANCH_NAME"];
+
+/// CI variables holding the target branch of a pull/merge request
+const CI_BASE_VARS: [&str; 2] = ["GITHUB_BASE_REF", "CI_MERGE_REQUEST_TARGET_BRANCH_NAME"];
 
 /// Generate meaningful PR titles using ML models
 #[derive(Parser)]
@@ -19,63 +35,383 @@ Examples:
   generate-pr-title                        # Generate title for current branch
   generate-pr-title --verbose              # Enable verbose output  
   generate-pr-title --branch feature/auth  # Generate for specific branch
+  generate-pr-title main..feature/auth     # Same, using a git range
+  generate-pr-title --stack-base           # Compare against the parent of a stacked branch
   generate-pr-title --model phi-2          # Use different ML model
   generate-pr-title --temperature 0.5      # Adjust generation creativity
+  generate-pr-title --append-ticket-at suffix  # Fix login crash (CRU-310)
+  generate-pr-title --format json          # Title, candidates and context as JSON
+  generate-pr-title commit | git commit -F -  # Commit staged changes with a suggested message
+  generate-pr-title templates              # Show the templates behind pattern titles
+  generate-pr-title cache list             # Show cached titles
 "#)]
 pub struct Cli {
-    /// Branch to analyze (defaults to current branch)
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+
+    /// Git range to analyze, e.g. main..feature/x (alternative to --base/--branch)
+    #[arg(value_name = "RANGE", conflicts_with_all = ["base", "branch"])]
+    pub range: Option<String>,
+
+    /// Branch to analyze (defaults to CI source branch, then current branch)
     #[arg(long)]
     pub branch: Option<String>,
 
-    /// Base branch to compare against
-    #[arg(long, default_value = "main")]
-    pub base: String,
+    /// Base branch to compare against (defaults to CI target branch, then main);
+    /// a ticket in its name, e.g. epic/CRU-300, is not attributed to the PR
+    #[arg(long, visible_alias = "base-branch")]
+    pub base: Option<String>,
+
+    /// Compare against the branch this one is stacked on instead of the base
+    #[arg(long, conflicts_with_all = ["base", "range"])]
+    pub stack_base: bool,
 
     /// Maximum number of commits to analyze
     #[arg(long, default_value = "20")]
     pub max_commits: usize,
 
+    /// Commit order before --max-commits applies: newest first by time, or topological
+    #[arg(long, value_enum, default_value = "time")]
+    pub order: CommitOrder,
+
+    /// Number of representative commits to pass to the model
+    #[arg(long, default_value = "10")]
+    pub summary_size: usize,
+
+    /// Longest commit subject in characters; longer ones are cut to their first sentence
+    #[arg(long, default_value = "120", value_name = "CHARS")]
+    pub max_subject_chars: usize,
+
+    /// Longest prompt in characters passed to the model
+    #[arg(long, value_name = "CHARS")]
+    pub max_prompt_chars: Option<usize>,
+
+    /// Whether an oversized prompt is trimmed or an error
+    #[arg(long, value_enum, default_value = "trim")]
+    pub prompt_policy: PromptPolicy,
+
+    /// Merge consecutive commits whose keywords overlap at least this much (0.0-1.0), e.g. 0.6
+    #[arg(long, value_name = "THRESHOLD")]
+    pub cluster_threshold: Option<f32>,
+
+    /// Make the module of a file holding this share of the changed lines (default 0.6) the title's domain
+    #[arg(long, value_name = "SHARE", num_args = 0..=1, require_equals = true, default_missing_value = "0.6")]
+    pub emphasize_dominant: Option<f32>,
+
+    /// Include merge commits, e.g. merges of sub-feature branches
+    #[arg(long)]
+    pub include_merges: bool,
+
+    /// Pass the branch's nearest tag and the commits since it to the prompt, as a version hint
+    #[arg(long)]
+    pub nearest_tag: bool,
+
+    /// Only read commits whose author (Name <email>, after .mailmap) matches this regex
+    #[arg(long, value_name = "PATTERN")]
+    pub author: Option<String>,
+
+    /// Skip commits whose author matches this regex, e.g. '\[bot\]'
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude_author: Option<String>,
+
+    /// Only read commits after this ref, e.g. origin/feature/x for unpushed work
+    #[arg(long, value_name = "REF")]
+    pub since: Option<String>,
+
+    /// Stop at the first invalid --author/--exclude-author regex instead of listing them all
+    #[arg(long)]
+    pub strict_patterns: bool,
+
+    /// How the base branch merges PRs, or auto to detect it; unset, no commits are skipped as already landed
+    #[arg(long, value_enum)]
+    pub merge_style: Option<MergeStyle>,
+
+    /// Regex for release commit subjects with a (?P<version>...) group; matches release-please and semantic-release by default
+    #[arg(long, value_name = "PATTERN")]
+    pub release_pattern: Option<String>,
+
+    /// Leave the branch name out of the model prompt
+    #[arg(long)]
+    pub no_branch_context: bool,
+
+    /// Branch name prefixes to strip besides origin/ and refs/heads/, comma separated; '*' matches one segment, e.g. 'users/*'
+    #[arg(long, value_delimiter = ',', value_name = "PREFIX")]
+    pub strip_prefix: Vec<String>,
+
+    /// Title init and scaffold branches like any other instead of as "Initialize project"
+    #[arg(long)]
+    pub no_scaffolding: bool,
+
+    /// Type branches by name and commits only, without diffing them for docs-, test- or CI-only changes
+    #[arg(long)]
+    pub no_file_types: bool,
+
+    /// Title merge-queue branches with commits for several tickets like any other instead of as "Merge 3 PRs: ..."
+    #[arg(long)]
+    pub no_batches: bool,
+
+    /// List up to this many of the branch's tickets, e.g. 'CRU-310, CRU-311 +2 more: ...'; replaces batch titles
+    #[arg(long)]
+    pub max_tickets: Option<usize>,
+
+    /// Also recognize domains that recur in the base branch's commit history, e.g. "bottler"
+    #[arg(long)]
+    pub learn_vocab: bool,
+
     /// LLM model to use
     #[arg(long, default_value = "tiny-llama")]
     pub model: ModelType,
 
+    /// Fail instead of using the pattern backend when the model backend fails or is unavailable
+    #[arg(long)]
+    pub no_fallback: bool,
+
     /// Generation temperature (0.1-1.0)
     #[arg(long, default_value = "0.7")]
     pub temperature: f32,
 
+    /// Seed of the random choices, such as the template the pattern backend uses (default: 0)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
     /// Maximum title length
     #[arg(long, default_value = "50")]
     pub max_length: usize,
 
+    /// How title lengths are measured: characters, UTF-8 bytes or display width
+    #[arg(long, value_enum, default_value = "chars")]
+    pub length_metric: LengthMetric,
+
+    /// Maximum number of words in the title, not counting the ticket
+    #[arg(long)]
+    pub max_words: Option<usize>,
+
+    /// Marker for a truncated title, e.g. '…'; counts against --max-length
+    #[arg(long, default_value = "...")]
+    pub ellipsis: String,
+
     /// Enable verbose output
     #[arg(long, short)]
     pub verbose: bool,
+
+    /// Output format: the bare title, or JSON with candidates and diagnostics
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+
+    /// Order of the candidates in JSON output
+    #[arg(long, value_enum, default_value = "score")]
+    pub rank_by: RankBy,
+
+    /// Print the decision trace behind the title as JSON, for snapshot tests
+    #[arg(long, conflicts_with = "format")]
+    pub trace_json: bool,
+
+    /// Also print the backend's title before post-processing, to stderr
+    #[arg(long, conflicts_with = "trace_json")]
+    pub raw: bool,
+
+    /// Warn on stderr when a recent base commit already merged the branch's ticket
+    #[arg(long)]
+    pub check_merged: bool,
+
+    /// Force the title's action verb: add, fix, refactor, remove or update
+    #[arg(long)]
+    pub action: Option<String>,
+
+    /// Name the changed file in the title when one file dominates the branch
+    #[arg(long)]
+    pub use_filenames: bool,
+
+    /// Exit with code 3 instead of printing a generic title
+    #[arg(long)]
+    pub fail_on_generic: bool,
+
+    /// Keep the subject of a branch's only commit when it already scores as a good title
+    #[arg(long)]
+    pub prefer_commit_subject: bool,
+
+    /// Exit with code 4 when neither the branch nor its commits name a ticket
+    #[arg(long)]
+    pub require_ticket: bool,
+
+    /// Also generate a technical and a user-facing title; text output prints the technical one on stderr
+    #[arg(long)]
+    pub audience_titles: bool,
+
+    /// Use the ticket's project key as the scope, e.g. cru for CRU-310
+    #[arg(long)]
+    pub scope_from_ticket: bool,
+
+    /// Keep the title's first letter as generated instead of uppercasing it
+    #[arg(long)]
+    pub no_capitalize: bool,
+
+    /// Words to spell exactly as given wherever they appear, comma separated; iOS, GitHub, API and a few more are built in
+    #[arg(long, value_delimiter = ',', value_name = "WORD")]
+    pub case_exception: Vec<String>,
+
+    /// Verbs that replace the change type's action when they open most commits, comma separated; migrate, rename, deprecate and a few more are built in
+    #[arg(long, value_delimiter = ',', value_name = "VERB")]
+    pub strong_verb: Vec<String>,
+
+    /// Where to place the ticket in the title
+    #[arg(long, value_enum, default_value = "prefix")]
+    pub append_ticket_at: TicketPlacement,
+
+    /// Remove the ticket from the generated text so it only appears where --append-ticket-at puts it
+    #[arg(long)]
+    pub dedupe_ticket: bool,
+
+    /// Keep the primary issue reference, e.g. "(#1234)", at the end of the title instead of stripping it
+    #[arg(long)]
+    pub keep_refs: bool,
+
+    /// Read the first paragraph of each commit body along with its subject
+    #[arg(long)]
+    pub commit_bodies: bool,
+
+    /// Ticket formats to recognize, comma separated
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "jira")]
+    pub ticket_format: Vec<TicketFormat>,
+
+    /// Also recognize Jira keys with non-ASCII letters, e.g. Ä-123; may take uppercase words for tickets
+    #[arg(long)]
+    pub unicode_tickets: bool,
+
+    /// Jira project keys counted as tickets, comma separated, replacing CRU, JIRA, TASK, BUG, FEATURE and FIX
+    #[arg(long, value_delimiter = ',', conflicts_with = "any_ticket_prefix")]
+    pub ticket_prefixes: Vec<String>,
+
+    /// Count any Jira key written in uppercase as a ticket, e.g. PLAT-42 but not UTF-8 or SHA-256
+    #[arg(long)]
+    pub any_ticket_prefix: bool,
+
+    /// Config file to read instead of .pr-title.toml in the repository root
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Ignore the .pr-title.lock pins in the repository root
+    #[arg(long)]
+    pub no_lock: bool,
+
+    /// Reuse titles cached for an identical context and settings
+    #[arg(long)]
+    pub cache: bool,
+
+    /// TOML file of action templates that extend or replace the built-in ones
+    #[arg(long, visible_alias = "template-file", value_name = "PATH")]
+    pub pattern_file: Option<PathBuf>,
+
+    /// Text between a prefixed ticket and the title
+    #[arg(long, default_value = ": ")]
+    pub ticket_separator: String,
+
+    /// Leave out the comma before the final "and" of a list, e.g. "a, b and c"
+    #[arg(long)]
+    pub no_oxford_comma: bool,
+
+    /// Text between the items of a list in a title
+    #[arg(long, default_value = ", ")]
+    pub list_separator: String,
+
+    /// Ticket layout using {ticket} and {title} placeholders
+    #[arg(long)]
+    pub ticket_template: Option<String>,
+
+    /// Pipe the final title through a command, e.g. @./scripts/title.sh
+    #[arg(long, value_name = "@COMMAND")]
+    pub post_process: Option<String>,
+
+    /// Title each `base..branch` (or `BASE BRANCH`) line of a file and print a JSON array of results
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["range", "base", "branch", "stack_base", "trace_json"])]
+    pub ranges_file: Option<PathBuf>,
+
+    /// Generate the title from a `dump-context --format json` file ('-' for stdin) instead of git
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["range", "base", "branch", "stack_base", "ranges_file", "github_event", "format", "trace_json"])]
+    pub from_context: Option<PathBuf>,
+
+    /// Take the branches, and any commits, from the GitHub Actions event at GITHUB_EVENT_PATH
+    #[arg(long, conflicts_with_all = ["range", "base", "branch", "stack_base", "ranges_file"])]
+    pub github_event: bool,
+
+    /// Title a GitHub pull request by number, using the API instead of the local repo
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "NUMBER", conflicts_with_all = ["range", "base", "branch", "stack_base", "since", "merge_style", "check_merged"])]
+    pub pr: Option<u64>,
+
+    /// GitHub repository for --pr as owner/name (defaults to GITHUB_REPOSITORY, then origin)
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "OWNER/NAME", requires = "pr")]
+    pub repo: Option<String>,
+
+    /// Also copy the title to the system clipboard; only warns when there is none
+    #[cfg(feature = "clipboard")]
+    #[arg(long)]
+    pub copy: bool,
 }
 
-/// Supported ML models
-#[derive(Clone, Debug, ValueEnum)]
-pub enum ModelType {
-    #[value(name = "tiny-llama")]
-    TinyLlama,
-    #[value(name = "phi-2")]
-    Phi2,
-    #[value(name = "gemma-2b")]
-    Gemma2b,
-    #[value(name = "llama-2-7b")]
-    Llama2_7b,
+/// Subcommands besides titling a branch
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Suggest a conventional commit message for the staged changes
+    Commit,
+    /// List the pattern backend's templates for each action, including --pattern-file ones
+    Templates {
+        /// Output format: templates grouped by action, or a JSON list
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Print the effective settings after flags and the config file, with the source of each
+    Config {
+        /// Output format: one setting per line, or JSON objects with `value` and `source`
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Report noisy commit messages on the branch without generating a title; exits with code 5 on issues
+    Lint {
+        /// Also report subjects that are not conventional commits, e.g. `fix(auth): ...`
+        #[arg(long)]
+        require_conventional: bool,
+        /// Output format: one issue per line, or a JSON list of issues
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Print the commits and context the title would be generated from; replay JSON with --from-context
+    DumpContext {
+        /// Output format: the prompt, or JSON that --from-context replays
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Manage cached titles
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
 }
 
-impl ModelType {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            ModelType::TinyLlama => "tiny-llama",
-            ModelType::Phi2 => "phi-2",
-            ModelType::Gemma2b => "gemma-2b",
-            ModelType::Llama2_7b => "llama-2-7b",
-        }
-    }
+/// Title cache operations
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum CacheCommand {
+    /// List cached titles with their context and age
+    List,
+    /// Remove all cached titles
+    Clear,
 }
 
+/// How the result is printed; each command's `--format` help says what it prints
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Plain text
+    #[default]
+    Text,
+    /// JSON
+    Json,
+}
+
+/// Supported ML models, as accepted by `--model`
+pub type ModelType = Model;
+
 impl Cli {
     /// Parse command line arguments
     pub fn parse_args() -> Self {
@@ -98,6 +434,33 @@ impl Cli {
             });
         }
         
+        if self.max_words == Some(0) {
+            return Err(crate::Error::InvalidMaxWords { words: 0 });
+        }
+        
+        if self.max_tickets == Some(0) {
+            return Err(crate::Error::InvalidMaxTickets { tickets: 0 });
+        }
+        
+        // Validate external post-processor syntax
+        if let Some(value) = &self.post_process {
+            if self.post_process_command().is_none() {
+                return Err(crate::Error::InvalidPostProcess {
+                    value: value.clone(),
+                });
+            }
+        }
+        
+        // Validate forced action
+        if let Some(action) = &self.action {
+            crate::ml::validate_action(&action.to_lowercase())?;
+        }
+        
+        // Validate range syntax
+        if let Some(range) = &self.range {
+            RevisionRange::parse(range)?;
+        }
+        
         Ok(())
     }
     
@@ -105,57 +468,282 @@ impl Cli {
     pub fn to_config(&self) -> GeneratorConfig {
         GeneratorConfig {
             model_name: self.model.as_str().to_string(),
+            quantization: None,
+            backend: None,
+            pattern_fallback: !self.no_fallback,
             temperature: self.temperature,
+            seed: self.seed,
             max_length: self.max_length,
+            length_metric: self.length_metric,
+            max_words: self.max_words,
+            max_length_by_type: BTreeMap::new(),
+            ellipsis: self.ellipsis.clone(),
             max_commits: self.max_commits,
+            commit_order: self.order,
+            summary_size: self.summary_size,
+            max_subject_chars: self.max_subject_chars,
+            max_prompt_chars: self.max_prompt_chars,
+            prompt_policy: self.prompt_policy,
+            cluster_threshold: self.cluster_threshold,
+            emphasize_dominant: self.emphasize_dominant,
+            include_merges: self.include_merges,
+            include_nearest_tag: self.nearest_tag,
+            author: self.author.clone(),
+            exclude_author: self.exclude_author.clone(),
+            since: self.since.clone(),
+            strict_patterns: self.strict_patterns,
+            merge_style: self.merge_style,
+            base_branch: None,
+            release_pattern: self.release_pattern.clone(),
+            include_branch_context: !self.no_branch_context,
+            branch_prefixes: self.strip_prefix.clone(),
+            detect_scaffolding: !self.no_scaffolding,
+            detect_file_types: !self.no_file_types,
+            detect_batches: !self.no_batches,
+            max_tickets: self.max_tickets,
+            learn_vocab: self.learn_vocab,
+            domain_vocabulary: Vec::new(),
             verbose: self.verbose,
+            action: self.action.as_ref().map(|action| action.to_lowercase()),
+            use_filenames: self.use_filenames,
+            fail_on_generic: self.fail_on_generic,
+            prefer_commit_subject: self.prefer_commit_subject,
+            require_ticket: self.require_ticket,
+            audience_titles: self.audience_titles,
+            rank_by: self.rank_by,
+            scope_from_ticket: self.scope_from_ticket,
+            capitalize: !self.no_capitalize,
+            strong_verbs: crate::ml::DEFAULT_STRONG_VERBS
+                .iter()
+                .map(|verb| verb.to_string())
+                .chain(self.strong_verb.iter().map(|verb| verb.to_lowercase()))
+                .collect(),
+            case_exceptions: crate::ml::DEFAULT_CASE_EXCEPTIONS
+                .iter()
+                .map(|word| word.to_string())
+                .chain(self.case_exception.iter().cloned())
+                .collect(),
+            ticket_placement: self.append_ticket_at,
+            dedupe_ticket: self.dedupe_ticket,
+            keep_refs: self.keep_refs,
+            commit_bodies: self.commit_bodies,
+            ticket_formats: self.ticket_format.clone(),
+            unicode_tickets: self.unicode_tickets,
+            ticket_prefixes: self.allowed_ticket_prefixes(),
+            cache_dir: if self.cache { TitleCache::default_dir() } else { None },
+            pattern_file: self.pattern_file.clone(),
+            ticket_separator: self.ticket_separator.clone(),
+            list_style: ListStyle {
+                oxford_comma: !self.no_oxford_comma,
+                separator: self.list_separator.clone(),
+            },
+            ticket_template: self.ticket_template.clone(),
+            post_process_command: self.post_process_command(),
         }
     }
     
-    /// Get the branch name, using current branch if not specified
-    pub fn get_branch_name(&self) -> Result<String> {
-        if let Some(branch) = &self.branch {
-            Ok(branch.clone())
-        } else {
-            self.get_current_branch()
+    /// Convert CLI arguments to GeneratorConfig, adding settings from the config
+    /// and lock files
+    ///
+    /// Reads `--config` when given, otherwise `.pr-title.toml` in `repo_root`
+    /// if it exists. `.pr-title.lock` in `repo_root` then overrides both,
+    /// unless `--no-lock` is set.
+    pub fn load_config(&self, repo_root: &Path) -> Result<GeneratorConfig> {
+        let config = self.load_config_file(repo_root)?;
+        match self.lock_file(repo_root)? {
+            Some(lock) => lock.apply(config),
+            None => Ok(config),
         }
     }
     
-    /// Get the current git branch
-    fn get_current_branch(&self) -> Result<String> {
-        use std::process::Command;
-        
-        let output = Command::new("git")
-            .args(["branch", "--show-current"])
-            .output()
-            .map_err(|e| crate::Error::Io(e))?;
+    /// Effective settings with their source, see `config::effective_settings`
+    pub fn effective_settings(&self, repo_root: &Path) -> Result<BTreeMap<String, Setting>> {
+        config::effective_settings(
+            &self.to_config(),
+            &self.load_config_file(repo_root)?,
+            &self.load_config(repo_root)?,
+        )
+    }
+    
+    /// Flags plus the config file, without the lock file
+    fn load_config_file(&self, repo_root: &Path) -> Result<GeneratorConfig> {
+        let file = match &self.config {
+            Some(path) => Some(ConfigFile::load(path)?),
+            None => ConfigFile::discover(repo_root)?,
+        };
         
-        if !output.status.success() {
-            return Err(crate::Error::NoBranch);
+        match file {
+            Some(file) => file.apply(self.to_config()),
+            None => Ok(self.to_config()),
+        }
+    }
+    
+    fn lock_file(&self, repo_root: &Path) -> Result<Option<LockFile>> {
+        if self.no_lock {
+            Ok(None)
+        } else {
+            LockFile::discover(repo_root)
+        }
+    }
+    
+    /// Get the external post-process command without its `@` marker
+    fn post_process_command(&self) -> Option<String> {
+        self.post_process
+            .as_deref()
+            .and_then(|value| value.strip_prefix('@'))
+            .map(str::trim)
+            .filter(|command| !command.is_empty())
+            .map(str::to_string)
+    }
+    
+    /// Get the Jira keys counted as tickets, the defaults unless some are given
+    fn allowed_ticket_prefixes(&self) -> TicketPrefixes {
+        if self.any_ticket_prefix {
+            TicketPrefixes::AnyUppercase
+        } else if self.ticket_prefixes.is_empty() {
+            TicketPrefixes::default()
+        } else {
+            TicketPrefixes::Keys(self.ticket_prefixes.clone())
+        }
+    }
+    
+    /// Get the branch name: explicit flag or range, then CI environment, then `HEAD`
+    pub fn get_branch_name(&self) -> Result<String> {
+        self.branch_name_from(process_env)
+    }
+    
+    /// Get the branch name, reading CI variables through `env`
+    fn branch_name_from(&self, env: impl Fn(&str) -> Option<String>) -> Result<String> {
+        if let Some(range) = self.parsed_range()? {
+            return Ok(range.branch.unwrap_or_else(|| DEFAULT_BRANCH.to_string()));
         }
         
-        let branch = String::from_utf8_lossy(&output.stdout)
-            .trim()
-            .to_string();
+        if let Some(branch) = &self.branch {
+            return Ok(branch.clone());
+        }
         
-        if branch.is_empty() {
-            Err(crate::Error::NoBranch)
-        } else {
-            Ok(branch)
+        Ok(ci_env_value(&CI_BRANCH_VARS, env).unwrap_or_else(|| DEFAULT_BRANCH.to_string()))
+    }
+    
+    /// Get the base branch: explicit flag or range, then CI environment, then `main`
+    pub fn get_base_branch(&self) -> Result<String> {
+        self.base_branch_from(process_env)
+    }
+    
+    /// Get the base branch, reading CI variables through `env`
+    fn base_branch_from(&self, env: impl Fn(&str) -> Option<String>) -> Result<String> {
+        if let Some(range) = self.parsed_range()? {
+            return Ok(range.base.unwrap_or_else(|| "HEAD".to_string()));
         }
+        
+        Ok(self
+            .base
+            .clone()
+            .or_else(|| ci_env_value(&CI_BASE_VARS, env))
+            .unwrap_or_else(|| DEFAULT_BASE_BRANCH.to_string()))
+    }
+    
+    /// Parse the positional range argument, if given
+    fn parsed_range(&self) -> Result<Option<RevisionRange>> {
+        self.range.as_deref().map(RevisionRange::parse).transpose()
     }
 }
 
+/// Look up a variable in the process environment
+fn process_env(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+/// Get the first non-empty value among the given environment variables
+fn ci_env_value(names: &[&str], env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env(name))
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty())
+}
+
 impl Default for Cli {
     fn default() -> Self {
         Self {
+            command: None,
+            range: None,
             branch: None,
-            base: "main".to_string(),
+            base: None,
+            stack_base: false,
             max_commits: 20,
+            order: CommitOrder::Time,
+            summary_size: 10,
+            max_subject_chars: 120,
+            max_prompt_chars: None,
+            prompt_policy: PromptPolicy::Trim,
+            cluster_threshold: None,
+            emphasize_dominant: None,
+            include_merges: false,
+            nearest_tag: false,
+            author: None,
+            exclude_author: None,
+            since: None,
+            strict_patterns: false,
+            merge_style: None,
+            release_pattern: None,
+            no_branch_context: false,
+            strip_prefix: Vec::new(),
+            no_scaffolding: false,
+            no_file_types: false,
+            no_batches: false,
+            max_tickets: None,
+            learn_vocab: false,
             model: ModelType::TinyLlama,
+            no_fallback: false,
             temperature: 0.7,
+            seed: None,
             max_length: 50,
+            length_metric: LengthMetric::Chars,
+            max_words: None,
+            ellipsis: "...".to_string(),
             verbose: false,
+            format: OutputFormat::Text,
+            rank_by: RankBy::Score,
+            trace_json: false,
+            raw: false,
+            check_merged: false,
+            action: None,
+            use_filenames: false,
+            fail_on_generic: false,
+            prefer_commit_subject: false,
+            require_ticket: false,
+            audience_titles: false,
+            scope_from_ticket: false,
+            no_capitalize: false,
+            case_exception: Vec::new(),
+            strong_verb: Vec::new(),
+            append_ticket_at: TicketPlacement::Prefix,
+            dedupe_ticket: false,
+            keep_refs: false,
+            commit_bodies: false,
+            ticket_format: vec![TicketFormat::Jira],
+            unicode_tickets: false,
+            ticket_prefixes: Vec::new(),
+            any_ticket_prefix: false,
+            config: None,
+            no_lock: false,
+            cache: false,
+            pattern_file: None,
+            ticket_separator: ": ".to_string(),
+            no_oxford_comma: false,
+            list_separator: ", ".to_string(),
+            ticket_template: None,
+            post_process: None,
+            ranges_file: None,
+            from_context: None,
+            github_event: false,
+            #[cfg(feature = "http")]
+            pr: None,
+            #[cfg(feature = "http")]
+            repo: None,
+            #[cfg(feature = "clipboard")]
+            copy: false,
         }
     }
 }
@@ -224,4 +812,179 @@ mod tests {
         
         assert!(cli.validate().is_ok());
     }
+    
+    /// Environment holding only the given variables
+    fn fake_env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+    }
+    
+    #[test]
+    fn test_branch_from_ci_environment() {
+        let env = fake_env(&[("GITHUB_HEAD_REF", "feature/CRU-310-from-ci")]);
+        
+        let from_env = Cli::default().branch_name_from(&env).unwrap();
+        let from_flag = Cli {
+            branch: Some("feature/explicit".to_string()),
+            ..Default::default()
+        }
+        .branch_name_from(&env)
+        .unwrap();
+        
+        assert_eq!(from_env, "feature/CRU-310-from-ci");
+        assert_eq!(from_flag, "feature/explicit");
+        
+        // Blank variables are skipped in favor of the next one
+        let env = fake_env(&[("GITHUB_HEAD_REF", " "), ("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", "feature/gitlab")]);
+        assert_eq!(Cli::default().branch_name_from(env).unwrap(), "feature/gitlab");
+    }
+    
+    #[test]
+    fn test_base_from_ci_environment() {
+        let env = fake_env(&[("CI_MERGE_REQUEST_TARGET_BRANCH_NAME", "develop")]);
+        
+        let from_env = Cli::default().base_branch_from(&env).unwrap();
+        let from_flag = Cli {
+            base: Some("release".to_string()),
+            ..Default::default()
+        }
+        .base_branch_from(&env)
+        .unwrap();
+        
+        assert_eq!(from_env, "develop");
+        assert_eq!(from_flag, "release");
+        assert_eq!(Cli::default().base_branch_from(fake_env(&[])).unwrap(), DEFAULT_BASE_BRANCH);
+    }
+    
+    #[test]
+    fn test_range_sets_base_and_branch() {
+        let cli = Cli::try_parse_from(["generate-pr-title", "develop...feature/CRU-310-fix"]).unwrap();
+        
+        assert_eq!(cli.get_base_branch().unwrap(), "develop");
+        assert_eq!(cli.get_branch_name().unwrap(), "feature/CRU-310-fix");
+        
+        // Without a branch the checked-out one is analyzed, even on a detached HEAD
+        let cli = Cli::try_parse_from(["generate-pr-title", "main.."]).unwrap();
+        assert_eq!(cli.branch_name_from(fake_env(&[])).unwrap(), DEFAULT_BRANCH);
+        assert_eq!(Cli::default().branch_name_from(fake_env(&[])).unwrap(), DEFAULT_BRANCH);
+    }
+    
+    #[test]
+    fn test_range_conflicts_with_flags() {
+        let result = Cli::try_parse_from(["generate-pr-title", "main..feature/x", "--base", "develop"]);
+        assert!(result.is_err());
+    }
+    
+    #[test]
+    fn test_stack_base_conflicts_with_base() {
+        let result = Cli::try_parse_from(["generate-pr-title", "--stack-base", "--base", "develop"]);
+        assert!(result.is_err());
+        
+        let cli = Cli::try_parse_from(["generate-pr-title", "--stack-base", "--branch", "feature/x"]).unwrap();
+        assert!(cli.stack_base);
+    }
+    
+    #[test]
+    fn test_post_process_requires_command_marker() {
+        let cli = Cli {
+            post_process: Some("./title.sh".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(cli.validate(), Err(crate::Error::InvalidPostProcess { .. })));
+        
+        let cli = Cli {
+            post_process: Some("@./title.sh --team core".to_string()),
+            ..Default::default()
+        };
+        assert!(cli.validate().is_ok());
+        assert_eq!(cli.to_config().post_process_command.as_deref(), Some("./title.sh --team core"));
+    }
+    
+    #[tokio::test]
+    async fn test_action_update_uses_update_template() {
+        let cli = Cli::try_parse_from(["generate-pr-title", "--action", "Update"]).unwrap();
+        assert!(cli.validate().is_ok());
+        
+        let context = crate::context::CleanContext {
+            commits: vec!["wallet balance rounding".to_string()],
+            ..Default::default()
+        };
+        let generator = crate::ml::TitleGenerator::new(cli.to_config()).unwrap();
+        assert_eq!(generator.generate_title(&context).await.unwrap(), "Update crypto wallet balance rounding");
+    }
+    
+    #[test]
+    fn test_cache_subcommands_parse() {
+        let cli = Cli::try_parse_from(["generate-pr-title", "cache", "clear"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Cache { action: CacheCommand::Clear }));
+        
+        let cli = Cli::try_parse_from(["generate-pr-title", "main..feature/x"]).unwrap();
+        assert_eq!(cli.command, None);
+        assert_eq!(cli.range.as_deref(), Some("main..feature/x"));
+    }
+    
+    #[test]
+    fn test_subcommand_format_help_describes_its_output() {
+        let mut command = <Cli as clap::CommandFactory>::command();
+        let lint = command.find_subcommand_mut("lint").unwrap().render_long_help().to_string();
+        assert!(lint.contains("one issue per line"), "{}", lint);
+        assert!(!lint.contains("OutputPayload") && !lint.contains("Only the title"), "{}", lint);
+    }
+    
+    #[test]
+    fn test_config_dump_shows_file_and_flag_overrides() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join(crate::config::CONFIG_FILE_NAME), "[max_length_by_type]\ndocs = 30\n").unwrap();
+        
+        let cli = Cli::try_parse_from(["generate-pr-title", "--max-length", "72", "config"]).unwrap();
+        assert_eq!(cli.command, Some(Command::Config { format: OutputFormat::Text }));
+        let settings = cli.effective_settings(dir.path()).unwrap();
+        
+        assert_eq!(settings["max_length"].value, 72);
+        assert_eq!(settings["max_length"].source, config::ConfigSource::Flag);
+        assert_eq!(settings["max_length_by_type"].value["docs"], 30);
+        assert_eq!(settings["max_length_by_type"].source, config::ConfigSource::ConfigFile);
+        assert_eq!(settings["temperature"].value, 0.7);
+        assert_eq!(settings["temperature"].source, config::ConfigSource::Default);
+    }
+    
+    #[test]
+    fn test_lock_file_overrides_flags_and_config_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join(crate::config::CONFIG_FILE_NAME), "[max_length_by_type]\ndocs = 30\n").unwrap();
+        std::fs::write(dir.path().join(crate::config::LOCK_FILE_NAME), "model = \"phi-2\"\ntemperature = 0.3\n").unwrap();
+        
+        let cli = Cli::try_parse_from(["generate-pr-title", "--model", "gemma-2b", "--temperature", "0.9"]).unwrap();
+        let config = cli.load_config(dir.path()).unwrap();
+        assert_eq!(config.model_name, "phi-2");
+        assert_eq!(config.temperature, 0.3);
+        assert_eq!(config.max_length_by_type.len(), 1);
+        
+        let settings = cli.effective_settings(dir.path()).unwrap();
+        assert_eq!(settings["temperature"].source, config::ConfigSource::LockFile);
+        assert_eq!(settings["max_length_by_type"].source, config::ConfigSource::ConfigFile);
+        
+        let unlocked = Cli::try_parse_from(["generate-pr-title", "--no-lock", "--temperature", "0.9"]).unwrap();
+        assert_eq!(unlocked.load_config(dir.path()).unwrap().temperature, 0.9);
+    }
+    
+    #[test]
+    fn test_invalid_range_validation() {
+        let cli = Cli {
+            range: Some("main".to_string()),
+            ..Default::default()
+        };
+        
+        assert!(matches!(cli.validate(), Err(crate::Error::InvalidRange { .. })));
+    }
+    
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_pr_conflicts_with_branch_selection() {
+        let cli = Cli::try_parse_from(["generate-pr-title", "--pr", "42", "--repo", "acme/app"]).unwrap();
+        assert_eq!(cli.pr, Some(42));
+        assert_eq!(cli.repo.as_deref(), Some("acme/app"));
+        
+        let result = Cli::try_parse_from(["generate-pr-title", "--pr", "42", "--branch", "feature/x"]);
+        assert!(result.is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,116 @@
+//! Copying titles to the system clipboard for `--copy`
+//!
+//! Instead of linking a clipboard library, the text is piped to the first
+//! platform clipboard command that works: `pbcopy`, `wl-copy`, `xclip`,
+//! `xsel` or `clip.exe`. Headless machines such as CI runners have none,
+//! which callers should treat as a warning.
+
+use crate::{Error, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Somewhere a title can be copied to
+pub trait Clipboard {
+    fn copy(&self, text: &str) -> Result<()>;
+}
+
+/// The clipboard of the desktop session, reached through its command line tools
+pub struct SystemClipboard {
+    commands: Vec<(String, Vec<String>)>,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Self {
+        let commands: [(&str, &[&str]); 5] = [
+            ("pbcopy", &[]),
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+            ("clip.exe", &[]),
+        ];
+        Self::with_commands(commands.iter().map(|(program, args)| (*program, *args)))
+    }
+
+    /// Try these programs and arguments in order instead of the platform ones
+    pub fn with_commands<'a>(commands: impl IntoIterator<Item = (&'a str, &'a [&'a str])>) -> Self {
+        Self {
+            commands: commands
+                .into_iter()
+                .map(|(program, args)| (program.to_string(), args.iter().map(|arg| arg.to_string()).collect()))
+                .collect(),
+        }
+    }
+}
+
+impl Default for SystemClipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn copy(&self, text: &str) -> Result<()> {
+        let mut failures = Vec::new();
+        for (program, args) in &self.commands {
+            match pipe_to(program, args, text) {
+                Ok(()) => return Ok(()),
+                Err(message) => failures.push(format!("{}: {}", program, message)),
+            }
+        }
+
+        Err(Error::ClipboardUnavailable {
+            message: failures.join("; "),
+        })
+    }
+}
+
+fn pipe_to(program: &str, args: &[String], text: &str) -> std::result::Result<(), String> {
+    // xclip keeps running to serve the selection, so its output is not awaited
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+    }
+
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("exited with {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_first_working_command_receives_title() {
+        let dir = TempDir::new().unwrap();
+        let script = format!("cat > {}", dir.path().join("clipboard").display());
+        let clipboard = SystemClipboard::with_commands([
+            ("pr-title-missing-clipboard", &[][..]),
+            ("sh", &["-c", script.as_str()][..]),
+        ]);
+
+        clipboard.copy("CRU-310: Fix bottle stuck").unwrap();
+        let copied = std::fs::read_to_string(dir.path().join("clipboard")).unwrap();
+        assert_eq!(copied, "CRU-310: Fix bottle stuck");
+    }
+
+    #[test]
+    fn test_no_clipboard_command_is_unavailable() {
+        let clipboard = SystemClipboard::with_commands([("pr-title-missing-clipboard", &[][..]), ("false", &[][..])]);
+
+        let error = clipboard.copy("CRU-310: Fix bottle stuck").unwrap_err();
+        assert!(matches!(error, Error::ClipboardUnavailable { .. }));
+        assert!(error.to_string().contains("false: exited with"));
+    }
+}
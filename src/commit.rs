@@ -0,0 +1,180 @@
+//! Conventional commit messages for staged changes
+//!
+//! `generate-pr-title commit` runs the title machinery over the staged diff
+//! and the current branch name, and prints `type(scope): subject` with an
+//! optional body, ready for `git commit -F -`.
+
+use crate::{
+    context::ChangeType,
+    git::{ChangedFile, GitRepo},
+    ml::TitleGenerator,
+    pipeline::context_processor,
+    tickets::project_key,
+    Error, GeneratorConfig, Result, TicketPlacement,
+};
+use std::fmt;
+
+/// A conventional commit message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitMessage {
+    pub change_type: Option<ChangeType>,
+    pub scope: Option<String>,
+    pub subject: String,
+    /// Staged files when there are several, then a `Refs:` footer for the ticket
+    pub body: Option<String>,
+}
+
+impl CommitMessage {
+    /// Conventional commit type, e.g. `feat`; untyped changes are `chore`
+    pub fn type_label(&self) -> &'static str {
+        match self.change_type {
+            Some(ChangeType::Fix) | Some(ChangeType::Hotfix) => "fix",
+            Some(ChangeType::Feature) => "feat",
+            Some(ChangeType::Refactor) => "refactor",
+            Some(ChangeType::Docs) => "docs",
+            Some(ChangeType::Test) => "test",
+            Some(ChangeType::Ci) => "ci",
+            Some(ChangeType::Chore) | None => "chore",
+        }
+    }
+}
+
+impl fmt::Display for CommitMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.scope {
+            Some(scope) => write!(f, "{}({}): {}", self.type_label(), scope, self.subject)?,
+            None => write!(f, "{}: {}", self.type_label(), self.subject)?,
+        }
+        if let Some(body) = &self.body {
+            write!(f, "\n\n{}", body)?;
+        }
+        Ok(())
+    }
+}
+
+/// Suggest a commit message for the changes staged in `repo`
+///
+/// The change type comes from the branch name, or is `docs` when only
+/// documentation is staged. The ticket goes into a `Refs:` footer instead
+/// of the subject.
+pub async fn generate_commit_message(repo: &GitRepo, config: &GeneratorConfig) -> Result<CommitMessage> {
+    let files = repo.staged_files()?;
+    if files.is_empty() {
+        return Err(Error::NothingStaged);
+    }
+
+    // A detached HEAD or unborn branch just has no branch context
+    let branch = repo.current_branch().unwrap_or_default();
+    let context_processor = context_processor(config)?;
+    let branch_context = context_processor.extract_branch_context(&branch);
+
+    let mut context = context_processor.create_clean_context(&branch_context, &[]);
+    if context.change_type.is_none() {
+        context.change_type = context_processor.file_change_type(&files);
+    }
+    if config.scope_from_ticket {
+        context.scope = context.ticket.as_deref().and_then(project_key).map(str::to_lowercase);
+    }
+    context.action = config.action.clone().or_else(|| Some(action_for(context.change_type.as_ref()).to_string()));
+    context.files = files;
+
+    let subject_config = config
+        .clone()
+        .with_filenames(true)
+        .with_capitalize(false)
+        .with_ticket_placement(TicketPlacement::None);
+    let subject = TitleGenerator::new(subject_config)?.generate_title(&context).await?;
+
+    Ok(CommitMessage {
+        body: commit_body(&context.files, context.ticket.as_deref()),
+        change_type: context.change_type,
+        scope: context.scope,
+        subject: lowercase_first(&subject),
+    })
+}
+
+/// Verb for the subject, so the subject never repeats the type label
+fn action_for(change_type: Option<&ChangeType>) -> &'static str {
+    match change_type {
+        Some(ChangeType::Fix) | Some(ChangeType::Hotfix) => "fix",
+        Some(ChangeType::Feature) => "add",
+        Some(ChangeType::Refactor) => "refactor",
+        Some(ChangeType::Test) => "test",
+        Some(ChangeType::Ci) => "ci",
+        _ => "update",
+    }
+}
+
+fn commit_body(files: &[ChangedFile], ticket: Option<&str>) -> Option<String> {
+    let mut sections = Vec::new();
+    if files.len() > 1 {
+        let list: Vec<String> = files.iter().map(|file| format!("- {}", file.path)).collect();
+        sections.push(list.join("\n"));
+    }
+    if let Some(ticket) = ticket {
+        sections.push(format!("Refs: {}", ticket));
+    }
+
+    (!sections.is_empty()).then(|| sections.join("\n\n"))
+}
+
+/// Lowercase the first letter unless it starts an acronym such as `README`
+fn lowercase_first(subject: &str) -> String {
+    let mut chars = subject.chars();
+    match (chars.next(), chars.next()) {
+        (Some(first), Some(second)) if second.is_lowercase() => {
+            first.to_lowercase().chain(subject.chars().skip(1)).collect()
+        }
+        _ => subject.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestRepo;
+
+    fn stage_file(repo: &TestRepo, name: &str, content: &str) {
+        std::fs::write(repo.path().join(name), content).unwrap();
+        repo.git(&["add", name]);
+    }
+
+    #[tokio::test]
+    async fn test_staged_change_on_feature_branch() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-install-guide"]);
+        stage_file(&test_repo, "README.md", "# Test Repo\n\nInstall with cargo.\n");
+
+        let message = generate_commit_message(&test_repo.open(), &GeneratorConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(message.type_label(), "feat");
+        assert_eq!(message.to_string(), "feat: add README install guide\n\nRefs: CRU-310");
+    }
+
+    #[tokio::test]
+    async fn test_docs_type_and_file_list() {
+        let test_repo = TestRepo::new();
+        stage_file(&test_repo, "README.md", "# Test Repo\n\nUsage.\n");
+        stage_file(&test_repo, "CHANGELOG.md", "# Changelog\n");
+        // Unstaged edits are not part of the message
+        std::fs::write(test_repo.path().join("notes.rs"), "fn main() {}").unwrap();
+
+        let config = GeneratorConfig::default().with_scope_from_ticket(true);
+        let message = generate_commit_message(&test_repo.open(), &config).await.unwrap();
+
+        assert_eq!(message.change_type, Some(ChangeType::Docs));
+        assert_eq!(message.scope, None);
+        assert!(message.to_string().starts_with("docs: update "));
+        assert!(message.body.as_deref().unwrap().contains("- CHANGELOG.md\n- README.md"));
+    }
+
+    #[tokio::test]
+    async fn test_nothing_staged() {
+        let test_repo = TestRepo::new();
+
+        let result = generate_commit_message(&test_repo.open(), &GeneratorConfig::default()).await;
+        assert!(matches!(result, Err(Error::NothingStaged)));
+    }
+}
@@ -0,0 +1,302 @@
+//! Project settings read from a TOML config file
+//!
+//! The file is `.pr-title.toml` in the repository root unless `--config`
+//! names another one. Values here fill in settings the command line does
+//! not cover:
+//!
+//! ```toml
+//! [max_length_by_type]
+//! docs = 40
+//! feature = 60
+//! ```
+//!
+//! A committed `.pr-title.lock` pins the settings that decide the title, so
+//! everyone on a team gets the same one. It overrides flags and the config
+//! file unless `--no-lock` is given:
+//!
+//! ```toml
+//! model = "tiny-llama"
+//! quantization = "q4_0"
+//! backend = "pattern"
+//! temperature = 0.7
+//! seed = 42
+//! pattern_file = "ci/patterns.toml"
+//! template_version = 1
+//! ```
+
+use crate::{context::ChangeType, ml::TEMPLATE_VERSION, Error, GeneratorConfig, Model, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Config file looked up in the repository root
+pub const CONFIG_FILE_NAME: &str = ".pr-title.toml";
+
+/// Lock file looked up in the repository root
+pub const LOCK_FILE_NAME: &str = ".pr-title.lock";
+
+/// Settings loaded from a config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Title length limits keyed by change type, e.g. `docs = 40`
+    #[serde(default)]
+    pub max_length_by_type: BTreeMap<String, usize>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl ConfigFile {
+    /// Read a config file
+    pub fn load(path: &Path) -> Result<Self> {
+        let file: ConfigFile = read_toml(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ..file
+        })
+    }
+
+    /// Read `.pr-title.toml` from `dir` if it exists
+    pub fn discover(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if path.is_file() {
+            Self::load(&path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Apply the file's settings on top of `config`
+    pub fn apply(&self, mut config: GeneratorConfig) -> Result<GeneratorConfig> {
+        for (label, &length) in &self.max_length_by_type {
+            let change_type = ChangeType::parse(label).ok_or_else(|| Error::InvalidConfigFile {
+                path: self.path.clone(),
+                message: format!("unknown change type '{}' in max_length_by_type", label),
+            })?;
+            config.max_length_by_type.insert(change_type, length);
+        }
+
+        Ok(config)
+    }
+}
+
+/// Generation settings pinned by a lock file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LockFile {
+    /// Model name as given to `--model`, e.g. `phi-2`
+    pub model: Option<String>,
+    /// Weight quantization of the model, e.g. `q4_0`
+    pub quantization: Option<String>,
+    /// Backend titles must come from, e.g. `pattern`
+    pub backend: Option<String>,
+    /// `ml::TEMPLATE_VERSION` the titles were generated with; a build with
+    /// other built-in templates refuses the lock file
+    pub template_version: Option<u32>,
+    /// Sampling temperature for model backends
+    pub temperature: Option<f32>,
+    /// Seed of the random choices, such as which template the pattern backend uses
+    pub seed: Option<u64>,
+    /// Action templates, relative to the lock file's directory
+    pub pattern_file: Option<PathBuf>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl LockFile {
+    /// Read a lock file
+    pub fn load(path: &Path) -> Result<Self> {
+        let file: LockFile = read_toml(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ..file
+        })
+    }
+
+    /// Read `.pr-title.lock` from `dir` if it exists
+    pub fn discover(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(LOCK_FILE_NAME);
+        if path.is_file() {
+            Self::load(&path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Apply the pinned settings on top of `config`
+    pub fn apply(&self, mut config: GeneratorConfig) -> Result<GeneratorConfig> {
+        if let Some(model) = &self.model {
+            let model = Model::parse(model).ok_or_else(|| Error::InvalidConfigFile {
+                path: self.path.clone(),
+                message: format!("unknown model '{}'", model),
+            })?;
+            config.model_name = model.as_str().to_string();
+        }
+        if let Some(quantization) = &self.quantization {
+            config.quantization = Some(quantization.clone());
+        }
+        if let Some(backend) = &self.backend {
+            config.backend = Some(backend.clone());
+        }
+        if let Some(version) = self.template_version.filter(|&version| version != TEMPLATE_VERSION) {
+            return Err(Error::InvalidConfigFile {
+                path: self.path.clone(),
+                message: format!("pinned template version {}, but these are version {}", version, TEMPLATE_VERSION),
+            });
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(seed) = self.seed {
+            config.seed = Some(seed);
+        }
+        if let Some(pattern_file) = &self.pattern_file {
+            let dir = self.path.parent().unwrap_or(Path::new(""));
+            config.pattern_file = Some(dir.join(pattern_file));
+        }
+
+        Ok(config)
+    }
+}
+
+fn read_toml<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let text = fs::read_to_string(path)?;
+    toml::from_str(&text).map_err(|e| Error::InvalidConfigFile {
+        path: path.to_path_buf(),
+        message: e.message().to_string(),
+    })
+}
+
+/// Where an effective setting came from, from lowest to highest precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    Default,
+    Flag,
+    ConfigFile,
+    LockFile,
+}
+
+impl ConfigSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigSource::Default => "default",
+            ConfigSource::Flag => "flag",
+            ConfigSource::ConfigFile => "config file",
+            ConfigSource::LockFile => "lock file",
+        }
+    }
+}
+
+/// An effective setting and where it came from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Setting {
+    pub value: serde_json::Value,
+    pub source: ConfigSource,
+}
+
+/// Every `GeneratorConfig` field of `effective`, keyed by name, with its source
+///
+/// `flags` is the config built from the command line alone and `configured`
+/// adds the config file to it. A field counts as from the lock file when it
+/// differs from `configured`, as from the config file when `configured`
+/// differs from `flags`, and as a flag when `flags` differs from the default.
+pub fn effective_settings(
+    flags: &GeneratorConfig,
+    configured: &GeneratorConfig,
+    effective: &GeneratorConfig,
+) -> Result<BTreeMap<String, Setting>> {
+    let defaults = config_fields(&GeneratorConfig::default())?;
+    let flags = config_fields(flags)?;
+    let configured = config_fields(configured)?;
+
+    Ok(config_fields(effective)?
+        .into_iter()
+        .map(|(name, value)| {
+            let source = if configured.get(&name) != Some(&value) {
+                ConfigSource::LockFile
+            } else if flags.get(&name) != Some(&value) {
+                ConfigSource::ConfigFile
+            } else if defaults.get(&name) != Some(&value) {
+                ConfigSource::Flag
+            } else {
+                ConfigSource::Default
+            };
+            (name, Setting { value, source })
+        })
+        .collect())
+}
+
+fn config_fields(config: &GeneratorConfig) -> Result<serde_json::Map<String, serde_json::Value>> {
+    match serde_json::to_value(config)? {
+        serde_json::Value::Object(fields) => Ok(fields),
+        _ => Ok(serde_json::Map::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_discover_and_apply_length_overrides() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(CONFIG_FILE_NAME), "[max_length_by_type]\ndocs = 30\n").unwrap();
+
+        let file = ConfigFile::discover(dir.path()).unwrap().unwrap();
+        let config = file.apply(GeneratorConfig::default()).unwrap();
+
+        assert_eq!(config.max_length_by_type.get(&ChangeType::Docs), Some(&30));
+        assert_eq!(config.max_length, GeneratorConfig::default().max_length);
+    }
+
+    #[test]
+    fn test_missing_config_file_is_none() {
+        let dir = TempDir::new().unwrap();
+        assert!(ConfigFile::discover(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_lock_file_pins_pattern_file_next_to_it() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(LOCK_FILE_NAME), "pattern_file = \"ci/patterns.toml\"\n").unwrap();
+
+        let lock = LockFile::discover(dir.path()).unwrap().unwrap();
+        let config = lock.apply(GeneratorConfig::default()).unwrap();
+        assert_eq!(config.pattern_file, Some(dir.path().join("ci/patterns.toml")));
+        assert_eq!(config.model_name, GeneratorConfig::default().model_name);
+
+        fs::write(dir.path().join(LOCK_FILE_NAME), "model = \"gpt-9\"\n").unwrap();
+        let result = LockFile::discover(dir.path()).unwrap().unwrap().apply(GeneratorConfig::default());
+        assert!(matches!(result, Err(Error::InvalidConfigFile { .. })));
+
+        fs::write(dir.path().join(LOCK_FILE_NAME), "quantization = \"q4_0\"\nbackend = \"pattern\"\ntemplate_version = 1\n").unwrap();
+        let config = LockFile::discover(dir.path()).unwrap().unwrap().apply(GeneratorConfig::default()).unwrap();
+        assert_eq!(config.quantization.as_deref(), Some("q4_0"));
+        assert_eq!(config.backend.as_deref(), Some("pattern"));
+
+        fs::write(dir.path().join(LOCK_FILE_NAME), format!("template_version = {}\n", TEMPLATE_VERSION + 1)).unwrap();
+        let result = LockFile::discover(dir.path()).unwrap().unwrap().apply(GeneratorConfig::default());
+        assert!(matches!(result, Err(Error::InvalidConfigFile { .. })));
+
+        fs::write(dir.path().join(LOCK_FILE_NAME), "seed = 42\n").unwrap();
+        let config = LockFile::discover(dir.path()).unwrap().unwrap().apply(GeneratorConfig::default()).unwrap();
+        assert_eq!(config.seed, Some(42));
+
+        fs::write(dir.path().join(LOCK_FILE_NAME), "top_p = 0.9\n").unwrap();
+        assert!(matches!(LockFile::discover(dir.path()), Err(Error::InvalidConfigFile { .. })));
+    }
+
+    #[test]
+    fn test_unknown_change_type_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("custom.toml");
+        fs::write(&path, "[max_length_by_type]\nstyle = 30\n").unwrap();
+
+        let result = ConfigFile::load(&path).unwrap().apply(GeneratorConfig::default());
+        assert!(matches!(result, Err(Error::InvalidConfigFile { .. })));
+    }
+}
@@ -1,19 +1,34 @@
 //! Branch context extraction and text processing
 
-use crate::{git::CommitInfo, Result};
+use crate::{
+    error::PatternCompiler,
+    git::{ChangedFile, CommitInfo, NearestTag},
+    ml::{inflects, is_prose_file},
+    tickets::{TicketExtractor, TicketFormat, TicketPrefixes},
+    Error, Model, Result,
+};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::ops::Range;
 
 /// Extracted context from a branch name and commits
 #[derive(Debug, Clone)]
 pub struct BranchContext {
+    /// Branch name without remote prefixes or the ticket
+    pub branch: String,
+    /// First ticket of `tickets`, kept for callers that only use one
     pub ticket: Option<String>,
+    /// Every ticket the branch name references, in order and without repeats
+    pub tickets: Vec<String>,
     pub change_type: Option<ChangeType>,
     pub description: Option<String>,
 }
 
 /// Type of change inferred from branch name or commits
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
 pub enum ChangeType {
     Fix,
     Feature,
@@ -21,9 +36,26 @@ pub enum ChangeType {
     Hotfix,
     Chore,
     Docs,
+    Test,
+    Ci,
 }
 
 impl ChangeType {
+    /// Parse a change type label such as `feat` or `bugfix`
+    pub fn parse(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "fix" | "bug" | "bugfix" => Some(ChangeType::Fix),
+            "feat" | "feature" => Some(ChangeType::Feature),
+            "refactor" => Some(ChangeType::Refactor),
+            "hotfix" => Some(ChangeType::Hotfix),
+            "chore" => Some(ChangeType::Chore),
+            "doc" | "docs" => Some(ChangeType::Docs),
+            "test" | "tests" => Some(ChangeType::Test),
+            "ci" => Some(ChangeType::Ci),
+            _ => None,
+        }
+    }
+    
     pub fn as_str(&self) -> &'static str {
         match self {
             ChangeType::Fix => "fix",
@@ -32,22 +64,112 @@ impl ChangeType {
             ChangeType::Hotfix => "hotfix",
             ChangeType::Chore => "chore",
             ChangeType::Docs => "docs",
+            ChangeType::Test => "test",
+            ChangeType::Ci => "ci",
         }
     }
 }
 
+/// Commit trailer keys that explicitly set context fields, matched case-insensitively
+#[derive(Debug, Clone)]
+pub struct TrailerKeys {
+    pub change_type: Vec<String>,
+    pub scope: Vec<String>,
+}
+
+impl Default for TrailerKeys {
+    fn default() -> Self {
+        Self {
+            change_type: vec!["Type".to_string()],
+            scope: vec!["Scope".to_string()],
+        }
+    }
+}
+
+/// Share of changed lines above which one file dominates a branch
+const DOMINANT_FILE_SHARE: f32 = 0.75;
+
+/// Directories that hold code rather than name a module, e.g. `src`
+const SOURCE_ROOTS: [&str; 7] = ["src", "lib", "app", "pkg", "internal", "source", "crates"];
+
+/// File stems that stand for their directory's module, e.g. `mod.rs`
+const MODULE_ENTRY_POINTS: [&str; 5] = ["mod", "lib", "main", "index", "__init__"];
+
+/// Commits passed to the prompt when no summary size is configured
+pub const DEFAULT_SUMMARY_SIZE: usize = 10;
+
+/// Longest cleaned commit subject, in characters, when no cap is configured
+pub const DEFAULT_MAX_SUBJECT_CHARS: usize = 120;
+
+/// New files at which a branch counts as scaffolding whatever its name
+const SCAFFOLD_MIN_NEW_FILES: usize = 100;
+
+/// Most files the base may have for a branch to scaffold on it, e.g. a
+/// README, a license and a `.gitignore`
+pub const SCAFFOLD_MAX_BASE_FILES: usize = 5;
+
+/// Directories holding only tests, e.g. `tests/` or `__tests__/`
+const TEST_DIRS: [&str; 5] = ["test", "tests", "__tests__", "spec", "specs"];
+
+/// CI configuration, as path prefixes relative to the repository root
+const CI_PATHS: [&str; 9] = [
+    ".github/workflows/",
+    ".github/actions/",
+    ".circleci/",
+    ".buildkite/",
+    ".gitlab-ci.yml",
+    ".travis.yml",
+    "azure-pipelines.yml",
+    "bitbucket-pipelines.yml",
+    "Jenkinsfile",
+];
+
+/// Distinct commit tickets at which a branch counts as a merge-queue batch
+pub const BATCH_MIN_TICKETS: usize = 3;
+
+/// Branch name prefixes merge queues and merge trains create their branches under
+pub const MERGE_QUEUE_PREFIXES: [&str; 4] = ["gh-readonly-queue/", "mergify/merge-queue/", "merge-queue/", "merge-train/"];
+
+/// Issue or PR references such as `#1234`, `(#1234)`, `GH-1234` or `refs #1234`
+const ISSUE_REF_PATTERN: &str = r"(?i)(?:,?\s*\b(?:refs?|see|closes|fixes|resolves)\s+)?\(?(?:#|\bGH-)(\d+)\b\)?";
+
+/// Subjects release automation such as release-please (`chore(main): release 1.2.0`)
+/// and semantic-release (`chore(release): 1.2.0`) commits with
+pub const DEFAULT_RELEASE_PATTERN: &str =
+    r"(?i)^chore(?:\([^)]*\))?!?:\s+(?:release\s+)?v?(?P<version>\d+\.\d+\.\d+\S*)";
+
+/// Words that mark progress on a topic rather than the topic itself
+const PROGRESS_WORDS: &[&str] = &[
+    "start", "started", "begin", "continue", "continued", "finish", "finished",
+    "complete", "completed", "more", "further", "wip", "initial", "final",
+];
+
 /// Context processor for extracting meaningful information from git data
 pub struct ContextProcessor {
     // Precompiled regex patterns for efficiency
-    ticket_regex: Regex,
+    ticket_extractors: Vec<Box<dyn TicketExtractor>>,
+    // Settings the extractors were built from, to rebuild them when one changes
+    ticket_formats: Vec<TicketFormat>,
+    unicode_tickets: bool,
+    ticket_prefixes: TicketPrefixes,
     noise_patterns: Vec<Regex>,
     generic_terms: HashSet<String>,
+    trailer_keys: TrailerKeys,
+    include_branch: bool,
+    summary_size: usize,
+    max_subject_chars: usize,
+    cluster_threshold: Option<f32>,
+    release_pattern: Regex,
+    issue_ref_pattern: Regex,
+    keep_refs: bool,
+    /// Fold the first paragraph of commit bodies into the cleaned subjects
+    commit_bodies: bool,
+    /// Team-specific branch prefixes such as `dev/` or `users/*/`
+    branch_prefixes: Vec<Regex>,
 }
 
 impl ContextProcessor {
     pub fn new() -> Result<Self> {
-        let ticket_regex = Regex::new(r"([A-Z]+-\d+)")?;
-        
         let noise_patterns = vec![
             Regex::new(r"\b\d{4,}\b")?,                    // Long numbers
             Regex::new(r"\b[a-f0-9]{8,}\b")?,             // Hex strings
@@ -62,21 +184,165 @@ impl ContextProcessor {
             .collect();
         
         Ok(Self {
-            ticket_regex,
+            ticket_extractors: vec![TicketFormat::Jira.extractor()?],
+            ticket_formats: vec![TicketFormat::Jira],
+            unicode_tickets: false,
+            ticket_prefixes: TicketPrefixes::default(),
             noise_patterns,
             generic_terms,
+            trailer_keys: TrailerKeys::default(),
+            include_branch: true,
+            summary_size: DEFAULT_SUMMARY_SIZE,
+            max_subject_chars: DEFAULT_MAX_SUBJECT_CHARS,
+            cluster_threshold: None,
+            release_pattern: Regex::new(DEFAULT_RELEASE_PATTERN)?,
+            issue_ref_pattern: Regex::new(ISSUE_REF_PATTERN)?,
+            keep_refs: false,
+            commit_bodies: false,
+            branch_prefixes: Vec::new(),
         })
     }
     
+    /// Set whether the cleaned branch name is passed on in the clean context
+    pub fn with_branch_context(mut self, include_branch: bool) -> Self {
+        self.include_branch = include_branch;
+        self
+    }
+    
+    /// Recognize tickets in the given formats, tried in order
+    pub fn with_ticket_formats(mut self, formats: &[TicketFormat]) -> Result<Self> {
+        self.ticket_formats = formats.to_vec();
+        self.unicode_tickets = false;
+        self.build_ticket_extractors()
+    }
+    
+    /// Like `with_ticket_formats`, but Jira keys may use any letters, e.g. `Ä-123`
+    pub fn with_unicode_ticket_formats(mut self, formats: &[TicketFormat]) -> Result<Self> {
+        self.ticket_formats = formats.to_vec();
+        self.unicode_tickets = true;
+        self.build_ticket_extractors()
+    }
+    
+    /// Count Jira keys `prefixes` allows as tickets, e.g. `PLAT-42` for `PLAT`
+    ///
+    /// The default allows the project keys in `DEFAULT_TICKET_PREFIXES`.
+    pub fn with_ticket_prefixes(mut self, prefixes: TicketPrefixes) -> Result<Self> {
+        self.ticket_prefixes = prefixes;
+        self.build_ticket_extractors()
+    }
+    
+    fn build_ticket_extractors(mut self) -> Result<Self> {
+        self.ticket_extractors = self
+            .ticket_formats
+            .iter()
+            .map(|format| format.configured_extractor(self.unicode_tickets, &self.ticket_prefixes))
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+    
+    /// Set how many commits the clean context keeps for the prompt
+    pub fn with_summary_size(mut self, summary_size: usize) -> Self {
+        self.summary_size = summary_size;
+        self
+    }
+    
+    /// Cap cleaned commit subjects at `max_subject_chars` characters
+    ///
+    /// A longer subject is cut to its first sentence, or else to the whole
+    /// words that fit, so one verbose commit cannot swamp the title.
+    pub fn with_max_subject_chars(mut self, max_subject_chars: usize) -> Self {
+        self.max_subject_chars = max_subject_chars;
+        self
+    }
+    
+    /// Merge consecutive commits on the same topic, see `cluster_commits`
+    ///
+    /// `None` leaves commits as they are; a threshold must be between 0.0 and 1.0.
+    pub fn with_cluster_threshold(mut self, threshold: Option<f32>) -> Result<Self> {
+        if let Some(threshold) = threshold.filter(|threshold| !(0.0..=1.0).contains(threshold)) {
+            return Err(Error::InvalidClusterThreshold { threshold });
+        }
+        self.cluster_threshold = threshold;
+        Ok(self)
+    }
+    
+    /// Recognize release commits by a custom subject pattern
+    ///
+    /// The pattern must capture the version in a `version` group, e.g.
+    /// `^Bump version to (?P<version>\S+)`.
+    pub fn with_release_pattern(mut self, pattern: &str) -> Result<Self> {
+        if let Some(regex) = compile_release_pattern(&mut PatternCompiler::new(true), pattern)? {
+            self.release_pattern = regex;
+        }
+        Ok(self)
+    }
+    
+    /// Use a release pattern compiled by `compile_release_pattern`
+    pub(crate) fn with_release_regex(mut self, regex: Regex) -> Self {
+        self.release_pattern = regex;
+        self
+    }
+    
+    /// Remove issue references such as `(#1234)` from commit subjects whole
+    ///
+    /// The title gets the primary reference from `issue_ref` instead, so
+    /// no `#` is left behind where cleaning drops the number.
+    pub fn with_keep_refs(mut self, keep_refs: bool) -> Self {
+        self.keep_refs = keep_refs;
+        self
+    }
+    
+    /// Follow each commit subject with the first paragraph of its body
+    ///
+    /// Terse subjects such as `fix: crash` often leave the reason to the
+    /// body; trailers and `BREAKING CHANGE:` footers are left out.
+    pub fn with_commit_bodies(mut self, commit_bodies: bool) -> Self {
+        self.commit_bodies = commit_bodies;
+        self
+    }
+    
+    /// Also strip these prefixes from branch names, after `origin/` and the other built-in ones
+    ///
+    /// A prefix covers whole path segments, with or without the trailing
+    /// `/`, and `*` matches any one segment, so `users/*` strips
+    /// `users/alice/` from `users/alice/CRU-310-fix`.
+    pub fn with_branch_prefixes(mut self, prefixes: &[String]) -> Result<Self> {
+        let mut patterns = PatternCompiler::new(false);
+        self.branch_prefixes = compile_branch_prefixes(&mut patterns, prefixes)?;
+        patterns.finish()?;
+        Ok(self)
+    }
+    
+    /// Use branch prefixes compiled by `compile_branch_prefixes`
+    pub(crate) fn with_branch_prefix_regexes(mut self, prefixes: Vec<Regex>) -> Self {
+        self.branch_prefixes = prefixes;
+        self
+    }
+    
+    /// Use custom trailer keys for explicit type and scope
+    pub fn with_trailer_keys(mut self, trailer_keys: TrailerKeys) -> Self {
+        self.trailer_keys = trailer_keys;
+        self
+    }
+    
     /// Extract context from a branch name
     pub fn extract_branch_context(&self, branch_name: &str) -> BranchContext {
         let clean_branch = self.remove_branch_prefixes(branch_name);
-        let ticket = self.extract_ticket_number(&clean_branch);
+        let tickets = self.extract_all_tickets(&clean_branch);
+        let ticket = tickets.first().cloned();
+        // Later tickets would otherwise end up in the description
+        let clean_branch = tickets
+            .iter()
+            .skip(1)
+            .fold(clean_branch, |branch, extra| self.remove_ticket(&branch, &Some(extra.clone())));
         let change_type = self.infer_change_type(&clean_branch);
         let description = self.extract_description(&clean_branch, &ticket);
+        let branch = self.remove_ticket(&clean_branch, &ticket);
         
         BranchContext {
+            branch,
             ticket,
+            tickets,
             change_type,
             description,
         }
@@ -86,7 +352,41 @@ impl ContextProcessor {
     pub fn clean_commit_messages(&self, commits: &[CommitInfo]) -> Vec<String> {
         commits
             .iter()
-            .filter_map(|commit| self.clean_single_commit_message(commit.clean_message()))
+            .filter_map(|commit| self.clean_single_commit_message(&self.commit_text(commit)))
+            .collect()
+    }
+    
+    /// Commits that did not make it into `context`, with the reason for each
+    pub fn dropped_commits(&self, commits: &[CommitInfo], context: &CleanContext) -> Vec<DroppedCommit> {
+        // Later commits of a run are merged into the run's first, newest commit
+        let meaningful = self.filter_meaningful_commits(&self.clean_commit_messages(commits));
+        let mut clustered = HashSet::new();
+        let mut representatives = HashSet::new();
+        if let Some(threshold) = self.cluster_threshold {
+            for run in self.commit_runs(&meaningful, threshold).into_iter().filter(|run| run.len() > 1) {
+                representatives.insert(&meaningful[run.start]);
+                clustered.extend(&meaningful[run.start + 1..run.end]);
+            }
+        }
+        
+        commits
+            .iter()
+            .filter_map(|commit| {
+                let reason = match self.clean_commit(&self.commit_text(commit)) {
+                    Err(reason) => reason,
+                    Ok(cleaned) if self.is_generic_commit(&cleaned) => DropReason::Generic,
+                    Ok(cleaned) if representatives.contains(&cleaned) => return None,
+                    Ok(cleaned) if clustered.contains(&cleaned) => DropReason::Clustered,
+                    Ok(cleaned) if !context.commits.contains(&cleaned) => DropReason::Summarized,
+                    Ok(_) => return None,
+                };
+                
+                Some(DroppedCommit {
+                    hash: commit.hash.clone(),
+                    subject: commit.subject(),
+                    reason,
+                })
+            })
             .collect()
     }
     
@@ -97,53 +397,294 @@ impl ContextProcessor {
         commit_messages: &[String],
     ) -> CleanContext {
         let meaningful_commits = self.filter_meaningful_commits(commit_messages);
+        let meaningful_commits = match self.cluster_threshold {
+            Some(threshold) => self.cluster_commits(&meaningful_commits, threshold),
+            None => meaningful_commits,
+        };
+        let meaningful_commits = self.summarize_commits(&meaningful_commits, self.summary_size);
+        
+        let branch = Some(branch_context.branch.clone())
+            .filter(|branch| self.include_branch && !branch.is_empty());
         
         CleanContext {
+            branch,
             ticket: branch_context.ticket.clone(),
+            tickets: branch_context.tickets.clone(),
             change_type: branch_context.change_type.clone(),
             description: branch_context.description.clone(),
             commits: meaningful_commits,
+            ..Default::default()
+        }
+    }
+    
+    /// Check if every changed file is documentation: prose such as `*.md`,
+    /// or anything under a `docs/` directory
+    pub fn is_docs_only(&self, files: &[ChangedFile]) -> bool {
+        !files.is_empty()
+            && files.iter().all(|file| {
+                is_prose_file(&file.path) || file.path.split('/').rev().skip(1).any(|dir| dir == "docs")
+            })
+    }
+    
+    /// Check if every changed file is a test, e.g. `client_test.go`,
+    /// `test_client.py`, `client.spec.ts` or anything under `tests/`
+    pub fn is_test_only(&self, files: &[ChangedFile]) -> bool {
+        !files.is_empty() && files.iter().all(|file| is_test_file(&file.path))
+    }
+    
+    /// Check if every changed file is CI configuration, e.g. `.github/workflows/ci.yml`
+    pub fn is_ci_only(&self, files: &[ChangedFile]) -> bool {
+        !files.is_empty() && files.iter().all(|file| CI_PATHS.iter().any(|ci_path| file.path.starts_with(ci_path)))
+    }
+    
+    /// Change type decided by the changed files alone: `Docs`, `Test` or `Ci`
+    /// when every file is documentation, a test or CI configuration
+    pub fn file_change_type(&self, files: &[ChangedFile]) -> Option<ChangeType> {
+        if self.is_docs_only(files) {
+            Some(ChangeType::Docs)
+        } else if self.is_test_only(files) {
+            Some(ChangeType::Test)
+        } else if self.is_ci_only(files) {
+            Some(ChangeType::Ci)
+        } else {
+            None
+        }
+    }
+    
+    /// Whether a branch bootstraps a project instead of changing one
+    ///
+    /// Either its name starts with a word such as `init` or `scaffold`, or
+    /// it creates at least 100 files while barely touching existing ones on
+    /// a base of at most `SCAFFOLD_MAX_BASE_FILES` files, as the first branch
+    /// of a new repository does. `base_files` is `None` when the base is unknown.
+    pub fn is_scaffolding(&self, context: &CleanContext, files: &[ChangedFile], base_files: Option<usize>) -> bool {
+        let added = files.iter().filter(|file| file.added).count();
+        let near_empty_base = base_files.is_some_and(|count| count <= SCAFFOLD_MAX_BASE_FILES);
+        scaffold_intent(context).is_some()
+            || (near_empty_base && added >= SCAFFOLD_MIN_NEW_FILES && added * 10 >= files.len() * 9)
+    }
+    
+    /// Type a branch that only touches documentation, tests or CI
+    /// configuration as `Docs`, `Test` or `Ci`
+    ///
+    /// Changed files are a stronger signal than the branch name or commit
+    /// wording, so this replaces their type; commit trailers still win.
+    pub fn apply_file_change_type(&self, mut context: CleanContext, files: &[ChangedFile]) -> CleanContext {
+        if let Some(change_type) = self.file_change_type(files) {
+            context.change_type = Some(change_type);
+        }
+        context
+    }
+    
+    /// Override inferred type and scope with explicit commit trailers
+    ///
+    /// Trailers such as `Type: refactor` state the author's intent, so they
+    /// win over branch-name heuristics. The most recent commit takes precedence.
+    pub fn apply_commit_trailers(&self, mut context: CleanContext, commits: &[CommitInfo]) -> CleanContext {
+        let trailer_type = self
+            .find_trailer(commits, &self.trailer_keys.change_type)
+            .and_then(|value| ChangeType::parse(&value));
+        
+        if let Some(change_type) = trailer_type {
+            context.change_type = Some(change_type);
+        }
+        
+        if let Some(scope) = self.find_trailer(commits, &self.trailer_keys.scope) {
+            context.scope = Some(scope);
         }
+        
+        context
+    }
+    
+    /// Breaking changes the commits announce, newest first: the text of each
+    /// `BREAKING CHANGE:` footer, or the description of a `feat!:` subject
+    /// without one
+    pub fn breaking_changes(&self, commits: &[CommitInfo]) -> Vec<String> {
+        let mut changes: Vec<String> = Vec::new();
+        for commit in commits {
+            let footers = breaking_change_footers(&commit.body());
+            let found = if footers.is_empty() {
+                breaking_subject(&commit.subject()).into_iter().collect()
+            } else {
+                footers
+            };
+            for change in found {
+                if !changes.contains(&change) {
+                    changes.push(change);
+                }
+            }
+        }
+        changes
+    }
+    
+    /// Find the first trailer value matching one of the keys
+    fn find_trailer(&self, commits: &[CommitInfo], keys: &[String]) -> Option<String> {
+        commits.iter().find_map(|commit| {
+            trailer_block(&commit.body()).into_iter().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                let value = value.trim();
+                let is_match = keys.iter().any(|k| k.eq_ignore_ascii_case(key.trim()));
+                (is_match && !value.is_empty()).then(|| value.to_string())
+            })
+        })
     }
     
-    /// Remove common branch prefixes
+    /// Remove common branch prefixes, then the configured ones
     fn remove_branch_prefixes(&self, branch_name: &str) -> String {
-        branch_name
+        let branch = branch_name
             .replace("origin/", "")
             .replace("cursor/", "")
             .replace("refs/heads/", "")
-            .replace("refs/remotes/", "")
+            .replace("refs/remotes/", "");
+        
+        self.branch_prefixes
+            .iter()
+            .fold(branch, |branch, prefix| prefix.replace(&branch, "").to_string())
     }
     
-    /// Extract ticket number from branch name
-    fn extract_ticket_number(&self, branch_name: &str) -> Option<String> {
-        self.ticket_regex
-            .find(branch_name)
-            .map(|m| m.as_str().to_string())
-            .filter(|ticket| self.is_meaningful_ticket(ticket))
+    /// Remove the ticket and its separators from a branch name
+    fn remove_ticket(&self, branch_name: &str, ticket: &Option<String>) -> String {
+        let Some(ticket) = ticket else {
+            return branch_name.to_string();
+        };
+        
+        let is_separator = |c: char| matches!(c, '-' | '_' | '/');
+        let ticket_pattern = Regex::new(&format!("(?i){}", regex::escape(ticket)));
+        let Some(found) = ticket_pattern.ok().and_then(|pattern| pattern.find(branch_name)) else {
+            return branch_name.to_string();
+        };
+        
+        let before = branch_name[..found.start()].trim_end_matches(['-', '_']);
+        let after = branch_name[found.end()..].trim_start_matches(is_separator);
+        let joined = match (before.is_empty(), after.is_empty()) {
+            (true, _) => after.to_string(),
+            (false, true) => before.to_string(),
+            (false, false) if before.ends_with('/') => format!("{}{}", before, after),
+            (false, false) => format!("{}-{}", before, after),
+        };
+        
+        joined.trim_matches(is_separator).to_string()
     }
     
-    /// Check if a ticket number looks meaningful (not just random numbers)
-    fn is_meaningful_ticket(&self, ticket: &str) -> bool {
-        let prefixes = ["CRU-", "JIRA-", "TASK-", "BUG-", "FEATURE-", "FIX-"];
-        prefixes.iter().any(|prefix| ticket.starts_with(prefix))
+    /// Version of the newest release commit, e.g. `1.2.0` for
+    /// `chore(main): release 1.2.0`
+    ///
+    /// Release automation commits would otherwise be cleaned like any other
+    /// chore; when one is present the branch is titled as that release.
+    pub fn release_version(&self, commits: &[CommitInfo]) -> Option<String> {
+        commits.iter().find_map(|commit| {
+            let subject = commit.subject();
+            let captures = self.release_pattern.captures(&subject)?;
+            captures.name("version").map(|version| version.as_str().to_string())
+        })
+    }
+    
+    /// Distinct tickets referenced in commit messages, oldest commit first
+    ///
+    /// A merge-queue or merge-train branch batches several PRs, so it
+    /// references a ticket per PR.
+    pub fn commit_tickets(&self, commits: &[CommitInfo]) -> Vec<String> {
+        let mut tickets: Vec<String> = Vec::new();
+        for ticket in commits.iter().rev().filter_map(|commit| self.extract_ticket_number(&commit.clean_message())) {
+            if !tickets.contains(&ticket) {
+                tickets.push(ticket);
+            }
+        }
+        tickets
+    }
+    
+    /// Find a ticket referenced in commit messages, newest commit first
+    ///
+    /// Used when the branch name carries no ticket of its own.
+    pub fn extract_commit_ticket(&self, commits: &[CommitInfo]) -> Option<String> {
+        commits
+            .iter()
+            .find_map(|commit| self.extract_ticket_number(&commit.clean_message()))
+    }
+    
+    /// The issue the branch name references, else the newest commit's, as `#1234`
+    pub fn issue_ref(&self, branch: &str, commits: &[CommitInfo]) -> Option<String> {
+        std::iter::once(branch.to_string())
+            .chain(commits.iter().map(|commit| commit.clean_message()))
+            .find_map(|text| self.issue_ref_pattern.captures(&text).map(|captures| format!("#{}", &captures[1])))
+    }
+    
+    /// Drop a ticket that comes from the base branch, e.g. `epic/CRU-300`
+    ///
+    /// The ticket is removed from commit subjects, and from the context
+    /// itself when the branch only repeats it, so the epic is not prefixed
+    /// to every PR that targets it.
+    pub fn remove_base_ticket(&self, mut context: CleanContext, base_ticket: &str) -> CleanContext {
+        context.tickets.retain(|ticket| !ticket.eq_ignore_ascii_case(base_ticket));
+        if context.ticket.as_deref().is_some_and(|ticket| ticket.eq_ignore_ascii_case(base_ticket)) {
+            context.ticket = context.tickets.first().cloned();
+        }
+        
+        let pattern = format!(r"(?i)[\[(]?\b{}\b[\])]?:?", regex::escape(base_ticket));
+        if let Ok(pattern) = Regex::new(&pattern) {
+            context.commits = context
+                .commits
+                .iter()
+                .map(|commit| self.clean_text(&pattern.replace_all(commit, " ")))
+                .filter(|commit| !commit.is_empty())
+                .collect();
+        }
+        context
+    }
+    
+    /// Extract a ticket in canonical form using the configured formats
+    fn extract_ticket_number(&self, text: &str) -> Option<String> {
+        self.ticket_extractors
+            .iter()
+            .find_map(|extractor| extractor.extract(text))
+    }
+    
+    /// Every ticket in `text` in canonical form, without repeats
+    ///
+    /// Tickets of the first configured format come first, each format's
+    /// in order of appearance, so the first one is `extract_ticket_number`'s.
+    pub fn extract_all_tickets(&self, text: &str) -> Vec<String> {
+        let mut tickets: Vec<String> = Vec::new();
+        for ticket in self.ticket_extractors.iter().flat_map(|extractor| extractor.extract_all(text)) {
+            if !tickets.contains(&ticket) {
+                tickets.push(ticket);
+            }
+        }
+        tickets
+    }
+    
+    /// Suggest a single change type for the whole branch
+    ///
+    /// Signals are checked in priority order, returning the first that applies:
+    /// 1. The context's change type, set from a `Type:` trailer or the branch name
+    /// 2. The cleaned branch name, when the context has no change type yet
+    /// 3. The most common type among the commit messages, ties going to the newest
+    ///
+    /// The branch name wins over commits because it is chosen once for the
+    /// whole change, while individual commits often mix fixes into features.
+    pub fn suggest_change_type(&self, context: &CleanContext) -> Option<ChangeType> {
+        context
+            .change_type
+            .clone()
+            .or_else(|| context.branch.as_deref().and_then(|branch| self.infer_change_type(branch)))
+            .or_else(|| classify_commits(&context.commits))
     }
     
     /// Infer the type of change from branch name
     fn infer_change_type(&self, branch_name: &str) -> Option<ChangeType> {
         let lower_branch = branch_name.to_lowercase();
         
-        if lower_branch.contains("hotfix") {
+        if has_word(&lower_branch, &["hotfix"]) {
             Some(ChangeType::Hotfix)
-        } else if lower_branch.contains("fix") || lower_branch.contains("bug") {
+        } else if has_word(&lower_branch, &["fix", "bugfix", "bug"]) {
             Some(ChangeType::Fix)
-        } else if lower_branch.contains("feature") || lower_branch.contains("feat") {
+        } else if has_word(&lower_branch, &["feature", "feat"]) {
             Some(ChangeType::Feature)
-        } else if lower_branch.contains("refactor") {
+        } else if has_word(&lower_branch, &["refactor"]) {
             Some(ChangeType::Refactor)
-        } else if lower_branch.contains("docs") || lower_branch.contains("doc") {
+        } else if has_word(&lower_branch, &["docs", "doc", "documentation"]) {
             Some(ChangeType::Docs)
-        } else if lower_branch.contains("chore") {
+        } else if has_word(&lower_branch, &["chore"]) {
             Some(ChangeType::Chore)
         } else {
             None
@@ -164,7 +705,12 @@ impl ContextProcessor {
             return None;
         }
         
-        let description_words: Vec<&str> = words[start_idx..].iter().copied().collect();
+        // Drop leading issue/PR numbers that were rejected as tickets
+        let description_words: Vec<&str> = words[start_idx..]
+            .iter()
+            .copied()
+            .skip_while(|word| is_bare_number(word))
+            .collect();
         let description = description_words.join(" ");
         let clean_description = self.clean_text(&description);
         
@@ -175,40 +721,109 @@ impl ContextProcessor {
         }
     }
     
+    /// Text of a commit that is cleaned into its subject
+    ///
+    /// The subject stays the first paragraph, so `clean_commit` tells merges
+    /// and reverts apart by it alone.
+    fn commit_text(&self, commit: &CommitInfo) -> String {
+        if !self.commit_bodies {
+            return commit.clean_message();
+        }
+        
+        let subject = commit.subject();
+        let body = commit.body();
+        let paragraph: Vec<&str> = body
+            .lines()
+            .map(str::trim)
+            .take_while(|line| !line.is_empty())
+            .filter(|line| !is_footer(line) && !line.starts_with("BREAKING CHANGE:"))
+            .collect();
+        if paragraph.is_empty() {
+            return subject;
+        }
+        // A sentence break lets `cap_subject` fall back to the subject alone
+        let separator = if subject.ends_with(['.', '!', '?']) { "" } else { "." };
+        format!("{}{}\n\n{}", subject.trim_end(), separator, paragraph.join(" "))
+    }
+    
     /// Clean a single commit message
     fn clean_single_commit_message(&self, message: &str) -> Option<String> {
+        self.clean_commit(message).ok()
+    }
+    
+    /// Clean a single commit message, or say why it carries no content
+    fn clean_commit(&self, message: &str) -> std::result::Result<String, DropReason> {
         let mut clean_message = message.to_string();
         
-        // Remove conventional commit prefixes
+        // A revert of a revert re-applies the original change
+        match unwrap_reverts(message.lines().next().unwrap_or_default()) {
+            (0, _) => {}
+            (depth, original) if depth % 2 == 0 => clean_message = original.to_string(),
+            _ => return Err(DropReason::MergeOrRevert),
+        }
+        
+        if self.keep_refs {
+            clean_message = self.issue_ref_pattern.replace_all(&clean_message, "").to_string();
+        }
+        
+        // Remove conventional commit prefixes, with or without a scope such as `feat(retry):`
         let conventional_prefixes = [
-            "fix:", "feat:", "feature:", "bug:", "hotfix:", "refactor:",
-            "docs:", "style:", "test:", "chore:", "perf:", "ci:",
+            "fix", "feat", "feature", "bug", "hotfix", "refactor",
+            "docs", "style", "test", "chore", "perf", "ci",
         ];
         
-        for prefix in &conventional_prefixes {
-            if clean_message.to_lowercase().starts_with(prefix) {
-                clean_message = clean_message[prefix.len()..].trim().to_string();
-                break;
+        // `feat!:` marks a breaking change; `breaking_changes` collects it
+        let prefix_len = conventional_prefixes.iter().find_map(|prefix| {
+            if !starts_with_ignore_case(&clean_message, prefix) {
+                return None;
             }
+            let rest = &clean_message[prefix.len()..];
+            let rest = match rest.strip_prefix('(') {
+                Some(scoped) => &scoped[scoped.find(')')? + 1..],
+                None => rest,
+            };
+            let rest = rest.strip_prefix('!').unwrap_or(rest);
+            rest.strip_prefix(':').map(|subject| clean_message.len() - subject.len())
+        });
+        if let Some(len) = prefix_len {
+            clean_message = clean_message[len..].trim().to_string();
         }
         
-        // Remove merge and revert messages
-        if clean_message.to_lowercase().contains("merge") 
-            || clean_message.to_lowercase().contains("revert") {
-            return None;
+        // Remove merge and revert messages, by the subject alone since a
+        // body may well mention a merge
+        let subject = clean_message
+            .lines()
+            .take_while(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        if subject.contains("merge") || subject.contains("revert") {
+            return Err(DropReason::MergeOrRevert);
         }
         
-        let cleaned = self.clean_text(&clean_message);
+        let cleaned = cap_subject(&self.clean_text(&clean_message), self.max_subject_chars);
         
         if cleaned.len() > 5 {
-            Some(cleaned)
+            Ok(cleaned)
         } else {
-            None
+            Err(DropReason::TooShort)
         }
     }
     
-    /// Clean text by removing noise patterns
-    fn clean_text(&self, text: &str) -> String {
+    /// Clean text with the same rules applied to commit messages
+    ///
+    /// Removes the configured noise patterns (long numbers, hashes and
+    /// branch names such as `main`) and collapses whitespace, including
+    /// tabs and line breaks, into single spaces.
+    ///
+    /// ```
+    /// use pr_title_generator::context::ContextProcessor;
+    ///
+    /// let processor = ContextProcessor::new().unwrap();
+    /// let clean = processor.clean_text("fix  crash on main after build 20240101 at 3f9a2c1b7e");
+    /// assert_eq!(clean, "fix crash on after build at");
+    /// ```
+    pub fn clean_text(&self, text: &str) -> String {
         let mut clean_text = text.to_string();
         
         for pattern in &self.noise_patterns {
@@ -220,7 +835,7 @@ impl ContextProcessor {
     }
     
     /// Filter commits to keep only meaningful ones
-    fn filter_meaningful_commits(&self, commits: &[String]) -> Vec<String> {
+    pub(crate) fn filter_meaningful_commits(&self, commits: &[String]) -> Vec<String> {
         commits
             .iter()
             .filter(|commit| !self.is_generic_commit(commit))
@@ -228,6 +843,98 @@ impl ContextProcessor {
             .collect()
     }
     
+    /// Select up to `limit` commits that together cover the most distinct keywords
+    ///
+    /// Commits are picked greedily by how many keywords they add to the
+    /// selection, so a branch with many similar commits is represented by
+    /// one commit per topic rather than by the first `limit` commits.
+    /// Ties go to the newer commit and the result keeps the original order.
+    pub fn summarize_commits(&self, commits: &[String], limit: usize) -> Vec<String> {
+        if commits.len() <= limit {
+            return commits.to_vec();
+        }
+        
+        let keywords: Vec<HashSet<String>> = commits.iter().map(|c| self.commit_keywords(c)).collect();
+        let mut covered: HashSet<&String> = HashSet::new();
+        let mut selected: Vec<usize> = Vec::with_capacity(limit);
+        
+        while selected.len() < limit {
+            let best = (0..commits.len())
+                .filter(|index| !selected.contains(index))
+                .max_by_key(|&index| {
+                    let new_keywords = keywords[index].iter().filter(|k| !covered.contains(k)).count();
+                    (new_keywords, std::cmp::Reverse(index))
+                });
+            
+            let Some(index) = best else { break };
+            covered.extend(keywords[index].iter());
+            selected.push(index);
+        }
+        
+        selected.sort_unstable();
+        selected.into_iter().map(|index| commits[index].clone()).collect()
+    }
+    
+    /// Merge runs of consecutive commits on the same topic into one subject
+    ///
+    /// Neighbours in the newest-first commit list form a run while their
+    /// keywords, ignoring progress words such as "start" or "finish", have a
+    /// Jaccard similarity of at least `threshold`. Unlike exact deduplication
+    /// this collapses "start search ranking", "continue search ranking" and
+    /// "finish search ranking" into "search ranking": the run's newest commit
+    /// without its leading progress words.
+    pub fn cluster_commits(&self, commits: &[String], threshold: f32) -> Vec<String> {
+        self.commit_runs(commits, threshold)
+            .into_iter()
+            .map(|run| {
+                let newest = &commits[run.start];
+                if run.len() == 1 {
+                    return newest.clone();
+                }
+                
+                let topic: Vec<&str> = newest
+                    .split_whitespace()
+                    .skip_while(|word| is_progress_word(word))
+                    .collect();
+                if topic.is_empty() {
+                    newest.clone()
+                } else {
+                    topic.join(" ")
+                }
+            })
+            .collect()
+    }
+    
+    /// Index ranges of consecutive commits on the same topic
+    fn commit_runs(&self, commits: &[String], threshold: f32) -> Vec<Range<usize>> {
+        let topics: Vec<HashSet<String>> = commits
+            .iter()
+            .map(|commit| {
+                let mut keywords = self.commit_keywords(commit);
+                keywords.retain(|word| !is_progress_word(word));
+                keywords
+            })
+            .collect();
+        
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        for index in 0..commits.len() {
+            match runs.last_mut() {
+                Some(run) if jaccard(&topics[index - 1], &topics[index]) >= threshold => run.end = index + 1,
+                _ => runs.push(index..index + 1),
+            }
+        }
+        runs
+    }
+    
+    /// Lowercased content words of a commit, without generic terms
+    fn commit_keywords(&self, commit: &str) -> HashSet<String> {
+        commit
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+            .filter(|word| word.len() > 3 && !self.generic_terms.contains(word))
+            .collect()
+    }
+    
     /// Check if a commit message is too generic to be useful
     fn is_generic_commit(&self, commit: &str) -> bool {
         let words: Vec<&str> = commit.split_whitespace().collect();
@@ -244,32 +951,454 @@ impl ContextProcessor {
     }
 }
 
+/// Compile a `--release-pattern`, which must capture a `version` group
+pub(crate) fn compile_release_pattern(patterns: &mut PatternCompiler, pattern: &str) -> Result<Option<Regex>> {
+    let option = "--release-pattern";
+    let Some(regex) = patterns.compile(option, pattern)? else {
+        return Ok(None);
+    };
+    if !regex.capture_names().any(|name| name == Some("version")) {
+        patterns.reject(option, pattern, "no (?P<version>...) group")?;
+        return Ok(None);
+    }
+    Ok(Some(regex))
+}
+
+/// Compile `--strip-prefix` globs, see `ContextProcessor::with_branch_prefixes`
+pub(crate) fn compile_branch_prefixes(patterns: &mut PatternCompiler, prefixes: &[String]) -> Result<Vec<Regex>> {
+    let mut compiled = Vec::new();
+    for prefix in prefixes {
+        let segments: Vec<String> = prefix
+            .trim_end_matches('/')
+            .split('/')
+            .map(|segment| regex::escape(segment).replace(r"\*", "[^/]*"))
+            .collect();
+        compiled.extend(patterns.compile("--strip-prefix", &format!("^{}/", segments.join("/")))?);
+    }
+    Ok(compiled)
+}
+
+/// Whether `branch` is named like a merge queue's branch, e.g.
+/// `gh-readonly-queue/main/pr-44`, also after a remote such as `origin/`
+pub fn is_merge_queue_branch(branch: &str) -> bool {
+    MERGE_QUEUE_PREFIXES
+        .iter()
+        .any(|prefix| branch.starts_with(prefix) || branch.contains(&format!("/{}", prefix)))
+}
+
+/// Classify a single commit message by the keywords it contains
+pub fn classify_commit(message: &str) -> Option<ChangeType> {
+    let text = message.to_lowercase();
+    
+    if has_word(&text, &["fix", "bugfix", "hotfix", "bug", "issue"]) {
+        Some(ChangeType::Fix)
+    } else if has_word(&text, &["add", "implement", "feature"]) {
+        Some(ChangeType::Feature)
+    } else if has_word(&text, &["refactor", "improve"]) {
+        Some(ChangeType::Refactor)
+    } else {
+        None
+    }
+}
+
+/// Whether `text` has one of `words` as a whole word, in any inflection,
+/// e.g. `fixed` for `fix` but not `prefix`
+fn has_word(text: &str, words: &[&str]) -> bool {
+    text.split(|c: char| !c.is_alphanumeric())
+        .any(|token| words.iter().any(|word| inflects(token, word)))
+}
+
+/// Check if a path is a test by its name, e.g. `client_test.go` or
+/// `ClientTest.java`, or its directory, e.g. `tests/client.rs`
+fn is_test_file(path: &str) -> bool {
+    let mut parts = path.rsplit('/');
+    let name = parts.next().unwrap_or_default();
+    if parts.any(|dir| TEST_DIRS.contains(&dir)) {
+        return true;
+    }
+    
+    let (stem, _) = name.rsplit_once('.').unwrap_or((name, ""));
+    stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with(".test")
+        || stem.ends_with(".spec")
+        || stem.ends_with("Test")
+        || stem.ends_with("Tests")
+}
+
+/// Text of the `BREAKING CHANGE:` footers of a commit body; a footer runs
+/// until a blank line or the next footer
+fn breaking_change_footers(body: &str) -> Vec<String> {
+    let mut footers: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+    for line in body.lines() {
+        let line = line.trim();
+        let value = line
+            .strip_prefix("BREAKING CHANGE:")
+            .or_else(|| line.strip_prefix("BREAKING-CHANGE:"));
+        match (value, current.as_mut()) {
+            (Some(value), _) => {
+                footers.extend(current.take());
+                current = Some(value.trim().to_string());
+            }
+            (None, Some(text)) if !line.is_empty() && !is_footer(line) => {
+                text.push(' ');
+                text.push_str(line);
+            }
+            (None, _) => footers.extend(current.take()),
+        }
+    }
+    footers.extend(current);
+    footers.retain(|footer| !footer.is_empty());
+    footers
+}
+
+/// Lines of the final paragraph of `body` when every one is a `Token: value` trailer
+fn trailer_block(body: &str) -> Vec<&str> {
+    let last = body.trim_end().rsplit("\n\n").next().unwrap_or_default();
+    let lines: Vec<&str> = last.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.iter().all(|line| is_footer(line)) {
+        lines
+    } else {
+        Vec::new()
+    }
+}
+
+/// Whether a line starts a git trailer, e.g. `Refs: CRU-310` or `Reviewed-by: ...`
+fn is_footer(line: &str) -> bool {
+    line.split_once(": ")
+        .is_some_and(|(token, _)| !token.is_empty() && token.chars().all(|c| c.is_alphanumeric() || c == '-'))
+}
+
+/// Description of a subject marked breaking, e.g. `drop the v1 API` for `feat(api)!: drop the v1 API`
+fn breaking_subject(subject: &str) -> Option<String> {
+    let (prefix, description) = subject.split_once(": ")?;
+    (prefix.ends_with('!') && !prefix.contains(' ') && !description.trim().is_empty())
+        .then(|| description.trim().to_string())
+}
+
+/// Title verb and subject of a branch named like `scaffold/billing-service`
+/// or `feature/init-billing`, e.g. `("Scaffold", "billing service")`
+pub(crate) fn scaffold_intent(context: &CleanContext) -> Option<(&'static str, String)> {
+    let description = context.description.as_deref().unwrap_or_default();
+    let type_word = context.branch.as_deref().and_then(|branch| branch.split(['-', '_', '/']).next());
+    if let Some(verb) = type_word.and_then(scaffold_verb) {
+        return Some((verb, description.to_string()));
+    }
+    
+    let mut words = description.split_whitespace();
+    let verb = words.next().and_then(scaffold_verb)?;
+    Some((verb, words.collect::<Vec<_>>().join(" ")))
+}
+
+/// Title verb for a branch name opener, e.g. `Scaffold` for `bootstrap`
+fn scaffold_verb(word: &str) -> Option<&'static str> {
+    match word.to_lowercase().as_str() {
+        "init" | "initial" | "initialize" => Some("Initialize"),
+        "scaffold" | "bootstrap" => Some("Scaffold"),
+        _ => None,
+    }
+}
+
+/// Pick the most common commit classification, preferring the newest commit on ties
+pub fn classify_commits(commits: &[String]) -> Option<ChangeType> {
+    let classified: Vec<ChangeType> = commits.iter().filter_map(|c| classify_commit(c)).collect();
+    let count = |change_type: &ChangeType| classified.iter().filter(|c| *c == change_type).count();
+    
+    classified
+        .iter()
+        .fold(None, |best: Option<&ChangeType>, candidate| match best {
+            Some(best) if count(best) >= count(candidate) => Some(best),
+            _ => Some(candidate),
+        })
+        .cloned()
+}
+
+/// Module a path belongs to: its directory, e.g. `auth` for
+/// `src/auth/session.rs`, or its own stem, e.g. `billing` for `src/billing.rs`
+pub fn module_name(path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    let file = parts.pop()?;
+    let stem = file.split('.').next().filter(|stem| !stem.is_empty()).unwrap_or(file);
+    
+    let module = if MODULE_ENTRY_POINTS.contains(&stem) {
+        parts.iter().rev().find(|dir| !SOURCE_ROOTS.contains(dir)).copied().unwrap_or(stem)
+    } else {
+        match parts.last() {
+            Some(dir) if !SOURCE_ROOTS.contains(dir) => dir,
+            _ => stem,
+        }
+    };
+    Some(module.to_string())
+}
+
+/// How many reverts wrap `subject`, and the subject they wrap
+///
+/// `Revert "Revert "add X""` is two reverts of `add X`. Git titles a revert of
+/// a revert `Reapply "add X"`, which counts as two as well.
+fn unwrap_reverts(subject: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut subject = subject.trim();
+    loop {
+        let (prefix, reverts) = if starts_with_ignore_case(subject, "revert \"") {
+            ("revert \"", 1)
+        } else if starts_with_ignore_case(subject, "reapply \"") {
+            ("reapply \"", 2)
+        } else {
+            return (depth, subject);
+        };
+        match subject[prefix.len()..].strip_suffix('"') {
+            Some(inner) => {
+                depth += reverts;
+                subject = inner.trim();
+            }
+            None => return (depth, subject),
+        }
+    }
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len()).is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// First sentence of `subject` when it is longer than `limit` characters,
+/// or else its leading whole words within `limit`
+fn cap_subject(subject: &str, limit: usize) -> String {
+    if subject.chars().count() <= limit {
+        return subject.to_string();
+    }
+    
+    // One extra character shows whether the cut falls between words
+    let within: String = subject.chars().take(limit + 1).collect();
+    let sentence_end = within.char_indices().find(|&(i, c)| {
+        i > 0 && matches!(c, '.' | '!' | '?' | ';') && within[i + c.len_utf8()..].starts_with(' ')
+    });
+    let cut = match sentence_end {
+        Some((end, _)) => &within[..end],
+        None => within.rfind(' ').map_or(within.as_str(), |end| &within[..end]),
+    };
+    cut.chars().take(limit).collect()
+}
+
+fn is_progress_word(word: &str) -> bool {
+    PROGRESS_WORDS.contains(&word.to_lowercase().as_str())
+}
+
+/// Overlap of two keyword sets from 0.0 to 1.0; two empty sets share nothing
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+/// Check if a word is only digits, unlike version-like tokens such as `v2` or `1.2`
+fn is_bare_number(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Why a commit was left out of the context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropReason {
+    /// Merge and revert commits describe history, not the change; a revert
+    /// of a revert is kept as the change it re-applies
+    MergeOrRevert,
+    /// Too little text left after cleaning
+    TooShort,
+    /// Only generic terms such as "update" or "fix"
+    Generic,
+    /// Covered by other commits when summarizing to `summary_size`
+    Summarized,
+    /// Merged into a neighbouring commit on the same topic
+    Clustered,
+}
+
+/// A commit left out of the context
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DroppedCommit {
+    pub hash: String,
+    pub subject: String,
+    pub reason: DropReason,
+}
+
+/// Who a title is written for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Audience {
+    /// Impact-focused, as for users and release notes
+    #[default]
+    UserFacing,
+    /// Implementation-focused, as for reviewers
+    Technical,
+}
+
 /// Cleaned context ready for ML model input
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct CleanContext {
+    /// Cleaned branch name without the ticket, if enabled
+    pub branch: Option<String>,
     pub ticket: Option<String>,
+    /// Every ticket the branch name references, `ticket` first
+    #[serde(default)]
+    pub tickets: Vec<String>,
     pub change_type: Option<ChangeType>,
+    pub scope: Option<String>,
+    /// Action verb forced by the user, bypassing inference
+    pub action: Option<String>,
     pub description: Option<String>,
+    /// Version from a release commit; the branch is titled as that release
+    pub release_version: Option<String>,
+    /// Nearest tag of the branch, hinting at the version being released
+    pub nearest_tag: Option<NearestTag>,
+    /// The branch bootstraps a project; it is titled as initializing or scaffolding it
+    #[serde(default)]
+    pub scaffolding: bool,
+    /// Tickets of the PRs a merge-queue branch batches, oldest first; it is titled as the batch
+    #[serde(default)]
+    pub batch_tickets: Vec<String>,
+    /// Issue reference kept for the end of the title, e.g. `#1234`
+    #[serde(default)]
+    pub issue_ref: Option<String>,
+    /// Tickets besides `ticket` that the branch name and then the commits
+    /// reference, commits oldest first; only collected with `max_tickets`
+    #[serde(default)]
+    pub extra_tickets: Vec<String>,
+    /// Breaking changes announced by the commits, newest first
+    #[serde(default)]
+    pub breaking_changes: Vec<String>,
     pub commits: Vec<String>,
+    /// Files changed on the branch, when requested
+    pub files: Vec<ChangedFile>,
 }
 
 impl CleanContext {
-    /// Generate a prompt for the ML model
+    /// A `## BREAKING CHANGES` section for a PR body, listing `breaking_changes`
+    pub fn breaking_changes_section(&self) -> Option<String> {
+        if self.breaking_changes.is_empty() {
+            return None;
+        }
+        let items: Vec<String> = self.breaking_changes.iter().map(|change| format!("- {}", change)).collect();
+        Some(format!("## BREAKING CHANGES\n\n{}", items.join("\n")))
+    }
+    
+    /// The only changed file, or the one holding most of the changed lines
+    pub fn focused_file(&self) -> Option<&ChangedFile> {
+        if let [file] = self.files.as_slice() {
+            return Some(file);
+        }
+        
+        self.dominant_file(DOMINANT_FILE_SHARE)
+    }
+    
+    /// The file holding at least `share` of the changed lines, if any
+    pub fn dominant_file(&self, share: f32) -> Option<&ChangedFile> {
+        let total: usize = self.files.iter().map(|file| file.lines_changed).sum();
+        self.files
+            .iter()
+            .max_by_key(|file| file.lines_changed)
+            .filter(|file| total > 0 && file.lines_changed as f32 / total as f32 >= share)
+    }
+    
+    /// Size of the generated prompt in characters
+    pub fn prompt_size(&self) -> usize {
+        self.prompt_size_for(&Model::TinyLlama)
+    }
+    
+    /// Size of the prompt in `model`'s chat template in characters
+    pub fn prompt_size_for(&self, model: &Model) -> usize {
+        self.to_prompt_for(model).chars().count()
+    }
+    
+    /// Drop the oldest commits, then changed files, until `prompt_size_for`
+    /// `model` is at most `max_chars`; returns whether the prompt fits
+    pub fn trim_to_prompt_size(&mut self, max_chars: usize, model: &Model) -> bool {
+        while self.prompt_size_for(model) > max_chars {
+            if self.commits.pop().is_none() && self.files.pop().is_none() {
+                return false;
+            }
+        }
+        true
+    }
+    
+    /// Generate a prompt in tiny-llama's chat template
     pub fn to_prompt(&self) -> String {
+        self.to_prompt_for(&Model::TinyLlama)
+    }
+    
+    /// Generate a prompt in the chat template `model` was trained on
+    pub fn to_prompt_for(&self, model: &Model) -> String {
+        self.to_audience_prompt_for(model, Audience::UserFacing)
+    }
+    
+    /// Generate a prompt for a title written for `audience`
+    pub fn to_audience_prompt_for(&self, model: &Model, audience: Audience) -> String {
+        let (system, user) = self.prompt_messages(audience);
+        render_prompt(model, &system, &user)
+    }
+    
+    /// Generate a prompt asking `model` to revise `previous` according to `feedback`
+    pub fn to_revision_prompt_for(&self, model: &Model, previous: &str, feedback: &str) -> String {
+        let (system, user) = self.prompt_messages(Audience::UserFacing);
+        let user = format!(
+            "{}\n\nThe previous title was \"{}\". Feedback: {}\nGenerate a revised PR title that addresses the feedback.",
+            user, previous, feedback
+        );
+        render_prompt(model, &system, &user)
+    }
+    
+    /// System and user messages of the prompt, before any chat template
+    fn prompt_messages(&self, audience: Audience) -> (String, String) {
         let mut context_parts = Vec::new();
         
-        if let Some(ticket) = &self.ticket {
+        if self.tickets.len() > 1 {
+            context_parts.push(format!("Tickets: {}", self.tickets.join(", ")));
+        } else if let Some(ticket) = &self.ticket {
             context_parts.push(format!("Ticket: {}", ticket));
         }
         
+        if let Some(branch) = &self.branch {
+            context_parts.push(format!("Branch: {}", branch));
+        }
+        
         if let Some(change_type) = &self.change_type {
             context_parts.push(format!("Type: {}", change_type.as_str()));
         }
         
+        if let Some(scope) = &self.scope {
+            context_parts.push(format!("Scope: {}", scope));
+        }
+        
+        if let Some(action) = &self.action {
+            context_parts.push(format!("Action: {}", action));
+        }
+        
         if let Some(description) = &self.description {
             context_parts.push(format!("Description: {}", description));
         }
         
+        if let Some(version) = &self.release_version {
+            context_parts.push(format!("Release: {}", version));
+        }
+        
+        if self.scaffolding {
+            context_parts.push("Kind: initial project scaffolding".to_string());
+        }
+        
+        if !self.batch_tickets.is_empty() {
+            context_parts.push(format!("Kind: merge queue batch of {}", self.batch_tickets.join(", ")));
+        }
+        
+        if let Some(tag) = &self.nearest_tag {
+            context_parts.push(format!("Nearest tag: {} ({} commits since)", tag.name, tag.distance));
+        }
+        
+        if !self.files.is_empty() {
+            let paths: Vec<&str> = self.files.iter().map(|file| file.path.as_str()).collect();
+            context_parts.push(format!("Files: {}", paths.join(", ")));
+        }
+        
         let context_str = if context_parts.is_empty() {
             "No specific context".to_string()
         } else {
@@ -282,26 +1411,52 @@ impl CleanContext {
             self.commits.join("; ")
         };
         
-        format!(
-            "<|system|>
-You are a helpful assistant that generates concise, meaningful PR titles based on commit messages and branch context.
+        let (focus, priority) = match audience {
+            Audience::UserFacing => (
+                "Focus on what was accomplished, not how it was implemented",
+                "Prioritize user-facing impact over technical implementation details",
+            ),
+            Audience::Technical => (
+                "Focus on how it was implemented: the components, interfaces and techniques changed",
+                "Write for code reviewers rather than for users of the product",
+            ),
+        };
+        
+        let system = format!(
+            "You are a helpful assistant that generates concise, meaningful PR titles based on commit messages and branch context.
 
 TITLE GENERATION RULES:
 - Generate a single, clear PR title that summarizes the main changes
 - Make it specific to the actual changes
-- Focus on what was accomplished, not how it was implemented
+- {}
 - Do not include any explanations or additional text - only the title
-- Prioritize user-facing impact over technical implementation details
+- {}
 - Keep it under 72 characters
 - Use present tense and active voice
 
 Context: {}
 Changes: {}
 
-Generate a concise PR title:<|user|>
-Based on the context and changes above, generate a concise PR title that captures the main accomplishment.<|assistant|>",
-            context_str, commits_str
-        )
+Generate a concise PR title:",
+            focus, priority, context_str, commits_str
+        );
+        let user = "Based on the context and changes above, generate a concise PR title that captures the main accomplishment.";
+        
+        (system, user.to_string())
+    }
+}
+
+/// Wrap the system and user messages in `model`'s chat template
+fn render_prompt(model: &Model, system: &str, user: &str) -> String {
+    match model {
+        Model::TinyLlama => format!("<|system|>\n{}<|user|>\n{}<|assistant|>", system, user),
+        // Gemma has no system role, so the instructions open the user turn
+        Model::Gemma2b => format!(
+            "<start_of_turn>user\n{}\n\n{}<end_of_turn>\n<start_of_turn>model\n",
+            system, user
+        ),
+        Model::Phi2 => format!("Instruct: {}\n{}\nOutput:", system, user),
+        Model::Llama2_7b => format!("<s>[INST] <<SYS>>\n{}\n<</SYS>>\n\n{} [/INST]", system, user),
     }
 }
 
@@ -336,6 +1491,121 @@ mod tests {
         );
     }
     
+    #[test]
+    fn test_configured_branch_prefixes_are_stripped() {
+        let branch = "users/alice/CRU-310-fix-bottle-stuck";
+        let polluted = ContextProcessor::new().unwrap().extract_branch_context(branch);
+        assert_eq!(polluted.description.as_deref(), Some("CRU 310 fix bottle stuck"));
+        
+        let prefixes = vec!["dev/".to_string(), "users/*".to_string()];
+        let processor = ContextProcessor::new().unwrap().with_branch_prefixes(&prefixes).unwrap();
+        let context = processor.extract_branch_context(&format!("origin/{}", branch));
+        assert_eq!(context.ticket.as_deref(), Some("CRU-310"));
+        assert_eq!(context.description.as_deref(), Some("fix bottle stuck"));
+        assert_eq!(context.branch, "fix-bottle-stuck");
+        
+        let context = processor.extract_branch_context("dev/fix/bottle-stuck-on-login");
+        assert_eq!(context.change_type, Some(ChangeType::Fix));
+        assert_eq!(context.description.as_deref(), Some("bottle stuck on login"));
+        // Only whole leading segments match
+        assert_eq!(processor.extract_branch_context("feature/dev/x").branch, "feature/dev/x");
+    }
+    
+    #[test]
+    fn test_extract_lowercase_ticket_number() {
+        let processor = ContextProcessor::new().unwrap();
+        
+        assert_eq!(
+            processor.extract_ticket_number("feature/cru-310-fix-bottle-stuck"),
+            Some("CRU-310".to_string())
+        );
+    }
+    
+    #[test]
+    fn test_branch_with_several_tickets() {
+        let processor = ContextProcessor::new().unwrap();
+        
+        assert_eq!(
+            processor.extract_all_tickets("CRU-310 and cru-311, again CRU-310 but not 123"),
+            vec!["CRU-310", "CRU-311"]
+        );
+        
+        let context = processor.extract_branch_context("feature/CRU-310-CRU-311-merge-flows");
+        assert_eq!(context.ticket.as_deref(), Some("CRU-310"));
+        assert_eq!(context.tickets, vec!["CRU-310", "CRU-311"]);
+        assert_eq!(context.description.as_deref(), Some("merge flows"));
+        assert_eq!(context.branch, "feature/merge-flows");
+        
+        let clean_context = processor.create_clean_context(&context, &[]);
+        let prompt = clean_context.to_prompt();
+        assert!(prompt.contains("Context: Tickets: CRU-310, CRU-311;"), "{}", prompt);
+        assert!(!prompt.contains("Ticket: "), "{}", prompt);
+        
+        let without_base = processor.remove_base_ticket(clean_context, "CRU-310");
+        assert_eq!(without_base.ticket.as_deref(), Some("CRU-311"));
+        assert!(without_base.to_prompt().contains("Context: Ticket: CRU-311;"));
+    }
+    
+    #[test]
+    fn test_azure_ticket_in_branch_and_commit() {
+        let processor = ContextProcessor::new()
+            .unwrap()
+            .with_ticket_formats(&[TicketFormat::Jira, TicketFormat::Azure])
+            .unwrap();
+        
+        let context = processor.extract_branch_context("feature/AB#1234-login-redirect");
+        assert_eq!(context.ticket, Some("AB#1234".to_string()));
+        assert_eq!(context.branch, "feature/login-redirect");
+        
+        let commits = vec![CommitInfo {
+            hash: "abc123".to_string(),
+            message: "Fix login redirect loop ab#1234".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        }];
+        assert_eq!(processor.extract_commit_ticket(&commits), Some("AB#1234".to_string()));
+        
+        // Only configured formats are recognized
+        let jira_only = ContextProcessor::new().unwrap();
+        assert_eq!(jira_only.extract_commit_ticket(&commits), None);
+    }
+    
+    #[test]
+    fn test_description_strips_leading_bare_number() {
+        let processor = ContextProcessor::new().unwrap();
+        
+        let context = processor.extract_branch_context("feature/123-add-search");
+        assert_eq!(context.description, Some("add search".to_string()));
+        
+        let context = processor.extract_branch_context("feature/CRU-310-fix-bottle-stuck");
+        assert_eq!(context.description, Some("fix bottle stuck".to_string()));
+        
+        let context = processor.extract_branch_context("feature/upgrade-to-v2-api");
+        assert_eq!(context.description, Some("upgrade to v2 api".to_string()));
+    }
+    
+    #[test]
+    fn test_branch_in_prompt_without_ticket() {
+        let processor = ContextProcessor::new().unwrap();
+        let branch_context = processor.extract_branch_context("origin/feature/CRU-310-fix-bottle-stuck");
+        let context = processor.create_clean_context(&branch_context, &[]);
+        
+        assert_eq!(context.branch.as_deref(), Some("feature/fix-bottle-stuck"));
+        assert!(context.to_prompt().contains("Branch: feature/fix-bottle-stuck"));
+        assert_eq!(context.to_prompt().matches("CRU-310").count(), 1);
+    }
+    
+    #[test]
+    fn test_branch_context_can_be_disabled() {
+        let processor = ContextProcessor::new().unwrap().with_branch_context(false);
+        let branch_context = processor.extract_branch_context("feature/new-auth-system");
+        let context = processor.create_clean_context(&branch_context, &[]);
+        
+        assert_eq!(context.branch, None);
+        assert!(!context.to_prompt().contains("Branch:"));
+    }
+    
     #[test]
     fn test_infer_change_type() {
         let processor = ContextProcessor::new().unwrap();
@@ -354,6 +1624,321 @@ mod tests {
             processor.infer_change_type("hotfix/critical-security-patch"),
             Some(ChangeType::Hotfix)
         );
+        
+        // Only whole words count, not `fix` inside `prefix`
+        assert_eq!(processor.infer_change_type("chore/prefix-suffix-cleanup"), Some(ChangeType::Chore));
+        assert_eq!(processor.infer_change_type("address-padding"), None);
+    }
+    
+    #[test]
+    fn test_classify_commit_matches_whole_words() {
+        assert_eq!(classify_commit("fixed the crash on startup"), Some(ChangeType::Fix));
+        assert_eq!(classify_commit("Adds retries to uploads"), Some(ChangeType::Feature));
+        assert_eq!(classify_commit("pad the address field"), None);
+        assert_eq!(classify_commit("strip the suffix and prefix"), None);
+    }
+    
+    #[test]
+    fn test_summary_covers_distinct_topics() {
+        let processor = ContextProcessor::new().unwrap();
+        let topics = [
+            ["login", "session", "token"],
+            ["search", "index", "query"],
+            ["invoice", "billing", "payment"],
+        ];
+        let commits: Vec<String> = (0..30)
+            .map(|i| {
+                let words = topics[i / 10];
+                format!("tweak {} {} handling", words[i % 3], words[(i + 1) % 3])
+            })
+            .collect();
+        
+        let summary = processor.summarize_commits(&commits, 3);
+        
+        assert_eq!(summary.len(), 3);
+        for words in &topics {
+            assert!(
+                summary.iter().any(|commit| words.iter().any(|w| commit.contains(w))),
+                "no commit about {:?} in {:?}",
+                words,
+                summary
+            );
+        }
+    }
+    
+    #[test]
+    fn test_progressive_commits_cluster_into_one_subject() {
+        let processor = ContextProcessor::new().unwrap().with_cluster_threshold(Some(0.6)).unwrap();
+        let commits: Vec<CommitInfo> = [
+            "Finish search ranking feature",
+            "Continue search ranking feature",
+            "Start search ranking feature",
+            "Fix login token refresh",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, message)| CommitInfo {
+            hash: format!("c{}", i),
+            message: message.to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        })
+        .collect();
+        
+        let branch_context = processor.extract_branch_context("feature/search-ranking");
+        let context = processor.create_clean_context(&branch_context, &processor.clean_commit_messages(&commits));
+        assert_eq!(context.commits, ["search ranking feature", "Fix login token refresh"]);
+        
+        let dropped = processor.dropped_commits(&commits, &context);
+        let hashes: Vec<&str> = dropped.iter().map(|commit| commit.hash.as_str()).collect();
+        assert_eq!(hashes, ["c1", "c2"]);
+        assert!(dropped.iter().all(|commit| commit.reason == DropReason::Clustered));
+        
+        // Without a threshold nothing is merged
+        let unclustered = ContextProcessor::new().unwrap();
+        let context = unclustered.create_clean_context(&branch_context, &unclustered.clean_commit_messages(&commits));
+        assert_eq!(context.commits.len(), 4);
+        assert!(matches!(
+            ContextProcessor::new().unwrap().with_cluster_threshold(Some(1.5)),
+            Err(Error::InvalidClusterThreshold { .. })
+        ));
+    }
+    
+    #[test]
+    fn test_docs_only_detection() {
+        let processor = ContextProcessor::new().unwrap();
+        let file = |path: &str| ChangedFile {
+            path: path.to_string(),
+            lines_changed: 1,
+            added: false,
+        };
+        
+        assert!(processor.is_docs_only(&[file("README.md"), file("docs/diagram.svg")]));
+        assert!(!processor.is_docs_only(&[file("README.md"), file("src/docs.rs")]));
+        assert!(!processor.is_docs_only(&[]));
+        
+        let branch_context = processor.extract_branch_context("feature/install-guide");
+        let context = processor.create_clean_context(&branch_context, &[]);
+        let context = processor.apply_file_change_type(context, &[file("CHANGELOG.md")]);
+        assert_eq!(context.change_type, Some(ChangeType::Docs));
+    }
+    
+    #[test]
+    fn test_test_and_ci_only_detection() {
+        let processor = ContextProcessor::new().unwrap();
+        let file = |path: &str| ChangedFile {
+            path: path.to_string(),
+            lines_changed: 1,
+            added: false,
+        };
+        
+        for path in ["pkg/client_test.go", "test_client.py", "web/client.spec.ts", "src/ClientTest.java", "tests/cli.rs"] {
+            assert!(processor.is_test_only(&[file(path)]), "{}", path);
+        }
+        assert!(!processor.is_test_only(&[file("pkg/client_test.go"), file("pkg/client.go")]));
+        assert!(!processor.is_test_only(&[file("src/latest.rs"), file("src/contest.rs")]));
+        
+        assert!(processor.is_ci_only(&[file(".github/workflows/ci.yml"), file(".gitlab-ci.yml")]));
+        assert!(!processor.is_ci_only(&[file(".github/CODEOWNERS")]));
+        
+        assert_eq!(processor.file_change_type(&[file("pkg/client_test.go")]), Some(ChangeType::Test));
+        assert_eq!(processor.file_change_type(&[file(".github/workflows/release.yml")]), Some(ChangeType::Ci));
+        assert_eq!(processor.file_change_type(&[file("tests/README.md")]), Some(ChangeType::Docs));
+        assert_eq!(processor.file_change_type(&[file("src/main.rs")]), None);
+    }
+    
+    #[test]
+    fn test_branch_change_type_wins_over_commits() {
+        let processor = ContextProcessor::new().unwrap();
+        let context = CleanContext {
+            change_type: processor.extract_branch_context("feature/search-indexing").change_type,
+            commits: vec![
+                "fix flaky index test".to_string(),
+                "fix typo in query builder".to_string(),
+            ],
+            ..Default::default()
+        };
+        
+        assert_eq!(processor.suggest_change_type(&context), Some(ChangeType::Feature));
+    }
+    
+    #[test]
+    fn test_commits_decide_change_type_without_branch_signal() {
+        let processor = ContextProcessor::new().unwrap();
+        let context = CleanContext {
+            branch: Some("search-indexing".to_string()),
+            commits: vec![
+                "implement index writer".to_string(),
+                "fix flaky index test".to_string(),
+                "add query parser".to_string(),
+            ],
+            ..Default::default()
+        };
+        
+        assert_eq!(processor.suggest_change_type(&context), Some(ChangeType::Feature));
+        assert_eq!(processor.suggest_change_type(&CleanContext::default()), None);
+    }
+    
+    #[test]
+    fn test_trailer_overrides_branch_change_type() {
+        let processor = ContextProcessor::new().unwrap();
+        let branch_context = processor.extract_branch_context("feature/new-auth-system");
+        let commits = vec![CommitInfo {
+            hash: "abc123".to_string(),
+            message: "Move token checks into middleware\n\nType: refactor\nScope: api".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        }];
+        
+        let context = processor.create_clean_context(&branch_context, &[]);
+        let context = processor.apply_commit_trailers(context, &commits);
+        
+        assert_eq!(context.change_type, Some(ChangeType::Refactor));
+        assert_eq!(context.scope, Some("api".to_string()));
+    }
+    
+    #[test]
+    fn test_breaking_change_footers_become_a_section() {
+        let processor = ContextProcessor::new().unwrap();
+        let commit = |hash: &str, message: &str| CommitInfo {
+            hash: hash.to_string(),
+            message: message.to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        };
+        let commits = vec![
+            commit("c3", "fix!: reject unsigned webhooks"),
+            commit("c2", "test: cover session expiry"),
+            commit(
+                "c1",
+                "feat!: replace session cookies with tokens\n\nClients send a bearer token.\n\nBREAKING CHANGE: the `session` cookie is no longer\naccepted; log in again to get a token\nRefs: CRU-310",
+            ),
+        ];
+        
+        let breaking_changes = processor.breaking_changes(&commits);
+        assert_eq!(breaking_changes, vec![
+            "reject unsigned webhooks",
+            "the `session` cookie is no longer accepted; log in again to get a token",
+        ]);
+        assert_eq!(processor.clean_commit_messages(&commits[..1]), vec!["reject unsigned webhooks"]);
+        
+        let context = CleanContext {
+            breaking_changes,
+            ..Default::default()
+        };
+        assert_eq!(
+            context.breaking_changes_section().unwrap(),
+            "## BREAKING CHANGES\n\n- reject unsigned webhooks\n- the `session` cookie is no longer accepted; log in again to get a token"
+        );
+        assert_eq!(CleanContext::default().breaking_changes_section(), None);
+    }
+    
+    #[test]
+    fn test_commit_bodies_are_folded_into_subjects() {
+        let commits = vec![
+            CommitInfo {
+                hash: "c2".to_string(),
+                message: "fix: crash on startup\n\nThe config loader panicked on an empty file\nwhen no profile was set.\n\nMore detail nobody reads.\n\nRefs: CRU-310".to_string(),
+                author: "Test User".to_string(),
+                author_email: "test@example.com".to_string(),
+                timestamp: 0,
+            },
+            CommitInfo {
+                hash: "c1".to_string(),
+                message: "docs: explain retries.\n\nReviewed-by: Someone <someone@example.com>".to_string(),
+                author: "Test User".to_string(),
+                author_email: "test@example.com".to_string(),
+                timestamp: 0,
+            },
+            CommitInfo {
+                hash: "c0".to_string(),
+                message: "fix: keep queued uploads\n\nThe merge of both queues dropped them.".to_string(),
+                author: "Test User".to_string(),
+                author_email: "test@example.com".to_string(),
+                timestamp: 0,
+            },
+        ];
+        
+        // Without the option the whole message is cleaned, as it always was;
+        // either way only a merge or revert subject drops a commit
+        let subjects = ContextProcessor::new().unwrap().clean_commit_messages(&commits);
+        assert_eq!(subjects, vec![
+            "crash on startup The config loader panicked on an empty file when no profile was set",
+            "explain retries. Reviewed-by: Someone <someone@example.com>",
+            "keep queued uploads The merge of both queues dropped them.",
+        ]);
+        
+        let processor = ContextProcessor::new().unwrap().with_commit_bodies(true);
+        assert_eq!(processor.clean_commit_messages(&commits), vec![
+            "crash on startup. The config loader panicked on an empty file when no profile was set.",
+            "explain retries.",
+            "keep queued uploads. The merge of both queues dropped them.",
+        ]);
+    }
+    
+    #[test]
+    fn test_custom_trailer_keys() {
+        let processor = ContextProcessor::new().unwrap().with_trailer_keys(TrailerKeys {
+            change_type: vec!["Change-Kind".to_string()],
+            scope: vec!["Component".to_string()],
+        });
+        let commits = vec![CommitInfo {
+            hash: "abc123".to_string(),
+            message: "Tidy up\n\nchange-kind: docs\nType: feature".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        }];
+        
+        let context = processor.apply_commit_trailers(CleanContext::default(), &commits);
+        
+        assert_eq!(context.change_type, Some(ChangeType::Docs));
+        assert_eq!(context.scope, None);
+        
+        // Prose that happens to contain a colon is not a trailer
+        let prose = vec![CommitInfo {
+            message: "Tidy up\n\nChange-Kind: docs\nis what the old footer said\n\nSigned-off-by: Someone".to_string(),
+            ..commits[0].clone()
+        }];
+        assert_eq!(processor.apply_commit_trailers(CleanContext::default(), &prose).change_type, None);
+    }
+    
+    #[test]
+    fn test_prompt_size_matches_prompt_length() {
+        let context = CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            description: Some("fix bottle stück issue".to_string()),
+            commits: vec!["bottle stuck with remediation system".to_string()],
+            ..Default::default()
+        };
+        
+        assert_eq!(context.prompt_size(), context.to_prompt().chars().count());
+        assert!(context.prompt_size() > CleanContext::default().prompt_size());
+    }
+    
+    #[test]
+    fn test_prompt_templates_per_model() {
+        let context = CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            commits: vec!["bottle stuck with remediation system".to_string()],
+            ..Default::default()
+        };
+        
+        let tiny_llama = context.to_prompt_for(&Model::TinyLlama);
+        let gemma = context.to_prompt_for(&Model::Gemma2b);
+        assert_eq!(tiny_llama, context.to_prompt());
+        assert_ne!(gemma, tiny_llama);
+        assert!(gemma.starts_with("<start_of_turn>user\n"));
+        assert!(gemma.ends_with("<start_of_turn>model\n"));
+        assert!(!gemma.contains("<|system|>"));
+        assert!(context.to_prompt_for(&Model::Phi2).ends_with("\nOutput:"));
+        
+        for model in [Model::TinyLlama, Model::Phi2, Model::Gemma2b, Model::Llama2_7b] {
+            assert!(context.to_prompt_for(&model).contains("Changes: bottle stuck with remediation system"));
+        }
     }
     
     #[test]
@@ -370,10 +1955,57 @@ mod tests {
             Some("implement new authentication".to_string())
         );
         
+        assert_eq!(
+            processor.clean_single_commit_message("fix:\tbottle stuck\r\n\twith remediation system"),
+            Some("bottle stuck with remediation system".to_string())
+        );
+        
         // Should filter out merge messages
         assert_eq!(
             processor.clean_single_commit_message("Merge branch 'main' into feature"),
             None
         );
     }
+    
+    #[test]
+    fn test_revert_chains() {
+        let processor = ContextProcessor::new().unwrap();
+        let body = "\n\nThis reverts commit 3f9a2c1b7e.";
+        
+        let single = format!("Revert \"add retry to bottle uploads\"{}", body);
+        assert_eq!(processor.clean_commit(&single), Err(DropReason::MergeOrRevert));
+        
+        let double = format!("Revert \"Revert \"add retry to bottle uploads\"\"{}", body);
+        assert_eq!(processor.clean_single_commit_message(&double).as_deref(), Some("add retry to bottle uploads"));
+        
+        let triple = "Revert \"Revert \"Revert \"add retry to bottle uploads\"\"\"";
+        assert_eq!(processor.clean_commit(triple), Err(DropReason::MergeOrRevert));
+        
+        let reapply = "Reapply \"feat: add retry to bottle uploads\"";
+        assert_eq!(processor.clean_single_commit_message(reapply).as_deref(), Some("add retry to bottle uploads"));
+        assert_eq!(unwrap_reverts("Revert \"Reapply \"add retry\"\""), (3, "add retry"));
+    }
+    
+    #[test]
+    fn test_module_name() {
+        assert_eq!(module_name("src/auth/session.rs").as_deref(), Some("auth"));
+        assert_eq!(module_name("src/billing.rs").as_deref(), Some("billing"));
+        assert_eq!(module_name("crates/search/src/lib.rs").as_deref(), Some("search"));
+        assert_eq!(module_name("src/main.rs").as_deref(), Some("main"));
+        assert_eq!(module_name("README.md").as_deref(), Some("README"));
+    }
+    
+    #[test]
+    fn test_long_single_line_subject_is_capped() {
+        let processor = ContextProcessor::new().unwrap();
+        let rambling = "fix login redirect loop. ".to_string() + &"then also touch the session cookie handling ".repeat(45);
+        assert!(rambling.len() > 2_000);
+        
+        let subject = processor.clean_single_commit_message(&rambling).unwrap();
+        assert_eq!(subject, "fix login redirect loop");
+        
+        let words = "touch the session cookie handling ".repeat(60);
+        let subject = processor.with_max_subject_chars(40).clean_single_commit_message(&words).unwrap();
+        assert_eq!(subject, "touch the session cookie handling touch");
+    }
 }
\ No newline at end of file
@@ -1,11 +1,13 @@
 //! Error types for the PR title generator
 
+use regex::Regex;
 use std::path::PathBuf;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     #[error("Git error: {0}")]
     Git(#[from] git2::Error),
@@ -25,15 +27,27 @@ pub enum Error {
     #[error("Could not determine current branch")]
     NoBranch,
     
+    #[error("HEAD is detached at {sha}")]
+    DetachedHead { sha: String },
+    
     #[error("Branch '{branch}' not found")]
     BranchNotFound { branch: String },
     
     #[error("Base branch '{branch}' not found")]
     BaseBranchNotFound { branch: String },
     
+    #[error("Invalid range '{spec}'. Expected <base>..<branch> or <base>...<branch>")]
+    InvalidRange { spec: String },
+    
+    #[error("'{since}' is not an ancestor of '{branch}'")]
+    SinceNotAncestor { since: String, branch: String },
+    
     #[error("No commits found between '{base}' and '{branch}'")]
     NoCommits { base: String, branch: String },
     
+    #[error("No staged changes; stage files with git add first")]
+    NothingStaged,
+    
     #[error("ML model error: {message}")]
     ModelError { message: String },
     
@@ -45,4 +59,262 @@ pub enum Error {
     
     #[error("Invalid max length: {length}. Must be greater than 0")]
     InvalidMaxLength { length: usize },
+    
+    #[error("Invalid max words: {words}. Must be greater than 0")]
+    InvalidMaxWords { words: usize },
+    
+    #[error("Invalid max tickets: {tickets}. Must be greater than 0")]
+    InvalidMaxTickets { tickets: usize },
+    
+    #[error("Ellipsis '{ellipsis}' must be shorter than the {limit} character title limit")]
+    InvalidEllipsis { ellipsis: String, limit: usize },
+    
+    #[error("Prompt is {size} characters, over the {limit} character limit; narrow the branch or raise --max-prompt-chars")]
+    PromptTooLarge { size: usize, limit: usize },
+    
+    #[error("Invalid cluster threshold: {threshold}. Must be between 0.0 and 1.0")]
+    InvalidClusterThreshold { threshold: f32 },
+    
+    #[error("Invalid dominance threshold: {threshold}. Must be above 0.0 and at most 1.0")]
+    InvalidDominanceThreshold { threshold: f32 },
+    
+    #[error("Post-process command '{command}' failed: {message}")]
+    PostProcessFailed { command: String, message: String },
+    
+    #[error("Invalid post-process value '{value}'. Expected @<command>")]
+    InvalidPostProcess { value: String },
+    
+    #[error("Invalid ticket template: '{template}'. Must contain {{ticket}} and {{title}}")]
+    InvalidTicketTemplate { template: String },
+    
+    #[error("Ticket separator must not be empty")]
+    EmptyTicketSeparator,
+    
+    #[error("Invalid config file {path}: {message}")]
+    InvalidConfigFile { path: PathBuf, message: String },
+    
+    #[error("Invalid pattern file {path}: {message}")]
+    InvalidPatternFile { path: PathBuf, message: String },
+    
+    #[error("Unknown placeholder {placeholder} in template '{template}'")]
+    UnknownPlaceholder { placeholder: String, template: String },
+    
+    #[error("Invalid author pattern '{pattern}': {message}")]
+    InvalidAuthorPattern { pattern: String, message: String },
+    
+    #[error("Invalid patterns:{}", list_pattern_errors(.errors))]
+    InvalidPatterns { errors: Vec<PatternError> },
+    
+    #[error("Invalid action '{action}'. Expected one of: {expected}")]
+    InvalidAction { action: String, expected: String },
+    
+    #[error("GitHub API error: {message}")]
+    GitHubApi { message: String },
+    
+    #[error("Invalid GitHub event payload: {message}")]
+    InvalidEvent { message: String },
+    
+    #[error("Generated title '{title}' is too generic; write more descriptive commit messages")]
+    GenericTitle { title: String },
+    
+    #[error("No {formats} ticket found in branch '{branch}' or its commits")]
+    MissingTicket { branch: String, formats: String },
+    
+    #[error("No clipboard available ({message})")]
+    ClipboardUnavailable { message: String },
+    
+    #[error("Commit lint found {issues} issue(s)")]
+    LintFailed { issues: usize },
+}
+
+/// What went wrong, for matching on an `Error` without its details
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Git,
+    Io,
+    Regex,
+    Json,
+    NotGitRepository,
+    NoBranch,
+    DetachedHead,
+    BranchNotFound,
+    BaseBranchNotFound,
+    InvalidRange,
+    SinceNotAncestor,
+    NoCommits,
+    NothingStaged,
+    ModelError,
+    UnsupportedModel,
+    InvalidTemperature,
+    InvalidMaxLength,
+    InvalidMaxWords,
+    InvalidMaxTickets,
+    InvalidEllipsis,
+    PromptTooLarge,
+    InvalidClusterThreshold,
+    InvalidDominanceThreshold,
+    PostProcessFailed,
+    InvalidPostProcess,
+    InvalidTicketTemplate,
+    EmptyTicketSeparator,
+    InvalidConfigFile,
+    InvalidPatternFile,
+    UnknownPlaceholder,
+    InvalidAuthorPattern,
+    InvalidPatterns,
+    InvalidAction,
+    GitHubApi,
+    InvalidEvent,
+    GenericTitle,
+    MissingTicket,
+    ClipboardUnavailable,
+    LintFailed,
+}
+
+/// A user-supplied regex that failed to compile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    /// Option the pattern came from, e.g. `--author`
+    pub option: String,
+    pub pattern: String,
+    pub message: String,
+}
+
+fn list_pattern_errors(errors: &[PatternError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("\n  {} '{}': {}", error.option, error.pattern, error.message))
+        .collect()
+}
+
+/// Last line of a regex error, e.g. `unclosed group`, so a list of them stays readable
+fn short_regex_message(error: &regex::Error) -> String {
+    let text = error.to_string();
+    let last = text.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or(&text);
+    last.trim().trim_start_matches("error: ").to_string()
+}
+
+/// Compiles user-supplied patterns, collecting every failure so they can be
+/// reported together, or stopping at the first one when strict
+pub(crate) struct PatternCompiler {
+    strict: bool,
+    errors: Vec<PatternError>,
+}
+
+impl PatternCompiler {
+    pub(crate) fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            errors: Vec::new(),
+        }
+    }
+    
+    /// Compile `pattern`; a failure is returned right away when strict and
+    /// recorded as `None` otherwise
+    pub(crate) fn compile(&mut self, option: &str, pattern: &str) -> Result<Option<Regex>> {
+        match Regex::new(pattern) {
+            Ok(regex) => Ok(Some(regex)),
+            Err(e) => {
+                self.reject(option, pattern, &short_regex_message(&e))?;
+                Ok(None)
+            }
+        }
+    }
+    
+    /// Record a pattern that compiles but is unusable for `option`
+    pub(crate) fn reject(&mut self, option: &str, pattern: &str, message: &str) -> Result<()> {
+        self.errors.push(PatternError {
+            option: option.to_string(),
+            pattern: pattern.to_string(),
+            message: message.to_string(),
+        });
+        if self.strict {
+            self.finish()?;
+        }
+        Ok(())
+    }
+    
+    /// Fail with every recorded error, if any
+    pub(crate) fn finish(&mut self) -> Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        Err(Error::InvalidPatterns {
+            errors: std::mem::take(&mut self.errors),
+        })
+    }
+}
+
+impl Error {
+    /// The variant of this error without its details
+    ///
+    /// ```
+    /// use pr_title_generator::{error::ErrorKind, Error};
+    ///
+    /// let error = Error::NoCommits { base: "main".into(), branch: "main".into() };
+    /// assert_eq!(error.kind(), ErrorKind::NoCommits);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Git(_) => ErrorKind::Git,
+            Error::Io(_) => ErrorKind::Io,
+            Error::Regex(_) => ErrorKind::Regex,
+            Error::Json(_) => ErrorKind::Json,
+            Error::NotGitRepository { .. } => ErrorKind::NotGitRepository,
+            Error::NoBranch => ErrorKind::NoBranch,
+            Error::DetachedHead { .. } => ErrorKind::DetachedHead,
+            Error::BranchNotFound { .. } => ErrorKind::BranchNotFound,
+            Error::BaseBranchNotFound { .. } => ErrorKind::BaseBranchNotFound,
+            Error::InvalidRange { .. } => ErrorKind::InvalidRange,
+            Error::SinceNotAncestor { .. } => ErrorKind::SinceNotAncestor,
+            Error::NoCommits { .. } => ErrorKind::NoCommits,
+            Error::NothingStaged => ErrorKind::NothingStaged,
+            Error::ModelError { .. } => ErrorKind::ModelError,
+            Error::UnsupportedModel { .. } => ErrorKind::UnsupportedModel,
+            Error::InvalidTemperature { .. } => ErrorKind::InvalidTemperature,
+            Error::InvalidMaxLength { .. } => ErrorKind::InvalidMaxLength,
+            Error::InvalidMaxWords { .. } => ErrorKind::InvalidMaxWords,
+            Error::InvalidMaxTickets { .. } => ErrorKind::InvalidMaxTickets,
+            Error::InvalidEllipsis { .. } => ErrorKind::InvalidEllipsis,
+            Error::PromptTooLarge { .. } => ErrorKind::PromptTooLarge,
+            Error::InvalidClusterThreshold { .. } => ErrorKind::InvalidClusterThreshold,
+            Error::InvalidDominanceThreshold { .. } => ErrorKind::InvalidDominanceThreshold,
+            Error::PostProcessFailed { .. } => ErrorKind::PostProcessFailed,
+            Error::InvalidPostProcess { .. } => ErrorKind::InvalidPostProcess,
+            Error::InvalidTicketTemplate { .. } => ErrorKind::InvalidTicketTemplate,
+            Error::EmptyTicketSeparator => ErrorKind::EmptyTicketSeparator,
+            Error::InvalidConfigFile { .. } => ErrorKind::InvalidConfigFile,
+            Error::InvalidPatternFile { .. } => ErrorKind::InvalidPatternFile,
+            Error::UnknownPlaceholder { .. } => ErrorKind::UnknownPlaceholder,
+            Error::InvalidAuthorPattern { .. } => ErrorKind::InvalidAuthorPattern,
+            Error::InvalidPatterns { .. } => ErrorKind::InvalidPatterns,
+            Error::InvalidAction { .. } => ErrorKind::InvalidAction,
+            Error::GitHubApi { .. } => ErrorKind::GitHubApi,
+            Error::InvalidEvent { .. } => ErrorKind::InvalidEvent,
+            Error::GenericTitle { .. } => ErrorKind::GenericTitle,
+            Error::MissingTicket { .. } => ErrorKind::MissingTicket,
+            Error::ClipboardUnavailable { .. } => ErrorKind::ClipboardUnavailable,
+            Error::LintFailed { .. } => ErrorKind::LintFailed,
+        }
+    }
+    
+    /// Process exit code: 1 for user errors, 2 for system errors, 3 for rejected
+    /// titles, 4 for a missing required ticket, 5 for commit lint issues
+    pub fn exit_code(&self) -> i32 {
+        match self.kind() {
+            ErrorKind::Git
+            | ErrorKind::Io
+            | ErrorKind::Regex
+            | ErrorKind::Json
+            | ErrorKind::ModelError
+            | ErrorKind::PostProcessFailed
+            | ErrorKind::GitHubApi
+            | ErrorKind::ClipboardUnavailable => 2,
+            ErrorKind::GenericTitle => 3,
+            ErrorKind::MissingTicket => 4,
+            ErrorKind::LintFailed => 5,
+            _ => 1,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,189 @@
+//! GitHub Actions `pull_request` event payloads (`--github-event`)
+//!
+//! Actions writes the triggering event to the JSON file at
+//! `GITHUB_EVENT_PATH`. Its `pull_request` object names the head and base
+//! refs, so the branches need no detection. A workflow that fetched the PR's
+//! commits can add them as a top-level `commits` array, in the shape of the
+//! PR commits API (`sha` and `commit.message`) or of a push event (`id` and
+//! `message`), and the title is then generated without reading git at all.
+
+use crate::{git::CommitInfo, Error, Result};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Variable Actions sets to the path of the event payload
+pub const EVENT_PATH_VAR: &str = "GITHUB_EVENT_PATH";
+
+/// The pull request an Actions event is about
+#[derive(Debug, Clone)]
+pub struct PullRequestEvent {
+    pub number: u64,
+    pub head_ref: String,
+    pub base_ref: String,
+    /// Commits included in the payload, newest first like a local revwalk
+    pub commits: Vec<CommitInfo>,
+}
+
+impl PullRequestEvent {
+    /// Read the payload at `GITHUB_EVENT_PATH`
+    pub fn from_env() -> Result<Self> {
+        let path = env::var_os(EVENT_PATH_VAR).filter(|path| !path.is_empty()).ok_or_else(|| Error::InvalidEvent {
+            message: format!("{} is not set; --github-event only works in GitHub Actions", EVENT_PATH_VAR),
+        })?;
+        Self::from_path(Path::new(&path))
+    }
+
+    /// Read the payload at `path`
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let invalid = |message: String| Error::InvalidEvent {
+            message: format!("{}: {}", path.display(), message),
+        };
+        let json = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        Self::parse(&json).map_err(|e| match e {
+            Error::InvalidEvent { message } => invalid(message),
+            e => e,
+        })
+    }
+
+    /// Parse a `pull_request` or `pull_request_target` event payload
+    pub fn parse(json: &str) -> Result<Self> {
+        let invalid = |message: String| Error::InvalidEvent { message };
+        let payload: EventPayload = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        let pull_request = payload
+            .pull_request
+            .ok_or_else(|| invalid("no pull_request object; is the workflow triggered by pull_request?".to_string()))?;
+
+        // Payloads list commits oldest first
+        let mut commits: Vec<CommitInfo> = payload.commits.into_iter().map(EventCommit::into_commit_info).collect();
+        commits.reverse();
+
+        Ok(Self {
+            number: pull_request.number.or(payload.number).unwrap_or_default(),
+            head_ref: pull_request.head.ref_name,
+            base_ref: pull_request.base.ref_name,
+            commits,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct EventPayload {
+    number: Option<u64>,
+    pull_request: Option<PullRequestPayload>,
+    #[serde(default)]
+    commits: Vec<EventCommit>,
+}
+
+#[derive(Deserialize)]
+struct PullRequestPayload {
+    number: Option<u64>,
+    head: RefPayload,
+    base: RefPayload,
+}
+
+#[derive(Deserialize)]
+struct RefPayload {
+    #[serde(rename = "ref")]
+    ref_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EventCommit {
+    /// PR commits API: `{"sha": ..., "commit": {"message": ..., "author": {...}}}`
+    Api { sha: String, commit: CommitPayload },
+    /// Push event: `{"id": ..., "message": ..., "author": {...}}`
+    Push {
+        id: String,
+        #[serde(flatten)]
+        commit: CommitPayload,
+    },
+}
+
+#[derive(Deserialize)]
+struct CommitPayload {
+    message: String,
+    author: Option<AuthorPayload>,
+}
+
+#[derive(Deserialize)]
+struct AuthorPayload {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+impl EventCommit {
+    /// Commit times are left at 0, so commits keep the payload's order
+    fn into_commit_info(self) -> CommitInfo {
+        let (hash, commit) = match self {
+            EventCommit::Api { sha, commit } => (sha, commit),
+            EventCommit::Push { id, commit } => (id, commit),
+        };
+        let author = commit.author.unwrap_or(AuthorPayload { name: None, email: None });
+
+        CommitInfo {
+            hash,
+            message: commit.message,
+            author: author.name.unwrap_or_else(|| "Unknown".to_string()),
+            author_email: author.email.unwrap_or_default(),
+            timestamp: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_commits_title, GeneratorConfig};
+
+    const EVENT: &str = r#"{
+        "action": "opened",
+        "number": 42,
+        "pull_request": {
+            "number": 42,
+            "title": "WIP",
+            "head": { "ref": "feature/CRU-310-fix-bottle-stuck", "sha": "abc123" },
+            "base": { "ref": "main", "sha": "def456" },
+            "commits": 2
+        },
+        "commits": [
+            { "sha": "abc122", "commit": { "message": "fix: bottle stuck with remediation system", "author": { "name": "Test User" } } },
+            { "id": "abc123", "message": "test: cover stuck bottles", "author": { "name": "Test User", "email": "test@example.com" } }
+        ]
+    }"#;
+
+    #[tokio::test]
+    async fn test_pull_request_event_is_parsed() {
+        let event = PullRequestEvent::parse(EVENT).unwrap();
+
+        assert_eq!(event.number, 42);
+        assert_eq!(event.head_ref, "feature/CRU-310-fix-bottle-stuck");
+        assert_eq!(event.base_ref, "main");
+        let hashes: Vec<&str> = event.commits.iter().map(|commit| commit.hash.as_str()).collect();
+        assert_eq!(hashes, vec!["abc123", "abc122"]);
+        assert_eq!(event.commits[0].author_email, "test@example.com");
+
+        let config = GeneratorConfig::default().with_base_branch(event.base_ref.clone());
+        let title = generate_commits_title(&event.head_ref, &event.commits, &config).await.unwrap();
+        assert!(title.starts_with("CRU-310: "), "{}", title);
+    }
+
+    #[test]
+    fn test_malformed_events_are_rejected() {
+        let push = r#"{ "ref": "refs/heads/main", "commits": [] }"#;
+        let error = PullRequestEvent::parse(push).unwrap_err();
+        assert!(error.to_string().contains("no pull_request object"), "{}", error);
+
+        let no_base = r#"{ "pull_request": { "head": { "ref": "feature/x" } } }"#;
+        assert!(matches!(PullRequestEvent::parse(no_base), Err(Error::InvalidEvent { .. })));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("event.json");
+        fs::write(&path, "{ not json").unwrap();
+        let error = PullRequestEvent::from_path(&path).unwrap_err();
+        assert!(matches!(&error, Error::InvalidEvent { message } if message.contains("event.json: ")), "{}", error);
+        assert_eq!(error.exit_code(), 1);
+    }
+}
@@ -1,8 +1,23 @@
 //! Git repository operations and validation
 
-use crate::{Error, Result};
-use git2::{Repository, Commit, Oid};
+use crate::{CommitOrder, Error, MergeStyle, Result};
+use git2::{
+    Repository, Commit, DescribeFormatOptions, DescribeOptions, ErrorClass, Mailmap, ObjectType, Oid,
+    TreeWalkMode, TreeWalkResult,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use tracing::{debug, instrument};
+
+/// Recent first-parent base commits inspected to detect the merge style
+const MERGE_STYLE_SAMPLE: usize = 20;
+
+/// Most base commits compared against the branch for already landed commits
+const LANDED_COMMITS_LIMIT: usize = 1_000;
+
+/// `.gitattributes` markers for vendored, generated or unexported paths
+const IGNORED_PATH_ATTRIBUTES: [&str; 3] = ["export-ignore", "linguist-generated", "linguist-vendored"];
 
 /// Git repository wrapper with validation and operations
 pub struct GitRepo {
@@ -29,15 +44,53 @@ impl GitRepo {
         Ok(Self { repo, root_path })
     }
     
+    /// Open a bare repository, e.g. from a server-side hook
+    ///
+    /// Commits and changed files are read from the object database, so
+    /// everything but `staged_files` works without a working directory.
+    pub fn open_bare<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        
+        let repo = Repository::open_bare(path)
+            .map_err(|_| Error::NotGitRepository {
+                path: path.to_path_buf()
+            })?;
+        let root_path = repo.path().to_path_buf();
+        
+        Ok(Self { repo, root_path })
+    }
+    
+    /// Open the repository at `path`, falling back to [`GitRepo::open_bare`]
+    /// when it has no working directory, as in a server-side hook
+    pub fn discover<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::open(path).or_else(|_| Self::open_bare(path))
+    }
+    
     /// Get the root path of the repository
+    ///
+    /// This is the working directory, or the git directory of a bare repository.
     pub fn root_path(&self) -> &Path {
         &self.root_path
     }
     
+    /// Directory holding the repository's objects and refs, e.g. `.git`
+    pub fn git_dir(&self) -> &Path {
+        self.repo.path()
+    }
+    
     /// Get the current branch name
+    ///
+    /// Returns `Error::DetachedHead` with the commit SHA when HEAD does not
+    /// point to a branch, so callers can compare against the SHA instead.
     pub fn current_branch(&self) -> Result<String> {
         let head = self.repo.head()?;
         
+        if !head.is_branch() {
+            let sha = head.peel_to_commit()?.id().to_string();
+            return Err(Error::DetachedHead { sha });
+        }
+        
         if let Some(name) = head.shorthand() {
             Ok(name.to_string())
         } else {
@@ -45,14 +98,95 @@ impl GitRepo {
         }
     }
     
-    /// Check if a branch exists
+    /// Check if a branch exists; `HEAD` and `@` exist whenever HEAD points at a commit
     pub fn branch_exists(&self, branch_name: &str) -> bool {
+        if is_head_alias(branch_name) {
+            return self.resolve_reference(branch_name).is_ok();
+        }
         self.repo.find_branch(branch_name, git2::BranchType::Local).is_ok() ||
         self.repo.find_branch(branch_name, git2::BranchType::Remote).is_ok()
     }
     
+    /// Name of the checked-out branch for `HEAD` or `@`, or the commit SHA on
+    /// a detached HEAD, so the branch name still gives context; other
+    /// references are returned unchanged
+    pub fn branch_name_for(&self, reference: &str) -> String {
+        if !is_head_alias(reference) {
+            return reference.to_string();
+        }
+        match self.current_branch() {
+            Ok(branch) => branch,
+            Err(Error::DetachedHead { sha }) => sha,
+            Err(_) => reference.to_string(),
+        }
+    }
+    
+    /// Check if a branch, tag or revision such as a pushed SHA resolves to a commit
+    pub fn commit_exists(&self, reference: &str) -> bool {
+        self.resolve_reference(reference).is_ok()
+    }
+    
+    /// Get the URL of a remote, e.g. `origin`
+    pub fn remote_url(&self, name: &str) -> Option<String> {
+        let remote = self.repo.find_remote(name).ok()?;
+        remote.url().map(str::to_string)
+    }
+    
+    /// Find the branch that `branch` is stacked on
+    ///
+    /// A local upstream (`git branch --set-upstream-to=<parent>`) wins.
+    /// Otherwise the parent is the local branch whose tip is the nearest
+    /// ancestor of `branch`, with ties going to the alphabetically first name.
+    /// Returns `None` when no other local branch is an ancestor.
+    pub fn stack_parent(&self, branch: &str) -> Result<Option<String>> {
+        let local = self
+            .repo
+            .find_branch(branch, git2::BranchType::Local)
+            .map_err(|_| Error::BranchNotFound {
+                branch: branch.to_string(),
+            })?;
+        
+        if let Ok(upstream) = local.upstream() {
+            if upstream.get().is_branch() {
+                if let Some(name) = upstream.name()? {
+                    debug!(parent = name, "stack parent from upstream");
+                    return Ok(Some(name.to_string()));
+                }
+            }
+        }
+        
+        let branch_oid = self.resolve_reference(branch)?;
+        let mut nearest: Option<(usize, String)> = None;
+        
+        for candidate in self.repo.branches(Some(git2::BranchType::Local))? {
+            let (candidate, _) = candidate?;
+            let (Some(name), Some(tip)) = (candidate.name()?, candidate.get().target()) else {
+                continue;
+            };
+            if name == branch || tip == branch_oid || !self.repo.graph_descendant_of(branch_oid, tip)? {
+                continue;
+            }
+            
+            let (distance, _) = self.repo.graph_ahead_behind(branch_oid, tip)?;
+            let candidate = (distance, name.to_string());
+            if nearest.as_ref().is_none_or(|best| candidate < *best) {
+                nearest = Some(candidate);
+            }
+        }
+        
+        debug!(parent = ?nearest, "stack parent from ancestry");
+        Ok(nearest.map(|(_, name)| name))
+    }
+    
     /// Get commits between base and branch
     pub fn get_commits_between(&self, base: &str, branch: &str, max_commits: usize) -> Result<Vec<CommitInfo>> {
+        let filter = CommitFilter::default().with_max_commits(max_commits);
+        self.get_filtered_commits(base, branch, &filter)
+    }
+    
+    /// Get commits between base and branch, selected by `filter`
+    #[instrument(name = "git", skip(self, filter), err(level = "debug"))]
+    pub fn get_filtered_commits(&self, base: &str, branch: &str, filter: &CommitFilter) -> Result<Vec<CommitInfo>> {
         // Resolve branch references
         let branch_oid = self.resolve_reference(branch)?;
         let base_oid = self.resolve_reference(base)?;
@@ -64,25 +198,63 @@ impl GitRepo {
         let mut revwalk = self.repo.revwalk()?;
         revwalk.push(branch_oid)?;
         revwalk.hide(merge_base)?;
+        
+        // Also stop at `since`, which must be on the branch's own history
+        if let Some(since) = &filter.since {
+            let since_oid = self.resolve_reference(since)?;
+            if since_oid != branch_oid && !self.repo.graph_descendant_of(branch_oid, since_oid)? {
+                return Err(Error::SinceNotAncestor {
+                    since: since.clone(),
+                    branch: branch.to_string(),
+                });
+            }
+            revwalk.hide(since_oid)?;
+        }
         revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
         
+        let mailmap = self.repo.mailmap()?;
+        let merge_style = match filter.merge_style {
+            Some(MergeStyle::Auto) => Some(self.detect_merge_style(base)?),
+            style => style,
+        };
+        let landed = match merge_style {
+            Some(style) => self.landed_commits(base_oid, merge_base, style, &mailmap)?,
+            None => Vec::new(),
+        };
         let mut commits = Vec::new();
         
-        for (i, oid) in revwalk.enumerate() {
-            if i >= max_commits {
+        for oid in revwalk {
+            // Time order needs every commit before the cap picks the newest
+            if filter.order == CommitOrder::Topo && commits.len() >= filter.max_commits {
                 break;
             }
             
             let oid = oid?;
             let commit = self.repo.find_commit(oid)?;
             
-            // Skip merge commits
-            if commit.parent_count() > 1 {
+            // Skip merge commits unless requested
+            if commit.parent_count() > 1 && !filter.include_merges {
                 continue;
             }
             
-            commits.push(CommitInfo::from_commit(&commit));
+            let info = CommitInfo::from_commit(&commit, &mailmap)?;
+            if merge_style.is_some_and(|style| already_landed(&info, &landed, style)) {
+                debug!(hash = %info.hash, "commit already landed on base");
+                continue;
+            }
+            commits.push(info);
+        }
+        
+        if filter.order == CommitOrder::Time {
+            // Stable, so commits made in the same second keep their topological order
+            commits.sort_by_key(|commit| std::cmp::Reverse(commit.timestamp));
+            commits.truncate(filter.max_commits);
         }
+        // The cap counts every commit on the branch, so an author filter
+        // narrows the newest commits instead of reaching further back
+        commits.retain(|commit| filter.matches_author(&commit.author, &commit.author_email));
+        
+        debug!(count = commits.len(), ?merge_style, "collected commits");
         
         if commits.is_empty() {
             return Err(Error::NoCommits {
@@ -94,76 +266,654 @@ impl GitRepo {
         Ok(commits)
     }
     
-    /// Resolve a reference (branch name) to an OID
-    fn resolve_reference(&self, reference: &str) -> Result<Oid> {
-        // Try as a direct reference first
-        if let Ok(reference) = self.repo.find_reference(reference) {
-            return Ok(reference.target().unwrap_or_else(|| {
-                reference.symbolic_target_bytes()
-                    .and_then(|name| self.repo.find_reference(std::str::from_utf8(name).ok()?).ok())
-                    .and_then(|r| r.target())
-                    .unwrap()
-            }));
-        }
+    /// Commits `branch` has that `base` lacks, and commits `base` has that `branch` lacks
+    ///
+    /// A branch behind its base may need rebasing before it merges cleanly.
+    pub fn ahead_behind(&self, base: &str, branch: &str) -> Result<(usize, usize)> {
+        let branch_oid = self.resolve_reference(branch)?;
+        let base_oid = self.resolve_reference(base)?;
+        Ok(self.repo.graph_ahead_behind(branch_oid, base_oid)?)
+    }
+    
+    /// Hash of the newest commit `base` and `branch` have in common
+    pub fn merge_base(&self, base: &str, branch: &str) -> Result<String> {
+        let branch_oid = self.resolve_reference(branch)?;
+        let base_oid = self.resolve_reference(base)?;
+        Ok(self.repo.merge_base(base_oid, branch_oid)?.to_string())
+    }
+    
+    /// Guess how PRs land on `base` from its recent first-parent history
+    ///
+    /// Any merge commit means `Merge`. A linear history where at least half
+    /// the subjects end in a PR number such as `(#123)` or `(!123)` means
+    /// `Squash`, and any other linear history `Rebase`.
+    pub fn detect_merge_style(&self, base: &str) -> Result<MergeStyle> {
+        let squash_subject = Regex::new(r"\((?:#|!)\d+\)$")?;
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(self.resolve_reference(base)?)?;
+        revwalk.simplify_first_parent()?;
         
-        // Try as a branch name
-        if let Ok(branch) = self.repo.find_branch(reference, git2::BranchType::Local) {
-            if let Some(oid) = branch.get().target() {
-                return Ok(oid);
+        let mut sampled = 0;
+        let mut squashed = 0;
+        for oid in revwalk.take(MERGE_STYLE_SAMPLE) {
+            let commit = self.repo.find_commit(oid?)?;
+            if commit.parent_count() > 1 {
+                return Ok(MergeStyle::Merge);
+            }
+            
+            sampled += 1;
+            if squash_subject.is_match(commit.summary().unwrap_or("").trim_end()) {
+                squashed += 1;
             }
         }
         
-        // Try as a remote branch
-        if let Ok(branch) = self.repo.find_branch(reference, git2::BranchType::Remote) {
-            if let Some(oid) = branch.get().target() {
-                return Ok(oid);
+        let style = if squashed > 0 && squashed * 2 >= sampled {
+            MergeStyle::Squash
+        } else {
+            MergeStyle::Rebase
+        };
+        debug!(?style, sampled, squashed, "detected merge style");
+        Ok(style)
+    }
+    
+    /// The last `limit` first-parent commits of `reference`, newest first
+    ///
+    /// On a base branch these are the merge and squash commits PRs landed as.
+    pub fn recent_commits(&self, reference: &str, limit: usize) -> Result<Vec<CommitInfo>> {
+        let mailmap = self.repo.mailmap()?;
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(self.resolve_reference(reference)?)?;
+        revwalk.simplify_first_parent()?;
+        
+        let mut commits = Vec::new();
+        for oid in revwalk.take(limit) {
+            let commit = self.repo.find_commit(oid?)?;
+            commits.push(CommitInfo::from_commit(&commit, &mailmap)?);
+        }
+        Ok(commits)
+    }
+    
+    /// Up to `limit` commits reachable from `reference`, newest commit time first
+    ///
+    /// Unlike `recent_commits`, merged branches are walked too, so these are
+    /// the commits developers wrote rather than the merges that landed them.
+    #[instrument(name = "git", skip(self), err(level = "debug"))]
+    pub fn history(&self, reference: &str, limit: usize) -> Result<Vec<CommitInfo>> {
+        let mailmap = self.repo.mailmap()?;
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(self.resolve_reference(reference)?)?;
+        revwalk.set_sorting(git2::Sort::TIME)?;
+        
+        let mut commits = Vec::new();
+        for oid in revwalk.take(limit) {
+            let commit = self.repo.find_commit(oid?)?;
+            commits.push(CommitInfo::from_commit(&commit, &mailmap)?);
+        }
+        Ok(commits)
+    }
+    
+    /// Nearest tag reachable from `reference` and how many commits it is behind
+    ///
+    /// Lightweight tags count, as with `git describe --tags`; `None` when no
+    /// tag is reachable.
+    pub fn describe(&self, reference: &str) -> Result<Option<NearestTag>> {
+        let oid = self.resolve_reference(reference)?;
+        let mut options = DescribeOptions::new();
+        options.describe_tags();
+        
+        let commit = self.repo.find_commit(oid)?;
+        // libgit2 reports a commit without reachable tags as a describe error
+        let describe = match commit.as_object().describe(&options) {
+            Ok(describe) => describe,
+            Err(e) if e.class() == ErrorClass::Describe => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // An abbreviation size of zero formats just the tag name
+        let name = describe.format(Some(DescribeFormatOptions::new().abbreviated_size(0)))?;
+        let tag_oid = self.resolve_reference(&format!("refs/tags/{}", name))?;
+        let (distance, _) = self.repo.graph_ahead_behind(oid, tag_oid)?;
+        
+        Ok(Some(NearestTag { name, distance }))
+    }
+    
+    /// Base commits since the merge base that may already contain branch commits
+    ///
+    /// Only commits made after the merge base can hold branch work, so the
+    /// walk stops at the first older one, and after `LANDED_COMMITS_LIMIT`.
+    fn landed_commits(&self, base_oid: Oid, merge_base: Oid, style: MergeStyle, mailmap: &Mailmap) -> Result<Vec<CommitInfo>> {
+        if matches!(style, MergeStyle::Merge | MergeStyle::Auto) || base_oid == merge_base {
+            return Ok(Vec::new());
+        }
+        
+        let diverged_at = self.repo.find_commit(merge_base)?.time().seconds();
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(base_oid)?;
+        revwalk.hide(merge_base)?;
+        revwalk.set_sorting(git2::Sort::TIME)?;
+        
+        let mut landed = Vec::new();
+        for oid in revwalk.take(LANDED_COMMITS_LIMIT) {
+            let commit = self.repo.find_commit(oid?)?;
+            if commit.time().seconds() < diverged_at {
+                break;
             }
+            landed.push(CommitInfo::from_commit(&commit, mailmap)?);
         }
+        Ok(landed)
+    }
+    
+    /// Get the files changed on `branch` since it diverged from `base`
+    ///
+    /// Paths `.gitattributes` marks as export-ignore, generated or vendored
+    /// are left out, so a large vendored tree cannot dominate the branch.
+    #[instrument(name = "git", skip(self), err(level = "debug"))]
+    pub fn changed_files(&self, base: &str, branch: &str) -> Result<Vec<ChangedFile>> {
+        let branch_oid = self.resolve_reference(branch)?;
+        let base_oid = self.resolve_reference(base)?;
+        let merge_base = self.repo.merge_base(base_oid, branch_oid)?;
+        
+        let old_tree = self.repo.find_commit(merge_base)?.tree()?;
+        let new_tree = self.repo.find_commit(branch_oid)?.tree()?;
+        let diff = self.repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+        
+        without_ignored_paths(&self.repo, &new_tree, diff_files(&diff)?)
+    }
+    
+    /// Number of files in the commit `branch` forked from `base` at, counting
+    /// no further than `limit + 1`
+    pub fn base_file_count(&self, base: &str, branch: &str, limit: usize) -> Result<usize> {
+        let branch_oid = self.resolve_reference(branch)?;
+        let base_oid = self.resolve_reference(base)?;
+        let tree = self.repo.find_commit(self.repo.merge_base(base_oid, branch_oid)?)?.tree()?;
         
-        // Try with refs/heads/ prefix
-        let full_ref = format!("refs/heads/{}", reference);
-        if let Ok(reference) = self.repo.find_reference(&full_ref) {
-            if let Some(oid) = reference.target() {
-                return Ok(oid);
+        let mut count = 0;
+        let walked = tree.walk(TreeWalkMode::PreOrder, |_, entry| {
+            if entry.kind() == Some(ObjectType::Blob) {
+                count += 1;
             }
+            if count > limit { TreeWalkResult::Abort } else { TreeWalkResult::Ok }
+        });
+        match walked {
+            Err(_) if count > limit => Ok(count),
+            walked => walked.map(|_| count).map_err(Error::from),
+        }
+    }
+    
+    /// Get the staged and unstaged changes to tracked files, compared against HEAD
+    ///
+    /// Bare repositories have no working tree and so no uncommitted changes.
+    /// Ignored paths are left out as for `changed_files`, by the
+    /// `.gitattributes` committed at HEAD.
+    #[instrument(name = "git", skip(self), err(level = "debug"))]
+    pub fn uncommitted_files(&self) -> Result<Vec<ChangedFile>> {
+        if self.repo.is_bare() {
+            return Ok(Vec::new());
         }
+        let head_tree = self.repo.head()?.peel_to_tree()?;
+        let diff = self.repo.diff_tree_to_workdir_with_index(Some(&head_tree), None)?;
         
-        // Try with refs/remotes/origin/ prefix
-        let remote_ref = format!("refs/remotes/origin/{}", reference);
-        if let Ok(reference) = self.repo.find_reference(&remote_ref) {
-            if let Some(oid) = reference.target() {
-                return Ok(oid);
+        without_ignored_paths(&self.repo, &head_tree, diff_files(&diff)?)
+    }
+    
+    /// Whether `.gitattributes` marks `path` as export-ignore, generated or vendored
+    ///
+    /// Attributes are read from the `.gitattributes` files committed at
+    /// `reference`, so this works in bare repositories and for branches
+    /// that are not checked out.
+    pub fn is_ignored_path(&self, reference: &str, path: &str) -> Result<bool> {
+        let tree = self.repo.find_commit(self.resolve_reference(reference)?)?.tree()?;
+        Ok(AttributeRules::read(&self.repo, &tree, [path])?.is_ignored(path))
+    }
+    
+    /// Get the files staged in the index, compared against HEAD
+    ///
+    /// Before the first commit every staged file counts as new.
+    #[instrument(name = "git", skip(self), err(level = "debug"))]
+    pub fn staged_files(&self) -> Result<Vec<ChangedFile>> {
+        let head_tree = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_tree()?),
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(e.into()),
+        };
+        let index = self.repo.index()?;
+        let diff = self.repo.diff_tree_to_index(head_tree.as_ref(), Some(&index), None)?;
+        
+        diff_files(&diff)
+    }
+    
+    /// Resolve a branch, tag or revision to the OID of the commit it points at
+    fn resolve_reference(&self, reference: &str) -> Result<Oid> {
+        let reference = if is_head_alias(reference) { "HEAD" } else { reference };
+        
+        // Try as a direct reference, then as a local or remote branch name
+        let candidates = [
+            reference.to_string(),
+            format!("refs/heads/{}", reference),
+            format!("refs/remotes/origin/{}", reference),
+            format!("refs/tags/{}", reference),
+        ];
+        
+        for name in &candidates {
+            if let Ok(found) = self.repo.find_reference(name) {
+                // Peeling follows symbolic refs and annotated tags to the commit
+                return Ok(found.peel_to_commit()?.id());
             }
         }
         
+        if let Ok(branch) = self.repo.find_branch(reference, git2::BranchType::Remote) {
+            return Ok(branch.get().peel_to_commit()?.id());
+        }
+        
+        // Fall back to any revision git understands, e.g. a tag name or SHA
+        if let Ok(commit) = self.repo.revparse_single(reference).and_then(|object| object.peel_to_commit()) {
+            return Ok(commit.id());
+        }
+        
         Err(Error::BranchNotFound {
             branch: reference.to_string(),
         })
     }
 }
 
-/// Information about a single commit
+/// Whether `reference` names whatever is checked out: `HEAD` or its shorthand `@`
+fn is_head_alias(reference: &str) -> bool {
+    reference == "HEAD" || reference == "@"
+}
+
+/// Leave out the files that `.gitattributes` in `tree` marks as ignored
+fn without_ignored_paths(repo: &Repository, tree: &git2::Tree<'_>, files: Vec<ChangedFile>) -> Result<Vec<ChangedFile>> {
+    let rules = AttributeRules::read(repo, tree, files.iter().map(|file| file.path.as_str()))?;
+    let (files, ignored): (Vec<ChangedFile>, Vec<ChangedFile>) =
+        files.into_iter().partition(|file| !rules.is_ignored(&file.path));
+    if !ignored.is_empty() {
+        debug!(ignored = ignored.len(), "skipped vendored or generated files");
+    }
+    Ok(files)
+}
+
+/// One line of a `.gitattributes` file that sets or unsets an ignored-path attribute
+struct AttributeRule {
+    pattern: Regex,
+    /// Each of `IGNORED_PATH_ATTRIBUTES` the line mentions, and whether it sets it
+    attributes: Vec<(&'static str, bool)>,
+}
+
+/// The `.gitattributes` rules that apply to some paths of a tree, outermost
+/// file first, so later rules take precedence as they do for git
+struct AttributeRules {
+    rules: Vec<AttributeRule>,
+}
+
+impl AttributeRules {
+    /// Read the `.gitattributes` files of `tree` in the directories containing `paths`
+    fn read<'a>(repo: &Repository, tree: &git2::Tree<'_>, paths: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut dirs = vec![String::new()];
+        for path in paths {
+            let Some((parent, _)) = path.rsplit_once('/') else {
+                continue;
+            };
+            let mut dir = String::new();
+            for segment in parent.split('/') {
+                dir = format!("{}{}/", dir, segment);
+                if !dirs.contains(&dir) {
+                    dirs.push(dir.clone());
+                }
+            }
+        }
+        dirs.sort_by_key(|dir| dir.matches('/').count());
+        
+        let mut rules = Vec::new();
+        for dir in dirs {
+            let Ok(entry) = tree.get_path(Path::new(&format!("{}.gitattributes", dir))) else {
+                continue;
+            };
+            let Ok(blob) = entry.to_object(repo)?.into_blob() else {
+                continue;
+            };
+            for line in String::from_utf8_lossy(blob.content()).lines() {
+                if let Some(rule) = attribute_rule(&dir, line)? {
+                    rules.push(rule);
+                }
+            }
+        }
+        Ok(Self { rules })
+    }
+    
+    /// Whether the last rule matching `path` for any ignored-path attribute sets it
+    fn is_ignored(&self, path: &str) -> bool {
+        IGNORED_PATH_ATTRIBUTES.iter().any(|name| {
+            self.rules
+                .iter()
+                .rev()
+                .filter(|rule| rule.pattern.is_match(path))
+                .find_map(|rule| rule.attributes.iter().find(|(attribute, _)| attribute == name))
+                .is_some_and(|&(_, set)| set)
+        })
+    }
+}
+
+/// Parse a `.gitattributes` line of the file in `dir`, e.g. `vendor/** export-ignore`
+///
+/// Lines that mention none of `IGNORED_PATH_ATTRIBUTES` give `None`.
+fn attribute_rule(dir: &str, line: &str) -> Result<Option<AttributeRule>> {
+    let mut fields = line.split_whitespace();
+    let Some(pattern) = fields.next().filter(|pattern| !pattern.starts_with('#')) else {
+        return Ok(None);
+    };
+    let attributes: Vec<(&'static str, bool)> = fields
+        .filter_map(|field| {
+            let (name, set) = match field.split_once('=') {
+                Some((name, value)) => (name, value == "true"),
+                None => match field.strip_prefix(['-', '!']) {
+                    Some(name) => (name, false),
+                    None => (field, true),
+                },
+            };
+            IGNORED_PATH_ATTRIBUTES.iter().find(|&&known| known == name).map(|&known| (known, set))
+        })
+        .collect();
+    if attributes.is_empty() {
+        return Ok(None);
+    }
+    
+    // Without a slash a pattern matches a file name at any depth, as in `.gitignore`
+    let anchored = pattern.trim_start_matches('/');
+    let glob = glob_regex(anchored);
+    let pattern = if pattern.trim_end_matches('/').contains('/') {
+        format!("^{}{}$", regex::escape(dir), glob)
+    } else {
+        format!("^{}(?:.*/)?{}$", regex::escape(dir), glob)
+    };
+    Ok(Some(AttributeRule {
+        pattern: Regex::new(&pattern)?,
+        attributes,
+    }))
+}
+
+/// Translate a `.gitattributes` glob into a regex: `*` and `?` stay within a
+/// path segment, while `**` crosses them
+fn glob_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex
+}
+
+/// List the files in a diff with their added plus deleted line count
+fn diff_files(diff: &git2::Diff<'_>) -> Result<Vec<ChangedFile>> {
+    let mut files = Vec::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) else {
+            continue;
+        };
+        
+        let lines_changed = match git2::Patch::from_diff(diff, index)? {
+            Some(patch) => {
+                let (_, additions, deletions) = patch.line_stats()?;
+                additions + deletions
+            }
+            None => 0,
+        };
+        
+        files.push(ChangedFile {
+            path: path.to_string_lossy().to_string(),
+            lines_changed,
+            added: delta.status() == git2::Delta::Added,
+        });
+    }
+    
+    Ok(files)
+}
+
+/// Options controlling which commits are read between two refs
 #[derive(Debug, Clone)]
+pub struct CommitFilter {
+    /// Most commits read from the branch, counted before the author filters
+    pub max_commits: usize,
+    /// Order commits are read in, and so which ones `max_commits` keeps
+    pub order: CommitOrder,
+    pub include_merges: bool,
+    /// Keep only commits whose `Name <email>` author matches
+    pub author: Option<Regex>,
+    /// Drop commits whose `Name <email>` author matches
+    pub exclude_author: Option<Regex>,
+    /// Only read commits after this ref, e.g. the last pushed commit
+    pub since: Option<String>,
+    /// How PRs land on the base; `None` skips no commits as already landed
+    pub merge_style: Option<MergeStyle>,
+}
+
+impl Default for CommitFilter {
+    fn default() -> Self {
+        Self {
+            max_commits: 20,
+            order: CommitOrder::Time,
+            include_merges: false,
+            author: None,
+            exclude_author: None,
+            since: None,
+            merge_style: None,
+        }
+    }
+}
+
+impl CommitFilter {
+    pub fn with_max_commits(mut self, max_commits: usize) -> Self {
+        self.max_commits = max_commits;
+        self
+    }
+    
+    pub fn with_order(mut self, order: CommitOrder) -> Self {
+        self.order = order;
+        self
+    }
+    
+    pub fn with_include_merges(mut self, include_merges: bool) -> Self {
+        self.include_merges = include_merges;
+        self
+    }
+    
+    /// Keep only commits by authors matching the `pattern` regex
+    pub fn with_author(mut self, pattern: &str) -> Result<Self> {
+        self.author = Some(author_regex(pattern)?);
+        Ok(self)
+    }
+    
+    /// Drop commits by authors matching the `pattern` regex
+    pub fn with_exclude_author(mut self, pattern: &str) -> Result<Self> {
+        self.exclude_author = Some(author_regex(pattern)?);
+        Ok(self)
+    }
+    
+    /// Only read commits after `since`, which must be an ancestor of the branch
+    pub fn with_since(mut self, since: impl Into<String>) -> Self {
+        self.since = Some(since.into());
+        self
+    }
+    
+    pub fn with_merge_style(mut self, merge_style: MergeStyle) -> Self {
+        self.merge_style = Some(merge_style);
+        self
+    }
+    
+    /// Check an author against the include and exclude patterns
+    fn matches_author(&self, name: &str, email: &str) -> bool {
+        let author = format!("{} <{}>", name, email);
+        self.author.as_ref().is_none_or(|pattern| pattern.is_match(&author))
+            && !self.exclude_author.as_ref().is_some_and(|pattern| pattern.is_match(&author))
+    }
+}
+
+/// Whether a branch commit already reached the base under `style`
+///
+/// A squash commit lands a branch commit when its subject, minus the PR
+/// number, or one of its body's bullet lines is the branch commit's subject.
+/// A rebased copy has the same subject and author.
+fn already_landed(commit: &CommitInfo, landed: &[CommitInfo], style: MergeStyle) -> bool {
+    let subject = commit.subject();
+    let subject = subject.trim();
+    
+    landed.iter().any(|base| match style {
+        MergeStyle::Merge | MergeStyle::Auto => false,
+        MergeStyle::Squash => {
+            strip_pr_number(&base.subject()).trim() == subject
+                || base.body().lines().any(|line| {
+                    line.trim().trim_start_matches(['*', '-']).trim() == subject
+                })
+        }
+        MergeStyle::Rebase => base.subject().trim() == subject && base.author_email == commit.author_email,
+    })
+}
+
+/// `Add search (#12)` without its trailing PR number
+fn strip_pr_number(subject: &str) -> &str {
+    match subject.trim_end().rsplit_once(" (") {
+        Some((title, number)) if number.starts_with(['#', '!']) && number.ends_with(')') => title,
+        _ => subject,
+    }
+}
+
+fn author_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| Error::InvalidAuthorPattern {
+        pattern: pattern.to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// A `base..branch` revision range, mirroring `git log` syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevisionRange {
+    /// Base side of the range; `None` means HEAD
+    pub base: Option<String>,
+    /// Branch side of the range; `None` means HEAD
+    pub branch: Option<String>,
+}
+
+impl RevisionRange {
+    /// Parse `base..branch` or `base...branch`, where either side may be omitted
+    ///
+    /// Both forms compare against the merge base, so `a...b` selects the same
+    /// commits as `a..b`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || Error::InvalidRange {
+            spec: spec.to_string(),
+        };
+        
+        let (base, branch) = spec
+            .split_once("...")
+            .or_else(|| spec.split_once(".."))
+            .ok_or_else(invalid)?;
+        
+        let is_valid_side = |side: &str| !side.contains("..") && !side.contains(char::is_whitespace);
+        if (base.is_empty() && branch.is_empty()) || !is_valid_side(base) || !is_valid_side(branch) {
+            return Err(invalid());
+        }
+        
+        let side = |name: &str| (!name.is_empty()).then(|| name.to_string());
+        Ok(Self {
+            base: side(base),
+            branch: side(branch),
+        })
+    }
+}
+
+/// A file touched by a branch, with its added plus deleted line count
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedFile {
+    pub path: String,
+    pub lines_changed: usize,
+    /// Whether the branch created the file
+    #[serde(default)]
+    pub added: bool,
+}
+
+/// The tag `git describe --tags` would name, with the commits made since it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NearestTag {
+    pub name: String,
+    pub distance: usize,
+}
+
+/// Information about a single commit
+///
+/// Fields may be added, so commits read elsewhere are built with [`CommitInfo::new`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct CommitInfo {
     pub hash: String,
     pub message: String,
     pub author: String,
+    pub author_email: String,
     pub timestamp: i64,
 }
 
 impl CommitInfo {
-    fn from_commit(commit: &Commit) -> Self {
+    pub fn new(
+        hash: impl Into<String>,
+        message: impl Into<String>,
+        author: impl Into<String>,
+        author_email: impl Into<String>,
+        timestamp: i64,
+    ) -> Self {
         Self {
+            hash: hash.into(),
+            message: message.into(),
+            author: author.into(),
+            author_email: author_email.into(),
+            timestamp,
+        }
+    }
+    
+    /// Read a commit, resolving its author through `.mailmap`
+    fn from_commit(commit: &Commit, mailmap: &Mailmap) -> Result<Self> {
+        let author = commit.author_with_mailmap(mailmap)?;
+        Ok(Self {
             hash: commit.id().to_string(),
             message: commit.message().unwrap_or("").to_string(),
-            author: commit.author().name().unwrap_or("Unknown").to_string(),
+            author: author.name().unwrap_or("Unknown").to_string(),
+            author_email: author.email().unwrap_or("").to_string(),
             timestamp: commit.time().seconds(),
-        }
+        })
     }
     
-    /// Get the commit message without the hash prefix
-    pub fn clean_message(&self) -> &str {
-        self.message.trim()
+    /// Get the trimmed commit message with `\r\n` and `\r` line endings as `\n`
+    pub fn clean_message(&self) -> String {
+        self.message.replace("\r\n", "\n").replace('\r', "\n").trim().to_string()
+    }
+    
+    /// Get the first paragraph of the commit message
+    pub fn subject(&self) -> String {
+        self.split_message().0
+    }
+    
+    /// Get everything after the first blank line of the commit message
+    pub fn body(&self) -> String {
+        self.split_message().1
+    }
+    
+    fn split_message(&self) -> (String, String) {
+        let message = self.clean_message();
+        // Lines holding only spaces or tabs still separate paragraphs
+        let lines: Vec<&str> = message.lines().map(str::trim_end).collect();
+        match lines.iter().position(|line| line.is_empty()) {
+            Some(blank) => (lines[..blank].join("\n"), lines[blank..].join("\n").trim().to_string()),
+            None => (lines.join("\n"), String::new()),
+        }
     }
 }
 
@@ -229,6 +979,402 @@ mod tests {
         assert!(branch == "main" || branch == "master");
     }
     
+    fn create_branch_with_merge() -> crate::test_support::TestRepo {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/search"]);
+        test_repo.commit_file("search.rs", "index", "Add search index skeleton");
+        test_repo.git(&["checkout", "-q", "-b", "feature/search-ranking"]);
+        test_repo.commit_file("ranking.rs", "rank", "Score results by relevance");
+        test_repo.git(&["checkout", "-q", "feature/search"]);
+        test_repo.git(&["merge", "-q", "--no-ff", "-m", "Integrate search ranking subsystem", "feature/search-ranking"]);
+        test_repo
+    }
+    
+    #[test]
+    fn test_merge_commits_skipped_by_default() {
+        let test_repo = create_branch_with_merge();
+        let repo = test_repo.open();
+        
+        let commits = repo.get_filtered_commits("main", "feature/search", &CommitFilter::default()).unwrap();
+        
+        assert_eq!(commits.len(), 2);
+        assert!(commits.iter().all(|c| c.subject() != "Integrate search ranking subsystem"));
+    }
+    
+    #[test]
+    fn test_merge_commits_included_when_requested() {
+        let test_repo = create_branch_with_merge();
+        let repo = test_repo.open();
+        
+        let filter = CommitFilter::default().with_include_merges(true);
+        let commits = repo.get_filtered_commits("main", "feature/search", &filter).unwrap();
+        
+        assert_eq!(commits.len(), 3);
+        assert!(commits.iter().any(|c| c.subject() == "Integrate search ranking subsystem"));
+    }
+    
+    #[test]
+    fn test_changed_files_since_merge_base() {
+        let test_repo = create_branch_with_merge();
+        test_repo.git(&["checkout", "-q", "main"]);
+        test_repo.commit_file("main-only.txt", "later", "Unrelated change on main");
+        let repo = test_repo.open();
+        
+        let mut files = repo.changed_files("main", "feature/search").unwrap();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        
+        let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["ranking.rs", "search.rs"]);
+        assert!(files.iter().all(|file| file.lines_changed == 1));
+    }
+    
+    #[test]
+    fn test_annotated_tag_resolves_to_commit() {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["tag", "-a", "v1.0", "-m", "Release 1.0"]);
+        test_repo.git(&["checkout", "-q", "-b", "feature/search"]);
+        test_repo.commit_file("search.rs", "index", "Add search index skeleton");
+        let repo = test_repo.open();
+        
+        let tagged_commit = test_repo.git(&["rev-parse", "v1.0^{commit}"]);
+        assert_eq!(repo.resolve_reference("v1.0").unwrap().to_string(), tagged_commit.trim());
+        assert_eq!(repo.resolve_reference("refs/tags/v1.0").unwrap().to_string(), tagged_commit.trim());
+        
+        let commits = repo.get_commits_between("v1.0", "feature/search", 20).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].subject(), "Add search index skeleton");
+    }
+    
+    #[test]
+    fn test_crlf_message_split() {
+        let commit = CommitInfo {
+            hash: "abc123".to_string(),
+            message: "fix: bottle stuck\t\r\n\t\r\nRetry the valve.\r\nType: fix\r\n".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        };
+        
+        assert_eq!(commit.subject(), "fix: bottle stuck");
+        assert_eq!(commit.body(), "Retry the valve.\nType: fix");
+        assert!(!commit.clean_message().contains('\r'));
+    }
+    
+    #[test]
+    fn test_filter_commits_by_author() {
+        let test_repo = crate::test_support::TestRepo::new();
+        std::fs::write(test_repo.path().join(".mailmap"), "Build Bot <bot@example.com> <ci@example.com>\n").unwrap();
+        test_repo.git(&["checkout", "-q", "-b", "feature/search"]);
+        test_repo.commit_file("search.rs", "index", "Add search index skeleton");
+        test_repo.git(&[
+            "-c", "user.name=ci", "-c", "user.email=ci@example.com",
+            "commit", "-q", "--allow-empty", "-m", "Bump search dependency versions",
+        ]);
+        test_repo.commit_file("ranking.rs", "ranking", "Rank search results");
+        let repo = test_repo.open();
+        
+        let humans = CommitFilter::default().with_exclude_author("Bot").unwrap();
+        let commits = repo.get_filtered_commits("main", "feature/search", &humans).unwrap();
+        assert_eq!(commits.len(), 2);
+        assert!(commits.iter().all(|commit| commit.author == "Test User"));
+        
+        // The bot committed as "ci"; .mailmap resolves it to "Build Bot"
+        let bot = CommitFilter::default().with_author("^Build Bot <").unwrap().with_max_commits(2);
+        let commits = repo.get_filtered_commits("main", "feature/search", &bot).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].subject(), "Bump search dependency versions");
+        
+        // The cap picks the branch's newest commits before the author filter
+        let newest_only = bot.with_max_commits(1);
+        let result = repo.get_filtered_commits("main", "feature/search", &newest_only);
+        assert!(matches!(result, Err(Error::NoCommits { .. })));
+        
+        let nobody = CommitFilter::default().with_author("nobody@example.com").unwrap();
+        let result = repo.get_filtered_commits("main", "feature/search", &nobody);
+        assert!(matches!(result, Err(Error::NoCommits { .. })));
+        
+        assert!(matches!(
+            CommitFilter::default().with_author("(unclosed"),
+            Err(Error::InvalidAuthorPattern { .. })
+        ));
+    }
+    
+    #[test]
+    fn test_since_mid_branch_commit() {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/search"]);
+        test_repo.commit_file("search.rs", "index", "Add search index skeleton");
+        let pushed = test_repo.git(&["rev-parse", "HEAD"]);
+        test_repo.commit_file("ranking.rs", "ranking", "Rank search results");
+        test_repo.commit_file("ranking.rs", "ranking v2", "Tune ranking weights");
+        let repo = test_repo.open();
+        
+        let filter = CommitFilter::default().with_since(&pushed);
+        let commits = repo.get_filtered_commits("main", "feature/search", &filter).unwrap();
+        let subjects: Vec<String> = commits.iter().map(CommitInfo::subject).collect();
+        assert_eq!(subjects, ["Tune ranking weights", "Rank search results"]);
+        
+        test_repo.git(&["checkout", "-q", "-b", "feature/other", "main"]);
+        test_repo.commit_file("other.rs", "other", "Unrelated work");
+        let filter = CommitFilter::default().with_since("feature/other");
+        let result = repo.get_filtered_commits("main", "feature/search", &filter);
+        assert!(matches!(result, Err(Error::SinceNotAncestor { .. })));
+    }
+    
+    /// `feature/ranking` stacked on `feature/index`, whose two commits then
+    /// landed on `main` as `main_message`
+    fn create_landed_parent_branch(main_message: &str) -> crate::test_support::TestRepo {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/index"]);
+        test_repo.commit_file("index.rs", "index", "Add search index skeleton");
+        test_repo.commit_file("index.rs", "index v2", "Wire search into the UI");
+        test_repo.git(&["checkout", "-q", "-b", "feature/ranking"]);
+        test_repo.commit_file("ranking.rs", "ranking", "Rank search results");
+        test_repo.git(&["checkout", "-q", "main"]);
+        test_repo.commit_file("index.rs", "index v2", main_message);
+        test_repo
+    }
+    
+    fn subjects_with_style(repo: &GitRepo, style: MergeStyle) -> Vec<String> {
+        let filter = CommitFilter::default().with_merge_style(style);
+        let commits = repo.get_filtered_commits("main", "feature/ranking", &filter).unwrap();
+        commits.iter().map(CommitInfo::subject).collect()
+    }
+    
+    #[test]
+    fn test_squash_style_skips_squashed_parent_commits() {
+        let test_repo = create_landed_parent_branch(
+            "Add search index (#12)\n\n* Add search index skeleton\n* Wire search into the UI",
+        );
+        let repo = test_repo.open();
+        
+        assert_eq!(subjects_with_style(&repo, MergeStyle::Squash), ["Rank search results"]);
+        assert_eq!(subjects_with_style(&repo, MergeStyle::Auto), ["Rank search results"]);
+        assert_eq!(repo.detect_merge_style("main").unwrap(), MergeStyle::Squash);
+        
+        // Without a merge style nothing is skipped
+        let commits = repo.get_filtered_commits("main", "feature/ranking", &CommitFilter::default()).unwrap();
+        assert_eq!(commits.len(), 3);
+    }
+    
+    #[test]
+    fn test_rebase_style_skips_rebased_copies() {
+        let test_repo = create_landed_parent_branch("Wire search into the UI");
+        let repo = test_repo.open();
+        
+        assert_eq!(
+            subjects_with_style(&repo, MergeStyle::Rebase),
+            ["Rank search results", "Add search index skeleton"]
+        );
+        assert_eq!(repo.detect_merge_style("main").unwrap(), MergeStyle::Rebase);
+    }
+    
+    #[test]
+    fn test_merge_style_reads_everything_since_merge_base() {
+        let test_repo = create_landed_parent_branch("Add search index (#12)\n\n* Add search index skeleton");
+        test_repo.git(&["checkout", "-q", "-b", "feature/docs"]);
+        test_repo.commit_file("docs.md", "docs", "Document search");
+        test_repo.git(&["checkout", "-q", "main"]);
+        test_repo.git(&["merge", "-q", "--no-ff", "-m", "Merge branch 'feature/docs'", "feature/docs"]);
+        let repo = test_repo.open();
+        
+        assert_eq!(
+            subjects_with_style(&repo, MergeStyle::Merge),
+            ["Rank search results", "Wire search into the UI", "Add search index skeleton"]
+        );
+        assert_eq!(repo.detect_merge_style("main").unwrap(), MergeStyle::Merge);
+    }
+    
+    #[test]
+    fn test_time_order_caps_to_newest_commits() {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/search"]);
+        test_repo.commit_file_at("index.rs", "index", "Add search index", 1_000);
+        test_repo.git(&["checkout", "-q", "-b", "feature/ranking"]);
+        // A skewed clock makes the newest commit the parent of an older one
+        test_repo.commit_file_at("ranking.rs", "ranking", "Rank search results", 4_000);
+        test_repo.commit_file_at("ranking.rs", "ranking v2", "Tune ranking weights", 1_500);
+        test_repo.git(&["checkout", "-q", "feature/search"]);
+        test_repo.commit_file_at("ui.rs", "ui", "Wire search into the UI", 2_000);
+        test_repo.commit_file_at("ui.rs", "ui v2", "Style search results", 3_000);
+        test_repo.git(&["merge", "-q", "--no-ff", "-m", "Merge ranking", "feature/ranking"]);
+        let repo = test_repo.open();
+        let subjects = |order| -> Vec<String> {
+            let filter = CommitFilter::default().with_max_commits(2).with_order(order);
+            let commits = repo.get_filtered_commits("main", "feature/search", &filter).unwrap();
+            commits.iter().map(CommitInfo::subject).collect()
+        };
+        
+        assert_eq!(subjects(CommitOrder::Time), ["Rank search results", "Style search results"]);
+        assert_ne!(subjects(CommitOrder::Topo), subjects(CommitOrder::Time));
+    }
+    
+    #[test]
+    fn test_stack_parent_of_two_level_stack() {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/search-index"]);
+        test_repo.commit_file("index.rs", "index", "Add search index");
+        test_repo.git(&["checkout", "-q", "-b", "feature/search-ranking"]);
+        test_repo.commit_file("ranking.rs", "ranking", "Rank search results");
+        test_repo.commit_file("ranking.rs", "ranking v2", "Tune ranking weights");
+        let repo = test_repo.open();
+        
+        assert_eq!(repo.stack_parent("feature/search-ranking").unwrap().as_deref(), Some("feature/search-index"));
+        assert_eq!(repo.stack_parent("feature/search-index").unwrap().as_deref(), Some("main"));
+        assert_eq!(repo.stack_parent("main").unwrap(), None);
+        
+        let commits = repo.get_commits_between("feature/search-index", "feature/search-ranking", 20).unwrap();
+        assert_eq!(commits.len(), 2);
+        
+        // An explicit local upstream overrides ancestry
+        test_repo.git(&["branch", "--set-upstream-to=main", "feature/search-ranking"]);
+        assert_eq!(repo.stack_parent("feature/search-ranking").unwrap().as_deref(), Some("main"));
+    }
+    
+    #[test]
+    fn test_parse_two_dot_range() {
+        let range = RevisionRange::parse("main..feature/x").unwrap();
+        assert_eq!(range.base.as_deref(), Some("main"));
+        assert_eq!(range.branch.as_deref(), Some("feature/x"));
+    }
+    
+    #[test]
+    fn test_parse_three_dot_range() {
+        let range = RevisionRange::parse("develop...feature/CRU-310-fix").unwrap();
+        assert_eq!(range.base.as_deref(), Some("develop"));
+        assert_eq!(range.branch.as_deref(), Some("feature/CRU-310-fix"));
+        
+        let range = RevisionRange::parse("main...").unwrap();
+        assert_eq!(range.base.as_deref(), Some("main"));
+        assert_eq!(range.branch, None);
+    }
+    
+    #[test]
+    fn test_parse_invalid_range() {
+        for spec in ["main", "..", "a..b..c", "main ..feature"] {
+            assert!(
+                matches!(RevisionRange::parse(spec), Err(Error::InvalidRange { .. })),
+                "expected '{}' to be rejected",
+                spec
+            );
+        }
+    }
+    
+    #[test]
+    fn test_head_resolves_to_current_branch() {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/search"]);
+        test_repo.commit_file("search.rs", "index", "Add search index skeleton");
+        test_repo.commit_file("ranking.rs", "rank", "Score results by relevance");
+        
+        let repo = test_repo.open();
+        let hashes = |branch: &str| -> Vec<String> {
+            repo.get_commits_between("main", branch, 10).unwrap().into_iter().map(|commit| commit.hash).collect()
+        };
+        for head in ["HEAD", "@"] {
+            assert!(repo.branch_exists(head));
+            assert_eq!(repo.branch_name_for(head), "feature/search");
+            assert_eq!(hashes(head), hashes("feature/search"));
+        }
+        assert_eq!(hashes("HEAD").len(), 2);
+        assert_eq!(repo.branch_name_for("main"), "main");
+        
+        test_repo.git(&["checkout", "-q", "--detach", "HEAD"]);
+        let sha = test_repo.git(&["rev-parse", "HEAD"]);
+        assert_eq!(repo.branch_name_for("@"), sha);
+    }
+    
+    #[test]
+    fn test_detached_head() {
+        let test_repo = crate::test_support::TestRepo::new();
+        let sha = test_repo.git(&["rev-parse", "HEAD"]);
+        test_repo.git(&["checkout", "-q", "--detach", "HEAD"]);
+        
+        let result = test_repo.open().current_branch();
+        assert!(matches!(result, Err(Error::DetachedHead { sha: head_sha }) if head_sha == sha));
+    }
+    
+    #[test]
+    fn test_bare_repo_commits_between_refs() {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "Fix bottle stuck with remediation system");
+        test_repo.commit_file("test.txt", "test", "Add remediation tests");
+        
+        let bare_dir = TempDir::new().unwrap();
+        test_repo.git(&["clone", "-q", "--bare", ".", &bare_dir.path().to_string_lossy()]);
+        
+        let repo = GitRepo::open_bare(bare_dir.path()).unwrap();
+        let commits = repo.get_commits_between("main", "feature/CRU-310-fix-bottle-stuck", 10).unwrap();
+        let subjects: Vec<String> = commits.iter().map(CommitInfo::subject).collect();
+        assert_eq!(subjects, ["Add remediation tests", "Fix bottle stuck with remediation system"]);
+        
+        let files = repo.changed_files("main", "feature/CRU-310-fix-bottle-stuck").unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(matches!(GitRepo::open(bare_dir.path()), Err(Error::NotGitRepository { .. })));
+        assert_eq!(GitRepo::discover(bare_dir.path()).unwrap().root_path(), repo.root_path());
+    }
+    
+    #[test]
+    fn test_ignored_paths_come_from_the_branch_commit() {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/sdk"]);
+        std::fs::create_dir_all(test_repo.path().join("vendor/patched")).unwrap();
+        test_repo.commit_file(".gitattributes", "vendor/** export-ignore\n*.pb.rs linguist-generated=true\n", "Ignore vendored code");
+        test_repo.commit_file("vendor/patched/.gitattributes", "keep.js -export-ignore\n", "Keep the patched sdk");
+        test_repo.commit_file("vendor/sdk.js", "var sdk;", "Vendor the sdk");
+        test_repo.commit_file("vendor/patched/keep.js", "var keep;", "Patch the sdk");
+        test_repo.commit_file("api.pb.rs", "struct Api;", "Generate the api");
+        test_repo.commit_file("session.rs", "fn expire() {}", "Expire idle sessions");
+        // Neither the working tree nor the index has the branch's attributes
+        test_repo.git(&["checkout", "-q", "main"]);
+        
+        let bare_dir = TempDir::new().unwrap();
+        test_repo.git(&["clone", "-q", "--bare", ".", &bare_dir.path().to_string_lossy()]);
+        for repo in [test_repo.open(), GitRepo::open_bare(bare_dir.path()).unwrap()] {
+            let files = repo.changed_files("main", "feature/sdk").unwrap();
+            let mut paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+            paths.sort();
+            assert_eq!(paths, [".gitattributes", "session.rs", "vendor/patched/keep.js"]);
+            assert!(repo.is_ignored_path("feature/sdk", "vendor/sdk.js").unwrap());
+            assert!(!repo.is_ignored_path("main", "vendor/sdk.js").unwrap());
+        }
+    }
+    
+    #[test]
+    fn test_ahead_behind_base() {
+        let test_repo = crate::test_support::TestRepo::new();
+        let fork_point = test_repo.git(&["rev-parse", "HEAD"]);
+        test_repo.git(&["checkout", "-q", "-b", "feature/search"]);
+        test_repo.commit_file("index.rs", "index", "Add search index");
+        test_repo.commit_file("query.rs", "query", "Add search query parser");
+        test_repo.commit_file("rank.rs", "rank", "Rank search results");
+        test_repo.git(&["checkout", "-q", "main"]);
+        test_repo.commit_file("CHANGELOG.md", "## 1.2.0", "Update changelog");
+        
+        let repo = test_repo.open();
+        assert_eq!(repo.ahead_behind("main", "feature/search").unwrap(), (3, 1));
+        assert_eq!(repo.merge_base("main", "feature/search").unwrap(), fork_point.trim());
+    }
+    
+    #[test]
+    fn test_describe_nearest_tag() {
+        let test_repo = crate::test_support::TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "release/1.2"]);
+        assert_eq!(test_repo.open().describe("release/1.2").unwrap(), None);
+        
+        test_repo.git(&["tag", "v1.0.0"]);
+        test_repo.commit_file("search.rs", "index", "Add search index");
+        test_repo.git(&["tag", "-a", "v1.1.0", "-m", "Version 1.1.0"]);
+        test_repo.commit_file("CHANGELOG.md", "## 1.2.0", "Update changelog");
+        test_repo.commit_file("version.txt", "1.2.0", "Bump version to 1.2.0");
+        
+        let repo = test_repo.open();
+        let nearest = repo.describe("release/1.2").unwrap();
+        assert_eq!(nearest, Some(NearestTag { name: "v1.1.0".to_string(), distance: 2 }));
+        assert_eq!(repo.describe("main").unwrap().unwrap().name, "v1.0.0");
+    }
+    
     #[test]
     fn test_not_git_repo() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,237 @@
+//! GitHub API access for titling pull requests without a local checkout
+
+use crate::{git::CommitInfo, Error, Result};
+use serde::Deserialize;
+use std::env;
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Commits per page; GitHub caps the PR commits endpoint at 250 commits
+const COMMITS_PER_PAGE: usize = 100;
+
+/// Branch refs of a pull request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PullRequest {
+    pub number: u64,
+    pub head_ref: String,
+    pub base_ref: String,
+}
+
+/// Minimal GitHub REST client for pull request data
+///
+/// Requests block the calling thread; async callers should run them with
+/// `tokio::task::spawn_blocking`.
+pub struct GitHubClient {
+    api_url: String,
+    repository: String,
+    token: Option<String>,
+}
+
+impl GitHubClient {
+    /// Create a client for `owner/name`, authenticating with `GITHUB_TOKEN` if set
+    ///
+    /// The repository is taken from the argument, then `GITHUB_REPOSITORY`,
+    /// then the `origin` remote URL when one is given.
+    pub fn from_env(repository: Option<&str>, origin_url: Option<&str>) -> Result<Self> {
+        let repository = repository
+            .map(str::to_string)
+            .or_else(|| env::var("GITHUB_REPOSITORY").ok().filter(|repo| !repo.is_empty()))
+            .or_else(|| origin_url.and_then(parse_repository_from_url))
+            .ok_or_else(|| Error::GitHubApi {
+                message: "could not detect repository; pass --repo owner/name".to_string(),
+            })?;
+
+        Ok(Self {
+            api_url: env::var("GITHUB_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string()),
+            repository,
+            token: env::var("GITHUB_TOKEN").ok().filter(|token| !token.is_empty()),
+        })
+    }
+
+    /// Fetch the head and base refs of a pull request
+    pub fn pull_request(&self, number: u64) -> Result<PullRequest> {
+        let url = format!("{}/repos/{}/pulls/{}", self.api_url, self.repository, number);
+        let response: PullRequestResponse = self.get_json(&url)?;
+
+        Ok(PullRequest {
+            number,
+            head_ref: response.head.ref_name,
+            base_ref: response.base.ref_name,
+        })
+    }
+
+    /// Fetch the commits of a pull request, newest first like a local revwalk
+    pub fn pull_request_commits(&self, number: u64, max_commits: usize) -> Result<Vec<CommitInfo>> {
+        let mut commits = Vec::new();
+
+        for page in 1.. {
+            let url = format!(
+                "{}/repos/{}/pulls/{}/commits?per_page={}&page={}",
+                self.api_url, self.repository, number, COMMITS_PER_PAGE, page
+            );
+            let batch: Vec<CommitResponse> = self.get_json(&url)?;
+            let is_last_page = batch.len() < COMMITS_PER_PAGE;
+            commits.extend(batch.into_iter().map(CommitResponse::into_commit_info));
+
+            if is_last_page {
+                break;
+            }
+        }
+
+        commits.reverse();
+        commits.truncate(max_commits);
+        Ok(commits)
+    }
+
+    fn get_json<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<T> {
+        let mut request = ureq::get(url)
+            .set("Accept", "application/vnd.github+json")
+            .set("User-Agent", "generate-pr-title");
+
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+
+        let response = request.call().map_err(|e| Error::GitHubApi {
+            message: match e {
+                ureq::Error::Status(401, _) => "authentication failed; check GITHUB_TOKEN".to_string(),
+                ureq::Error::Status(code, _) => format!("request to {} failed with status {}", url, code),
+                ureq::Error::Transport(transport) => transport.to_string(),
+            },
+        })?;
+
+        response.into_json().map_err(|e| Error::GitHubApi {
+            message: format!("invalid response from {}: {}", url, e),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct PullRequestResponse {
+    head: RefResponse,
+    base: RefResponse,
+}
+
+#[derive(Deserialize)]
+struct RefResponse {
+    #[serde(rename = "ref")]
+    ref_name: String,
+}
+
+#[derive(Deserialize)]
+struct CommitResponse {
+    sha: String,
+    commit: CommitDetails,
+}
+
+#[derive(Deserialize)]
+struct CommitDetails {
+    message: String,
+    author: Option<CommitAuthor>,
+}
+
+#[derive(Deserialize)]
+struct CommitAuthor {
+    name: Option<String>,
+    email: Option<String>,
+    date: Option<String>,
+}
+
+impl CommitResponse {
+    fn into_commit_info(self) -> CommitInfo {
+        let author = self.commit.author;
+
+        CommitInfo {
+            hash: self.sha,
+            message: self.commit.message,
+            author: author
+                .as_ref()
+                .and_then(|a| a.name.clone())
+                .unwrap_or_else(|| "Unknown".to_string()),
+            author_email: author
+                .as_ref()
+                .and_then(|a| a.email.clone())
+                .unwrap_or_default(),
+            timestamp: author
+                .as_ref()
+                .and_then(|a| a.date.as_deref())
+                .and_then(parse_timestamp)
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// Extract `owner/name` from a GitHub SSH or HTTPS remote URL
+fn parse_repository_from_url(url: &str) -> Option<String> {
+    let path = url
+        .strip_prefix("git@github.com:")
+        .or_else(|| url.split_once("github.com/").map(|(_, path)| path))?;
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+
+    let (owner, name) = path.split_once('/')?;
+    (!owner.is_empty() && !name.is_empty() && !name.contains('/')).then(|| path.to_string())
+}
+
+/// Parse a UTC `YYYY-MM-DDTHH:MM:SSZ` timestamp into Unix seconds
+fn parse_timestamp(value: &str) -> Option<i64> {
+    let (date, time) = value.trim_end_matches('Z').split_once('T')?;
+    let mut date_parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let mut time_parts = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+
+    let (year, month, day) = (date_parts.next()??, date_parts.next()??, date_parts.next()??);
+    let (hour, minute, second) = (time_parts.next()??, time_parts.next()??, time_parts.next()??);
+
+    // Days since the Unix epoch for a proleptic Gregorian date
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repository_from_remote_urls() {
+        assert_eq!(
+            parse_repository_from_url("git@github.com:alessandropac96/pr-title-generator.git"),
+            Some("alessandropac96/pr-title-generator".to_string())
+        );
+        assert_eq!(
+            parse_repository_from_url("https://github.com/alessandropac96/pr-title-generator"),
+            Some("alessandropac96/pr-title-generator".to_string())
+        );
+        assert_eq!(parse_repository_from_url("https://gitlab.com/group/project.git"), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("2024-02-29T12:30:15Z"), Some(1_709_209_815));
+        assert_eq!(parse_timestamp("not a date"), None);
+    }
+
+    #[test]
+    fn test_commit_response_conversion() {
+        let json = r#"[{
+            "sha": "abc123",
+            "commit": {
+                "message": "fix: bottle stuck with remediation system",
+                "author": { "name": "Test User", "date": "1970-01-01T00:01:00Z" }
+            }
+        }]"#;
+
+        let commits: Vec<CommitResponse> = serde_json::from_str(json).unwrap();
+        let commit = commits.into_iter().next().unwrap().into_commit_info();
+
+        assert_eq!(commit.hash, "abc123");
+        assert_eq!(commit.author, "Test User");
+        assert_eq!(commit.timestamp, 60);
+        assert_eq!(commit.subject(), "fix: bottle stuck with remediation system");
+    }
+}
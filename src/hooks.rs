@@ -0,0 +1,84 @@
+//! External commands that transform the generated title
+
+use crate::{Error, Result};
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// Pipe the title through a shell command and return its single-line output
+pub(crate) fn run_post_process_command(command: &str, title: &str) -> Result<String> {
+    let failed = |message: String| Error::PostProcessFailed {
+        command: command.to_string(),
+        message,
+    };
+
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command may exit without reading the title; its exit status and
+        // output still decide
+        match stdin.write_all(title.as_bytes()) {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(failed(e.to_string())),
+            _ => {}
+        }
+    }
+
+    let output = child.wait_with_output().map_err(|e| failed(e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(failed(format!("exited with {}: {}", output.status, stderr)));
+    }
+
+    single_line(&String::from_utf8_lossy(&output.stdout)).map_err(failed)
+}
+
+/// Accept output with exactly one non-empty line
+fn single_line(output: &str) -> std::result::Result<String, String> {
+    let lines: Vec<&str> = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    match lines.as_slice() {
+        [line] => Ok(line.to_string()),
+        [] => Err("produced no output".to_string()),
+        _ => Err(format!("produced {} lines, expected one", lines.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_transforms_title() {
+        let title = run_post_process_command("tr a-z A-Z", "fix login crash").unwrap();
+        assert_eq!(title, "FIX LOGIN CRASH");
+    }
+
+    #[test]
+    fn test_failing_command_is_reported() {
+        let result = run_post_process_command("exit 3", "fix login crash");
+        assert!(matches!(result, Err(Error::PostProcessFailed { .. })));
+    }
+
+    #[test]
+    fn test_command_may_ignore_the_title() {
+        // Longer than a pipe buffer, so the write outlives the command
+        let title = "fix login crash ".repeat(16_384);
+        let result = run_post_process_command("echo '[team] fixed'", &title).unwrap();
+        assert_eq!(result, "[team] fixed");
+    }
+
+    #[test]
+    fn test_multi_line_output_is_rejected() {
+        let result = run_post_process_command("printf 'one\\ntwo\\n'", "fix login crash");
+        assert!(matches!(result, Err(Error::PostProcessFailed { .. })));
+    }
+}
@@ -3,32 +3,426 @@
 //! A machine learning-based library for generating meaningful PR titles
 //! from commit messages and branch context.
 
+pub mod batch;
+pub mod cache;
 pub mod cli;
+pub mod commit;
+pub mod config;
 pub mod git;
+pub mod lint;
 pub mod context;
+pub mod event;
 pub mod ml;
 pub mod error;
+pub mod patterns;
+pub mod output;
+pub mod pipeline;
+pub mod tickets;
+pub mod vocab;
+mod hooks;
 
-pub use error::{Error, Result};
+#[cfg(feature = "http")]
+pub mod github;
+
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
+
+#[cfg(feature = "otel")]
+pub mod telemetry;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+
+#[cfg(test)]
+mod test_support;
+
+pub use error::{Error, ErrorKind, PatternError, Result};
+pub use output::{ContextDump, Explanation, OutputPayload};
+pub use pipeline::{
+    clean_branch_commits, dump_branch_context, explain_branch_title, explain_commits_title, find_merged_ticket,
+    generate_branch_output, generate_branch_title, generate_branch_title_with_progress, generate_commits_output,
+    generate_commits_title, generate_context_title, ProgressCallback, ProgressEvent,
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use context::ChangeType;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tickets::{TicketFormat, TicketPrefixes};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Where the detected ticket is placed in the generated title
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TicketPlacement {
+    /// `CRU-310: Fix login crash`
+    #[default]
+    Prefix,
+    /// `Fix login crash (CRU-310)`
+    Suffix,
+    /// Leave the ticket out of the title
+    None,
+}
+
+/// Supported ML models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Model {
+    #[default]
+    #[value(name = "tiny-llama")]
+    TinyLlama,
+    #[value(name = "phi-2")]
+    Phi2,
+    #[value(name = "gemma-2b")]
+    Gemma2b,
+    #[value(name = "llama-2-7b")]
+    Llama2_7b,
+}
+
+impl Model {
+    /// Look up a model by its `--model` name, e.g. `gemma-2b`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tiny-llama" => Some(Model::TinyLlama),
+            "phi-2" => Some(Model::Phi2),
+            "gemma-2b" => Some(Model::Gemma2b),
+            "llama-2-7b" => Some(Model::Llama2_7b),
+            _ => None,
+        }
+    }
+    
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Model::TinyLlama => "tiny-llama",
+            Model::Phi2 => "phi-2",
+            Model::Gemma2b => "gemma-2b",
+            Model::Llama2_7b => "llama-2-7b",
+        }
+    }
+}
+
+/// What happens when the prompt is longer than `max_prompt_chars`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PromptPolicy {
+    /// Drop the oldest commits, then changed files, until the prompt fits
+    #[default]
+    Trim,
+    /// Fail with `Error::PromptTooLarge`
+    Error,
+}
+
+/// Order of the alternative titles in `OutputPayload::candidates`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RankBy {
+    /// Highest `score_title` score first
+    #[default]
+    Score,
+    /// Shortest first, not counting the ticket or punctuation
+    Length,
+    /// Largest share of non-generic words first
+    Specificity,
+}
+
+/// How title lengths are measured against `max_length` and the 72-column limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LengthMetric {
+    /// Unicode scalar values
+    #[default]
+    Chars,
+    /// UTF-8 bytes, for systems that limit the encoded size
+    Bytes,
+    /// Display columns, so CJK and other wide characters count twice
+    Width,
+}
+
+impl LengthMetric {
+    /// Length of `text` in this metric
+    pub fn measure(self, text: &str) -> usize {
+        match self {
+            LengthMetric::Chars => text.chars().count(),
+            LengthMetric::Bytes => text.len(),
+            LengthMetric::Width => UnicodeWidthStr::width(text),
+        }
+    }
+    
+    /// Length of a single character in this metric
+    pub fn measure_char(self, c: char) -> usize {
+        match self {
+            LengthMetric::Chars => 1,
+            LengthMetric::Bytes => c.len_utf8(),
+            LengthMetric::Width => UnicodeWidthChar::width(c).unwrap_or(0),
+        }
+    }
+}
+
+/// How pull requests land on the base branch, which decides what counts as
+/// already merged when reading a branch's commits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeStyle {
+    /// Merge commits keep the branch's own commits; read everything since the merge base
+    Merge,
+    /// Each PR becomes one base commit; skip branch commits it summarizes
+    Squash,
+    /// Commits are replayed onto the base; skip branch commits copied there
+    Rebase,
+    /// Detect the style from the base branch's recent first-parent history
+    Auto,
+}
+
+/// Order commits are read in, which decides what `max_commits` keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommitOrder {
+    /// Newest commit time first, so the cap keeps the most recent commits
+    #[default]
+    Time,
+    /// Children before parents, one side of a merge at a time; the cap may
+    /// then keep older commits from one side over newer ones from the other
+    Topo,
+}
+
+/// How titles join several items, such as the tickets of a merge-queue batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListStyle {
+    /// Repeat the separator before the final `and`: `a, b, and c` rather than `a, b and c`
+    pub oxford_comma: bool,
+    /// Text between items, e.g. `", "` or `"; "`
+    pub separator: String,
+}
+
+impl Default for ListStyle {
+    fn default() -> Self {
+        Self {
+            oxford_comma: true,
+            separator: ", ".to_string(),
+        }
+    }
+}
+
+impl ListStyle {
+    /// `a`, `a and b`, or `a, b, and c`
+    pub fn join(&self, items: &[impl AsRef<str>]) -> String {
+        match items {
+            [] => String::new(),
+            [item] => item.as_ref().to_string(),
+            [first, second] => format!("{} and {}", first.as_ref(), second.as_ref()),
+            [rest @ .., last] => {
+                let rest: Vec<&str> = rest.iter().map(AsRef::as_ref).collect();
+                let conjunction = if self.oxford_comma {
+                    format!("{} and ", self.separator.trim_end())
+                } else {
+                    " and ".to_string()
+                };
+                format!("{}{}{}", rest.join(&self.separator), conjunction, last.as_ref())
+            }
+        }
+    }
+}
 
 /// Configuration for the PR title generator
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct GeneratorConfig {
     pub model_name: String,
+    /// Weight quantization model backends should load, e.g. `q4_0`
+    pub quantization: Option<String>,
+    /// `TitleBackend::name` titles must come from, e.g. `pattern`; no other
+    /// backend is used and there is no fallback to patterns
+    pub backend: Option<String>,
+    /// Use the pattern backend when the model backend fails or none is available
+    pub pattern_fallback: bool,
+    /// Sampling temperature for model backends
+    #[serde(serialize_with = "serialize_short_f32")]
     pub temperature: f32,
+    /// Seed of the random choices, such as which of an action's templates the
+    /// pattern backend uses; `ml::DEFAULT_SEED` when unset
+    pub seed: Option<u64>,
     pub max_length: usize,
+    /// Unit of `max_length`, the per-type limits and the 72-column limit
+    pub length_metric: LengthMetric,
+    /// Word limit applied with the length limit, not counting the ticket
+    pub max_words: Option<usize>,
+    /// Length limits that replace `max_length` for specific change types
+    pub max_length_by_type: BTreeMap<ChangeType, usize>,
+    /// Marker for a truncated title, e.g. `"..."` or `"…"`; counts against the length limit
+    pub ellipsis: String,
     pub max_commits: usize,
+    pub commit_order: CommitOrder,
+    /// Most representative commits kept for the prompt
+    pub summary_size: usize,
+    /// Longest commit subject in characters; longer ones are cut to their first sentence
+    pub max_subject_chars: usize,
+    /// Longest prompt in characters, as measured by `CleanContext::prompt_size_for` in the configured model's template; unlimited when unset
+    pub max_prompt_chars: Option<usize>,
+    pub prompt_policy: PromptPolicy,
+    /// Keyword overlap at which consecutive commits are merged into one subject;
+    /// off when unset
+    #[serde(serialize_with = "serialize_short_optional_f32")]
+    pub cluster_threshold: Option<f32>,
+    /// Share of changed lines at which one file's module becomes the title's
+    /// domain, e.g. 0.6; off when unset
+    #[serde(serialize_with = "serialize_short_optional_f32")]
+    pub emphasize_dominant: Option<f32>,
+    pub include_merges: bool,
+    /// Look up the branch's nearest tag, as `git describe --tags` would, and
+    /// pass it to the prompt as a hint at the version being released
+    pub include_nearest_tag: bool,
+    /// Regex an author's `Name <email>` must match for their commits to be read
+    pub author: Option<String>,
+    /// Regex for authors whose commits are skipped, e.g. bots
+    pub exclude_author: Option<String>,
+    /// Only read commits after this ref on the branch, instead of all since the merge base
+    pub since: Option<String>,
+    /// Stop at the first invalid user pattern instead of reporting them all together
+    pub strict_patterns: bool,
+    /// How the base branch merges PRs; unset, no commits are skipped as already landed
+    pub merge_style: Option<MergeStyle>,
+    /// Base branch of commits read elsewhere, e.g. a PR's target; a ticket in
+    /// its name is not attributed to the PR. The branch functions use their `base`
+    pub base_branch: Option<String>,
+    /// Subject regex of release commits, with a `version` group; defaults to
+    /// `context::DEFAULT_RELEASE_PATTERN`
+    pub release_pattern: Option<String>,
+    /// Pass the cleaned branch name to the prompt
+    pub include_branch_context: bool,
+    /// Branch name prefixes stripped besides the built-in ones, e.g. `dev/` or `users/*/`
+    pub branch_prefixes: Vec<String>,
+    /// Title branches that bootstrap a project as `Initialize project` or `Scaffold ...`
+    pub detect_scaffolding: bool,
+    /// Type branches that only touch documentation, tests or CI configuration
+    /// as `Docs`, `Test` or `Ci`, which needs a diff of the branch
+    pub detect_file_types: bool,
+    /// Title merge-queue branches, such as `gh-readonly-queue/main/pr-44`, that
+    /// batch PRs for several tickets as `Merge 3 PRs: ...`
+    pub detect_batches: bool,
+    /// List up to this many of the branch's tickets in front of the title,
+    /// e.g. `CRU-310, CRU-311 +2 more: ...`, instead of only the first; replaces
+    /// batch detection
+    pub max_tickets: Option<usize>,
+    /// Learn the domain vocabulary of the base branch's history into `domain_vocabulary`
+    pub learn_vocab: bool,
+    /// Repository-specific domains, e.g. `bottler`, recognized alongside the built-in ones
+    pub domain_vocabulary: Vec<String>,
+    /// Set by `--verbose`; diagnostics are `tracing` events filtered by the subscriber
     pub verbose: bool,
+    /// Verb that overrides the inferred action, e.g. `remove`
+    pub action: Option<String>,
+    /// Name the changed file in the title when one file dominates the branch
+    pub use_filenames: bool,
+    /// Reject titles that are too generic instead of returning them
+    pub fail_on_generic: bool,
+    /// Use the subject of a branch's only commit as it is when it already
+    /// makes a good title, instead of generating one
+    pub prefer_commit_subject: bool,
+    /// Fail when neither the branch nor its commits name a ticket
+    pub require_ticket: bool,
+    /// Also generate a technical and a user-facing title for `OutputPayload::titles`
+    pub audience_titles: bool,
+    /// Order of the candidate titles
+    pub rank_by: RankBy,
+    /// Uppercase the first letter of the title
+    pub capitalize: bool,
+    /// Verbs such as `migrate` that replace the change type's action when they open most commits
+    pub strong_verbs: Vec<String>,
+    /// Words always spelled exactly this way, e.g. `iOS` or `GitHub`, whatever `capitalize` says
+    pub case_exceptions: Vec<String>,
+    /// Use the lowercased ticket project key as the scope when none is found
+    pub scope_from_ticket: bool,
+    pub ticket_placement: TicketPlacement,
+    /// Remove mentions of the ticket from the generated text, e.g. `fix cru-310 login`,
+    /// so it only appears where `ticket_placement` puts it
+    pub dedupe_ticket: bool,
+    /// End the title with the primary issue reference, e.g. `(#1234)`, instead of stripping it
+    pub keep_refs: bool,
+    /// Follow commit subjects with the first paragraph of their bodies in the context
+    pub commit_bodies: bool,
+    /// Ticket formats recognized in branch names and commits, tried in order
+    pub ticket_formats: Vec<TicketFormat>,
+    /// Also recognize Jira keys with non-ASCII letters, e.g. `Ä-123`
+    pub unicode_tickets: bool,
+    /// Jira project keys counted as tickets
+    pub ticket_prefixes: TicketPrefixes,
+    /// Directory for cached titles; caching is off when unset
+    pub cache_dir: Option<PathBuf>,
+    /// TOML file of action templates for the pattern backend
+    pub pattern_file: Option<PathBuf>,
+    /// Text between a prefixed ticket and the title, e.g. `": "` or `" - "`
+    pub ticket_separator: String,
+    /// How titles listing several items join them
+    pub list_style: ListStyle,
+    /// Layout for ticket and title, using `{ticket}`, `{separator}` and `{title}` placeholders
+    pub ticket_template: Option<String>,
+    /// Shell command that receives the final title on stdin and prints a replacement
+    pub post_process_command: Option<String>,
 }
 
 impl Default for GeneratorConfig {
     fn default() -> Self {
         Self {
             model_name: "tiny-llama".to_string(),
+            quantization: None,
+            backend: None,
+            pattern_fallback: true,
             temperature: 0.7,
+            seed: None,
             max_length: 50,
+            length_metric: LengthMetric::Chars,
+            max_words: None,
+            max_length_by_type: BTreeMap::new(),
+            ellipsis: "...".to_string(),
             max_commits: 20,
+            commit_order: CommitOrder::Time,
+            summary_size: context::DEFAULT_SUMMARY_SIZE,
+            max_subject_chars: context::DEFAULT_MAX_SUBJECT_CHARS,
+            max_prompt_chars: None,
+            prompt_policy: PromptPolicy::Trim,
+            cluster_threshold: None,
+            emphasize_dominant: None,
+            include_merges: false,
+            include_nearest_tag: false,
+            author: None,
+            exclude_author: None,
+            since: None,
+            strict_patterns: false,
+            merge_style: None,
+            base_branch: None,
+            release_pattern: None,
+            include_branch_context: true,
+            branch_prefixes: Vec::new(),
+            detect_scaffolding: true,
+            detect_file_types: true,
+            detect_batches: true,
+            max_tickets: None,
+            learn_vocab: false,
+            domain_vocabulary: Vec::new(),
             verbose: false,
+            action: None,
+            use_filenames: false,
+            fail_on_generic: false,
+            prefer_commit_subject: false,
+            require_ticket: false,
+            audience_titles: false,
+            rank_by: RankBy::Score,
+            capitalize: true,
+            strong_verbs: ml::DEFAULT_STRONG_VERBS.iter().map(|verb| verb.to_string()).collect(),
+            case_exceptions: ml::DEFAULT_CASE_EXCEPTIONS.iter().map(|word| word.to_string()).collect(),
+            scope_from_ticket: false,
+            ticket_placement: TicketPlacement::Prefix,
+            dedupe_ticket: false,
+            keep_refs: false,
+            commit_bodies: false,
+            ticket_formats: vec![TicketFormat::Jira],
+            unicode_tickets: false,
+            ticket_prefixes: TicketPrefixes::default(),
+            cache_dir: None,
+            pattern_file: None,
+            ticket_separator: ": ".to_string(),
+            list_style: ListStyle::default(),
+            ticket_template: None,
+            post_process_command: None,
         }
     }
 }
@@ -39,23 +433,320 @@ impl GeneratorConfig {
         self
     }
     
+    pub fn with_quantization(mut self, quantization: impl Into<String>) -> Self {
+        self.quantization = Some(quantization.into());
+        self
+    }
+    
+    pub fn with_backend(mut self, backend: impl Into<String>) -> Self {
+        self.backend = Some(backend.into());
+        self
+    }
+    
+    pub fn with_pattern_fallback(mut self, pattern_fallback: bool) -> Self {
+        self.pattern_fallback = pattern_fallback;
+        self
+    }
+    
     pub fn with_temperature(mut self, temperature: f32) -> Self {
         self.temperature = temperature;
         self
     }
     
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    
     pub fn with_max_length(mut self, max_length: usize) -> Self {
         self.max_length = max_length;
         self
     }
     
+    pub fn with_ellipsis(mut self, ellipsis: impl Into<String>) -> Self {
+        self.ellipsis = ellipsis.into();
+        self
+    }
+    
+    pub fn with_max_words(mut self, max_words: usize) -> Self {
+        self.max_words = Some(max_words);
+        self
+    }
+    
+    pub fn with_length_metric(mut self, length_metric: LengthMetric) -> Self {
+        self.length_metric = length_metric;
+        self
+    }
+    
+    pub fn with_type_max_length(mut self, change_type: ChangeType, max_length: usize) -> Self {
+        self.max_length_by_type.insert(change_type, max_length);
+        self
+    }
+    
     pub fn with_max_commits(mut self, max_commits: usize) -> Self {
         self.max_commits = max_commits;
         self
     }
     
+    pub fn with_commit_order(mut self, commit_order: CommitOrder) -> Self {
+        self.commit_order = commit_order;
+        self
+    }
+    
+    pub fn with_summary_size(mut self, summary_size: usize) -> Self {
+        self.summary_size = summary_size;
+        self
+    }
+    
+    pub fn with_max_subject_chars(mut self, max_subject_chars: usize) -> Self {
+        self.max_subject_chars = max_subject_chars;
+        self
+    }
+    
+    pub fn with_max_prompt_chars(mut self, max_prompt_chars: usize) -> Self {
+        self.max_prompt_chars = Some(max_prompt_chars);
+        self
+    }
+    
+    pub fn with_prompt_policy(mut self, policy: PromptPolicy) -> Self {
+        self.prompt_policy = policy;
+        self
+    }
+    
+    pub fn with_cluster_threshold(mut self, threshold: f32) -> Self {
+        self.cluster_threshold = Some(threshold);
+        self
+    }
+    
+    pub fn with_emphasize_dominant(mut self, share: f32) -> Self {
+        self.emphasize_dominant = Some(share);
+        self
+    }
+    
+    pub fn with_include_merges(mut self, include_merges: bool) -> Self {
+        self.include_merges = include_merges;
+        self
+    }
+    
+    pub fn with_nearest_tag(mut self, include_nearest_tag: bool) -> Self {
+        self.include_nearest_tag = include_nearest_tag;
+        self
+    }
+    
+    pub fn with_author(mut self, pattern: impl Into<String>) -> Self {
+        self.author = Some(pattern.into());
+        self
+    }
+    
+    pub fn with_exclude_author(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude_author = Some(pattern.into());
+        self
+    }
+    
+    pub fn with_since(mut self, since: impl Into<String>) -> Self {
+        self.since = Some(since.into());
+        self
+    }
+    
+    pub fn with_strict_patterns(mut self, strict_patterns: bool) -> Self {
+        self.strict_patterns = strict_patterns;
+        self
+    }
+    
+    pub fn with_merge_style(mut self, merge_style: MergeStyle) -> Self {
+        self.merge_style = Some(merge_style);
+        self
+    }
+    
+    pub fn with_base_branch(mut self, base_branch: impl Into<String>) -> Self {
+        self.base_branch = Some(base_branch.into());
+        self
+    }
+    
+    pub fn with_release_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.release_pattern = Some(pattern.into());
+        self
+    }
+    
+    pub fn with_branch_context(mut self, include_branch_context: bool) -> Self {
+        self.include_branch_context = include_branch_context;
+        self
+    }
+    
+    pub fn with_branch_prefixes(mut self, prefixes: Vec<String>) -> Self {
+        self.branch_prefixes = prefixes;
+        self
+    }
+    
+    pub fn with_detect_scaffolding(mut self, detect_scaffolding: bool) -> Self {
+        self.detect_scaffolding = detect_scaffolding;
+        self
+    }
+    
+    pub fn with_detect_file_types(mut self, detect_file_types: bool) -> Self {
+        self.detect_file_types = detect_file_types;
+        self
+    }
+    
+    pub fn with_detect_batches(mut self, detect_batches: bool) -> Self {
+        self.detect_batches = detect_batches;
+        self
+    }
+    
+    pub fn with_max_tickets(mut self, max_tickets: usize) -> Self {
+        self.max_tickets = Some(max_tickets);
+        self
+    }
+    
     pub fn with_verbose(mut self, verbose: bool) -> Self {
         self.verbose = verbose;
         self
     }
+    
+    pub fn with_action(mut self, action: impl Into<String>) -> Self {
+        self.action = Some(action.into());
+        self
+    }
+    
+    pub fn with_filenames(mut self, use_filenames: bool) -> Self {
+        self.use_filenames = use_filenames;
+        self
+    }
+    
+    pub fn with_fail_on_generic(mut self, fail_on_generic: bool) -> Self {
+        self.fail_on_generic = fail_on_generic;
+        self
+    }
+    
+    pub fn with_prefer_commit_subject(mut self, prefer_commit_subject: bool) -> Self {
+        self.prefer_commit_subject = prefer_commit_subject;
+        self
+    }
+    
+    pub fn with_require_ticket(mut self, require_ticket: bool) -> Self {
+        self.require_ticket = require_ticket;
+        self
+    }
+    
+    pub fn with_audience_titles(mut self, audience_titles: bool) -> Self {
+        self.audience_titles = audience_titles;
+        self
+    }
+    
+    pub fn with_rank_by(mut self, rank_by: RankBy) -> Self {
+        self.rank_by = rank_by;
+        self
+    }
+    
+    pub fn with_capitalize(mut self, capitalize: bool) -> Self {
+        self.capitalize = capitalize;
+        self
+    }
+    
+    /// Replace the case exceptions, including the defaults
+    pub fn with_case_exceptions(mut self, words: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.case_exceptions = words.into_iter().map(Into::into).collect();
+        self
+    }
+    
+    /// Replace the strong verbs, including the defaults
+    pub fn with_strong_verbs(mut self, verbs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.strong_verbs = verbs.into_iter().map(Into::into).collect();
+        self
+    }
+    
+    pub fn with_dedupe_ticket(mut self, dedupe_ticket: bool) -> Self {
+        self.dedupe_ticket = dedupe_ticket;
+        self
+    }
+    
+    pub fn with_keep_refs(mut self, keep_refs: bool) -> Self {
+        self.keep_refs = keep_refs;
+        self
+    }
+    
+    pub fn with_commit_bodies(mut self, commit_bodies: bool) -> Self {
+        self.commit_bodies = commit_bodies;
+        self
+    }
+    
+    pub fn with_scope_from_ticket(mut self, scope_from_ticket: bool) -> Self {
+        self.scope_from_ticket = scope_from_ticket;
+        self
+    }
+    
+    pub fn with_ticket_placement(mut self, placement: TicketPlacement) -> Self {
+        self.ticket_placement = placement;
+        self
+    }
+    
+    pub fn with_ticket_formats(mut self, formats: impl Into<Vec<TicketFormat>>) -> Self {
+        self.ticket_formats = formats.into();
+        self
+    }
+    
+    pub fn with_unicode_tickets(mut self, unicode_tickets: bool) -> Self {
+        self.unicode_tickets = unicode_tickets;
+        self
+    }
+    
+    pub fn with_ticket_prefixes(mut self, prefixes: TicketPrefixes) -> Self {
+        self.ticket_prefixes = prefixes;
+        self
+    }
+    
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+    
+    pub fn with_pattern_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.pattern_file = Some(path.into());
+        self
+    }
+    
+    pub fn with_ticket_separator(mut self, separator: impl Into<String>) -> Self {
+        self.ticket_separator = separator.into();
+        self
+    }
+    
+    pub fn with_learn_vocab(mut self, learn_vocab: bool) -> Self {
+        self.learn_vocab = learn_vocab;
+        self
+    }
+    
+    pub fn with_domain_vocabulary(mut self, vocabulary: Vec<String>) -> Self {
+        self.domain_vocabulary = vocabulary;
+        self
+    }
+    
+    pub fn with_list_style(mut self, list_style: ListStyle) -> Self {
+        self.list_style = list_style;
+        self
+    }
+    
+    pub fn with_ticket_template(mut self, template: impl Into<String>) -> Self {
+        self.ticket_template = Some(template.into());
+        self
+    }
+    
+    pub fn with_post_process_command(mut self, command: impl Into<String>) -> Self {
+        self.post_process_command = Some(command.into());
+        self
+    }
+}
+
+/// Serialize an `f32` as the decimal it was written as, e.g. 0.7 rather than 0.699999988
+fn serialize_short_f32<S: serde::Serializer>(value: &f32, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(value.to_string().parse().unwrap_or(f64::from(*value)))
+}
+
+fn serialize_short_optional_f32<S: serde::Serializer>(
+    value: &Option<f32>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serialize_short_f32(value, serializer),
+        None => serializer.serialize_none(),
+    }
 }
\ No newline at end of file
@@ -0,0 +1,200 @@
+//! Commit message lint for a branch, without generating a title
+//!
+//! `generate-pr-title lint` runs the context cleaning over the branch's
+//! commits and reports the ones it would drop as noise. Subjects that are not
+//! conventional commits and a missing ticket are reported when required.
+
+use crate::{
+    context::DropReason,
+    git::{CommitInfo, GitRepo},
+    pipeline::{commit_filter, context_processor},
+    Error, GeneratorConfig, Result,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Types accepted by `--require-conventional`, as in `fix(auth): ...`
+const CONVENTIONAL_TYPES: [&str; 11] = [
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
+/// What is wrong with a commit message, or with the branch as a whole
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    MergeOrRevert,
+    TooShort,
+    Generic,
+    NotConventional,
+    /// Neither the branch name nor a commit names a ticket
+    MissingTicket,
+}
+
+impl LintRule {
+    pub fn description(&self) -> &'static str {
+        match self {
+            LintRule::MergeOrRevert => "merge or revert commit",
+            LintRule::TooShort => "too little text to describe the change",
+            LintRule::Generic => "only generic words such as \"update\" or \"fix\"",
+            LintRule::NotConventional => "not a conventional commit subject such as \"fix: ...\"",
+            LintRule::MissingTicket => "no ticket in the branch name or its commits",
+        }
+    }
+}
+
+/// A lint finding for one commit, or for the branch when `hash` is `None`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintIssue {
+    pub rule: LintRule,
+    pub hash: Option<String>,
+    /// Commit subject, or the branch name for branch-wide issues
+    pub subject: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.hash {
+            Some(hash) => {
+                let short_hash: String = hash.chars().take(7).collect();
+                write!(f, "{} {}: {}", short_hash, self.subject, self.rule.description())
+            }
+            None => write!(f, "{}: {}", self.subject, self.rule.description()),
+        }
+    }
+}
+
+/// Lint the commit messages on `branch` since it diverged from `base`
+///
+/// No title backend runs. Ticket issues are only reported with
+/// `config.require_ticket`, subject format issues with `require_conventional`.
+pub fn lint_branch(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+    require_conventional: bool,
+) -> Result<Vec<LintIssue>> {
+    if !repo.commit_exists(branch) {
+        return Err(Error::BranchNotFound {
+            branch: branch.to_string(),
+        });
+    }
+
+    let commits = repo.get_filtered_commits(base, branch, &commit_filter(config)?)?;
+    lint_commits(branch, &commits, config, require_conventional)
+}
+
+/// Lint commits that were already read, e.g. from an API
+pub fn lint_commits(
+    branch: &str,
+    commits: &[CommitInfo],
+    config: &GeneratorConfig,
+    require_conventional: bool,
+) -> Result<Vec<LintIssue>> {
+    let context_processor = context_processor(config)?;
+    let branch_context = context_processor.extract_branch_context(branch);
+    let context =
+        context_processor.create_clean_context(&branch_context, &context_processor.clean_commit_messages(commits));
+
+    let mut issues: Vec<LintIssue> = context_processor
+        .dropped_commits(commits, &context)
+        .into_iter()
+        .filter_map(|dropped| {
+            // Summarized and clustered commits are fine, just outnumbered
+            let rule = match dropped.reason {
+                DropReason::MergeOrRevert => LintRule::MergeOrRevert,
+                DropReason::TooShort => LintRule::TooShort,
+                DropReason::Generic => LintRule::Generic,
+                DropReason::Summarized | DropReason::Clustered => return None,
+            };
+            Some(LintIssue {
+                rule,
+                hash: Some(dropped.hash),
+                subject: dropped.subject,
+            })
+        })
+        .collect();
+
+    if require_conventional {
+        for commit in commits {
+            let subject = commit.subject();
+            if !is_conventional(&subject) && !issues.iter().any(|issue| issue.hash.as_ref() == Some(&commit.hash)) {
+                issues.push(LintIssue {
+                    rule: LintRule::NotConventional,
+                    hash: Some(commit.hash.clone()),
+                    subject,
+                });
+            }
+        }
+    }
+
+    let has_ticket = branch_context.ticket.is_some() || context_processor.extract_commit_ticket(commits).is_some();
+    if config.require_ticket && !has_ticket {
+        issues.push(LintIssue {
+            rule: LintRule::MissingTicket,
+            hash: None,
+            subject: branch.to_string(),
+        });
+    }
+
+    Ok(issues)
+}
+
+/// Whether `subject` reads `type(scope)!: description` with a known type
+fn is_conventional(subject: &str) -> bool {
+    let Some((prefix, description)) = subject.split_once(": ") else {
+        return false;
+    };
+    let prefix = prefix.strip_suffix('!').unwrap_or(prefix);
+    let commit_type = match prefix.split_once('(') {
+        Some((commit_type, scope)) if scope.ends_with(')') && scope.len() > 1 => commit_type,
+        Some(_) => return false,
+        None => prefix,
+    };
+
+    CONVENTIONAL_TYPES.contains(&commit_type) && !description.trim().is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestRepo;
+
+    #[test]
+    fn test_clean_branch_passes() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix(remediation): bottle stuck with remediation system");
+        test_repo.commit_file("retry.txt", "retry", "feat: retry stuck bottles after a timeout");
+
+        let config = GeneratorConfig::default().with_require_ticket(true);
+        let issues = lint_branch(&test_repo.open(), "main", "feature/CRU-310-fix-bottle-stuck", &config, true).unwrap();
+
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_generic_commit_is_reported() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "bottle-retries"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        test_repo.commit_file("more.txt", "more", "update some files");
+
+        let config = GeneratorConfig::default().with_require_ticket(true);
+        let issues = lint_branch(&test_repo.open(), "main", "bottle-retries", &config, true).unwrap();
+        let rules: Vec<LintRule> = issues.iter().map(|issue| issue.rule).collect();
+
+        assert_eq!(rules, vec![LintRule::Generic, LintRule::MissingTicket]);
+        assert_eq!(issues[0].subject, "update some files");
+        assert!(issues[0].to_string().ends_with("update some files: only generic words such as \"update\" or \"fix\""));
+    }
+
+    #[test]
+    fn test_conventional_subjects() {
+        assert!(is_conventional("fix: bottle stuck"));
+        assert!(is_conventional("feat(auth)!: drop legacy tokens"));
+        assert!(!is_conventional("Fix bottle stuck"));
+        assert!(!is_conventional("feature: bottle stuck"));
+        assert!(!is_conventional("fix(: bottle stuck"));
+    }
+}
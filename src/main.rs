@@ -4,23 +4,37 @@
 //! from commit messages and branch context.
 
 use pr_title_generator::{
-    cli::Cli,
-    context::ContextProcessor,
-    git::GitRepo,
+    batch::generate_ranges_file_titles,
+    cache::{format_age, TitleCache},
+    cli::{CacheCommand, Cli, Command, OutputFormat},
+    commit::generate_commit_message,
+    event::PullRequestEvent,
+    lint::lint_branch,
+    config::Setting,
+    dump_branch_context, explain_branch_title, explain_commits_title, find_merged_ticket, generate_branch_output,
+    generate_branch_title_with_progress, generate_commits_output, generate_commits_title, generate_context_title,
+    git::{CommitInfo, GitRepo},
     ml::TitleGenerator,
-    Error, Result,
+    patterns::{ActionTemplate, TemplateSource},
+    ContextDump, Error, GeneratorConfig, OutputPayload, ProgressCallback, ProgressEvent, Result,
 };
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
 use std::process;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() {
-    // Initialize logging
-    env_logger::init();
-    
     // Parse command line arguments
     let cli = Cli::parse_args();
     
+    init_tracing(cli.verbose);
+    
     // Validate arguments
     if let Err(e) = cli.validate() {
         eprintln!("Error: {}", e);
@@ -30,90 +44,361 @@ async fn main() {
     // Run the application
     if let Err(e) = run(cli).await {
         eprintln!("Error: {}", e);
-        process::exit(1);
+        process::exit(e.exit_code());
     }
 }
 
-async fn run(cli: Cli) -> Result<()> {
+/// Log diagnostics to stderr, filtered by `RUST_LOG` or `--verbose`
+fn init_tracing(verbose: bool) {
+    let default_filter = if verbose {
+        "pr_title_generator=debug,generate_pr_title=debug"
+    } else {
+        "warn"
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_filter));
+    
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .without_time()
+        .init();
+}
+
+async fn run(mut cli: Cli) -> Result<()> {
+    if let Some(Command::Cache { action }) = &cli.command {
+        return run_cache_command(action);
+    }
+    
     // Get current working directory
     let current_dir = env::current_dir()
-        .map_err(|e| Error::Io(e))?;
+        .map_err(Error::Io)?;
+    
+    info!(path = %current_dir.display(), "working directory");
     
-    if cli.verbose {
-        println!("Working directory: {}", current_dir.display());
+    if let Some(Command::Templates { format }) = &cli.command {
+        let templates = TitleGenerator::new(cli.to_config())?.templates();
+        return match format {
+            OutputFormat::Text => {
+                print_templates(&templates);
+                Ok(())
+            }
+            OutputFormat::Json => print_json(&templates),
+        };
     }
     
-    // Open and validate git repository
-    let git_repo = GitRepo::open(&current_dir)?;
+    if let Some(Command::Config { format }) = &cli.command {
+        // Outside a repository the config file is looked up in the working directory
+        let root = GitRepo::discover(&current_dir)
+            .map(|repo| repo.root_path().to_path_buf())
+            .unwrap_or_else(|_| current_dir.clone());
+        let settings = cli.effective_settings(&root)?;
+        return match format {
+            OutputFormat::Text => {
+                print_settings(&settings);
+                Ok(())
+            }
+            OutputFormat::Json => print_json(&settings),
+        };
+    }
     
-    if cli.verbose {
-        println!("Git repository found at: {}", git_repo.root_path().display());
+    if let Some(Command::Commit) = &cli.command {
+        let git_repo = GitRepo::discover(&current_dir)?;
+        let config = cli.load_config(git_repo.root_path())?;
+        println!("{}", generate_commit_message(&git_repo, &config).await?);
+        return Ok(());
     }
     
-    // Get branch name
-    let branch_name = cli.get_branch_name()?;
+    if let Some(Command::Lint { require_conventional, format }) = &cli.command {
+        let git_repo = GitRepo::discover(&current_dir)?;
+        let config = cli.load_config(git_repo.root_path())?;
+        let (base, branch) = (cli.get_base_branch()?, git_repo.branch_name_for(&cli.get_branch_name()?));
+        let issues = lint_branch(&git_repo, &base, &branch, &config, *require_conventional)?;
+        match format {
+            OutputFormat::Text => issues.iter().for_each(|issue| println!("{}", issue)),
+            OutputFormat::Json => print_json(&issues)?,
+        }
+        if !issues.is_empty() {
+            return Err(Error::LintFailed { issues: issues.len() });
+        }
+        return Ok(());
+    }
     
-    if cli.verbose {
-        println!("Analyzing branch: {}", branch_name);
-        println!("Base branch: {}", cli.base);
+    if let Some(Command::DumpContext { format }) = &cli.command {
+        let git_repo = GitRepo::discover(&current_dir)?;
+        let config = cli.load_config(git_repo.root_path())?;
+        let (base, branch) = (cli.get_base_branch()?, git_repo.branch_name_for(&cli.get_branch_name()?));
+        let dump = dump_branch_context(&git_repo, &base, &branch, &config)?;
+        return match format {
+            OutputFormat::Text => {
+                println!("{}", dump.context.to_prompt());
+                Ok(())
+            }
+            OutputFormat::Json => print_json(&dump),
+        };
     }
     
-    // Validate that the branch exists
-    if !git_repo.branch_exists(&branch_name) {
-        return Err(Error::BranchNotFound {
-            branch: branch_name,
-        });
+    #[cfg(feature = "http")]
+    if let Some(number) = cli.pr {
+        return run_pull_request(&cli, number, &current_dir).await;
     }
     
-    // Get commits between base and branch
-    let commits = git_repo.get_commits_between(&cli.base, &branch_name, cli.max_commits)?;
+    if let Some(path) = &cli.from_context {
+        let dump = read_context_dump(path)?;
+        info!(branch = %dump.branch, "replaying dumped context");
+        let config = cli.load_config(&current_dir)?;
+        let title = generate_context_title(&dump.context, &config).await?;
+        println!("{}", title);
+        copy_if_requested(&cli, &title);
+        return Ok(());
+    }
     
-    if cli.verbose {
-        println!("Found {} commits to analyze", commits.len());
-        for (i, commit) in commits.iter().enumerate().take(5) {
-            println!("  {}: {}", i + 1, commit.clean_message());
-        }
-        if commits.len() > 5 {
-            println!("  ... and {} more", commits.len() - 5);
+    if cli.github_event {
+        let event = PullRequestEvent::from_env()?;
+        info!(number = event.number, head = %event.head_ref, base = %event.base_ref, "analyzing pull request event");
+        // Commits in the payload make the checkout unnecessary
+        if !event.commits.is_empty() {
+            let config = cli.load_config(&current_dir)?.with_base_branch(event.base_ref);
+            return print_commits_title(&cli, &event.head_ref, &event.commits, &config).await;
         }
+        cli.branch = Some(event.head_ref);
+        cli.base = Some(event.base_ref);
     }
     
-    // Initialize context processor
-    let context_processor = ContextProcessor::new()?;
+    // Open and validate git repository
+    let git_repo = GitRepo::discover(&current_dir)?;
     
-    // Extract branch context
-    let branch_context = context_processor.extract_branch_context(&branch_name);
+    info!(path = %git_repo.root_path().display(), "git repository found");
     
-    if cli.verbose {
-        println!("Branch context: {:#?}", branch_context);
+    if let Some(path) = &cli.ranges_file {
+        let config = cli.load_config(git_repo.root_path())?;
+        return print_json(&generate_ranges_file_titles(&git_repo, path, &config).await?);
     }
     
-    // Clean commit messages
-    let clean_commits = context_processor.clean_commit_messages(&commits);
+    // Get branch names; `--branch HEAD` titles the checked-out branch under its own name
+    let branch_name = git_repo.branch_name_for(&cli.get_branch_name()?);
+    let stack_parent = if cli.stack_base {
+        git_repo.stack_parent(&branch_name)?
+    } else {
+        None
+    };
+    let base_branch = match stack_parent {
+        Some(parent) => parent,
+        None => cli.get_base_branch()?,
+    };
+    
+    info!(branch = %branch_name, base = %base_branch, "analyzing branch");
     
-    if cli.verbose {
-        println!("Cleaned commit messages:");
-        for (i, commit) in clean_commits.iter().enumerate() {
-            println!("  {}: {}", i + 1, commit);
+    // Generate PR title
+    let config = cli.load_config(git_repo.root_path())?;
+    if cli.check_merged {
+        if let Some(merged) = find_merged_ticket(&git_repo, &base_branch, &branch_name, &config)? {
+            eprintln!("warning: {}", merged);
         }
     }
+    if cli.trace_json {
+        return print_json(&explain_branch_title(&git_repo, &base_branch, &branch_name, &config)?);
+    }
+    let title = match cli.format {
+        OutputFormat::Text if cli.raw || config.audience_titles => {
+            let output = generate_branch_output(&git_repo, &base_branch, &branch_name, &config).await?;
+            print_with_details(&output, cli.raw);
+            output.title
+        }
+        OutputFormat::Text => {
+            // Status lines only where someone watches them, and not between debug logs
+            let show = |event| show_progress(&event);
+            let progress: Option<ProgressCallback<'_>> = (io::stderr().is_terminal() && !cli.verbose).then_some(&show);
+            let result =
+                generate_branch_title_with_progress(&git_repo, &base_branch, &branch_name, &config, progress).await;
+            if progress.is_some() {
+                eprint!("\r\x1b[2K");
+            }
+            let title = result?;
+            println!("{}", title);
+            title
+        }
+        OutputFormat::Json => {
+            let output = generate_branch_output(&git_repo, &base_branch, &branch_name, &config).await?;
+            print_json(&output)?;
+            output.title
+        }
+    };
+    copy_if_requested(&cli, &title);
     
-    // Create clean context for ML model
-    let clean_context = context_processor.create_clean_context(&branch_context, &clean_commits);
+    Ok(())
+}
+
+/// Read a `dump-context --format json` file, or stdin for `-`
+fn read_context_dump(path: &Path) -> Result<ContextDump> {
+    let json = if path == Path::new("-") {
+        let mut json = String::new();
+        io::stdin().read_to_string(&mut json)?;
+        json
+    } else {
+        fs::read_to_string(path)?
+    };
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn print_json(output: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(output)?);
+    Ok(())
+}
+
+/// Print each action followed by its indented templates, marking pattern file ones
+fn print_templates(templates: &[ActionTemplate]) {
+    let mut action = None;
+    for template in templates {
+        if action != Some(&template.action) {
+            println!("{}", template.action);
+            action = Some(&template.action);
+        }
+        match template.source {
+            TemplateSource::BuiltIn => println!("  {}", template.template),
+            TemplateSource::PatternFile => println!("  {}  (pattern file)", template.template),
+        }
+    }
+}
+
+/// Print settings as TOML, each followed by its source; unset ones are commented out
+fn print_settings(settings: &BTreeMap<String, Setting>) {
+    for (name, setting) in settings {
+        match toml::Value::try_from(&setting.value) {
+            Ok(value) => println!("{} = {}  # {}", name, value, setting.source.as_str()),
+            Err(_) => println!("# {} is unset  # {}", name, setting.source.as_str()),
+        }
+    }
+}
+
+/// Put the title on the clipboard for `--copy`
+#[cfg_attr(not(feature = "clipboard"), allow(unused_variables))]
+fn copy_if_requested(cli: &Cli, title: &str) {
+    #[cfg(feature = "clipboard")]
+    if cli.copy {
+        copy_title(&pr_title_generator::clipboard::SystemClipboard::new(), title);
+    }
+}
+
+/// Copy the title, warning instead of failing where there is no clipboard, e.g. on CI
+#[cfg(feature = "clipboard")]
+fn copy_title(clipboard: &impl pr_title_generator::clipboard::Clipboard, title: &str) {
+    if let Err(e) = clipboard.copy(title) {
+        eprintln!("warning: {}", e);
+    }
+}
+
+/// Show the current step on one stderr line, replacing the previous step
+fn show_progress(event: &ProgressEvent) {
+    let status = match event {
+        ProgressEvent::GitReadStarted => "reading commits...".to_string(),
+        ProgressEvent::GitReadFinished { commits } => format!("read {} commits", commits),
+        ProgressEvent::CommitsProcessed { analyzed, kept } => format!("using {} of {} commits", kept, analyzed),
+        ProgressEvent::GenerationStarted => "generating title...".to_string(),
+        ProgressEvent::GenerationFinished => String::new(),
+    };
+    eprint!("\r\x1b[2K{}", status);
+}
+
+/// Print the title, with the backend's raw title and the technical title on
+/// stderr so stdout stays scriptable
+fn print_with_details(output: &OutputPayload, raw: bool) {
+    if raw {
+        eprintln!("raw: {}", output.raw_title);
+    }
+    if let Some(titles) = &output.titles {
+        eprintln!("technical: {}", titles.technical);
+    }
+    println!("{}", output.title);
+}
+
+/// List or clear the title cache
+fn run_cache_command(action: &CacheCommand) -> Result<()> {
+    let dir = TitleCache::default_dir().ok_or_else(|| {
+        Error::Io(io::Error::new(io::ErrorKind::NotFound, "no cache directory; set HOME or XDG_CACHE_HOME"))
+    })?;
+    let cache = TitleCache::new(dir);
     
-    if cli.verbose {
-        println!("Clean context for ML model: {:#?}", clean_context);
+    match action {
+        CacheCommand::List => {
+            let entries = cache.list()?;
+            if entries.is_empty() {
+                println!("No cached titles in {}", cache.dir().display());
+            }
+            for entry in entries {
+                let age = format_age(entry.age_secs());
+                println!("{}  {:>4}  {}  ->  {}", entry.key, age, entry.summary, entry.title);
+            }
+        }
+        CacheCommand::Clear => {
+            let removed = cache.clear()?;
+            println!("Removed {} cached titles from {}", removed, cache.dir().display());
+        }
     }
     
-    // Initialize ML title generator
-    let config = cli.to_config();
-    let title_generator = TitleGenerator::new(config)?;
+    Ok(())
+}
+
+/// Generate a title from a GitHub pull request, ignoring the local history
+#[cfg(feature = "http")]
+async fn run_pull_request(cli: &Cli, number: u64, current_dir: &std::path::Path) -> Result<()> {
+    use pr_title_generator::github::GitHubClient;
+    
+    // The local checkout is only consulted to detect the repository
+    let origin_url = GitRepo::discover(current_dir)
+        .ok()
+        .and_then(|repo| repo.remote_url("origin"));
+    let client = GitHubClient::from_env(cli.repo.as_deref(), origin_url.as_deref())?;
     
-    // Generate PR title
-    let title = title_generator.generate_title(&clean_context).await?;
+    // ureq blocks, so the requests run off the runtime's worker threads
+    let max_commits = cli.max_commits;
+    let (pull_request, commits) = tokio::task::spawn_blocking(move || {
+        Ok::<_, Error>((client.pull_request(number)?, client.pull_request_commits(number, max_commits)?))
+    })
+    .await
+    .map_err(|e| Error::GitHubApi { message: e.to_string() })??;
     
-    // Output the generated title
-    println!("{}", title);
+    info!(number, head = %pull_request.head_ref, base = %pull_request.base_ref, "analyzing pull request");
+    
+    if commits.is_empty() {
+        return Err(Error::NoCommits {
+            base: pull_request.base_ref,
+            branch: pull_request.head_ref,
+        });
+    }
+    
+    let config = cli.load_config(current_dir)?.with_base_branch(pull_request.base_ref);
+    print_commits_title(cli, &pull_request.head_ref, &commits, &config).await
+}
+
+/// Print the title for commits read from GitHub rather than the local repository
+async fn print_commits_title(
+    cli: &Cli,
+    branch: &str,
+    commits: &[CommitInfo],
+    config: &GeneratorConfig,
+) -> Result<()> {
+    if cli.trace_json {
+        return print_json(&explain_commits_title(branch, commits, config)?);
+    }
+    let title = match cli.format {
+        OutputFormat::Text if cli.raw || config.audience_titles => {
+            let output = generate_commits_output(branch, commits, config).await?;
+            print_with_details(&output, cli.raw);
+            output.title
+        }
+        OutputFormat::Text => {
+            let title = generate_commits_title(branch, commits, config).await?;
+            println!("{}", title);
+            title
+        }
+        OutputFormat::Json => {
+            let output = generate_commits_output(branch, commits, config).await?;
+            print_json(&output)?;
+            output.title
+        }
+    };
+    copy_if_requested(cli, &title);
     
     Ok(())
 }
@@ -121,9 +406,13 @@ async fn run(cli: Cli) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use pr_title_generator::ErrorKind;
     use tempfile::TempDir;
     use std::process::Command;
     
+    /// Held by tests that change the working directory, which is per process
+    static WORKING_DIR: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+    
     fn create_test_repo_with_commits() -> (TempDir, String) {
         let temp_dir = TempDir::new().unwrap();
         let repo_path = temp_dir.path();
@@ -205,13 +494,14 @@ mod tests {
         let (_temp_dir, repo_path) = create_test_repo_with_commits();
         
         // Change to the test repo directory
+        let _working_dir = WORKING_DIR.lock().await;
         let original_dir = env::current_dir().unwrap();
         env::set_current_dir(&repo_path).unwrap();
         
         // Test the main workflow - use master as default git branch name
         let cli = Cli {
             branch: Some("feature/CRU-310-fix-bottle-stuck".to_string()),
-            base: "master".to_string(),
+            base: Some("master".to_string()),
             verbose: false,
             ..Default::default()
         };
@@ -226,18 +516,33 @@ mod tests {
         if let Err(e) = &result {
             println!("Test run result: {:?}", e);
             // Allow expected errors in test repos
-            let error_str = e.to_string();
-            assert!(
-                error_str.contains("No commits found") || 
-                error_str.contains("BranchNotFound") ||
-                error_str.contains("not found")
-            );
+            assert!(matches!(
+                e.kind(),
+                ErrorKind::NoCommits | ErrorKind::BranchNotFound | ErrorKind::BaseBranchNotFound
+            ));
         }
     }
     
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn test_copy_without_clipboard_only_warns() {
+        struct NoClipboard;
+        
+        impl pr_title_generator::clipboard::Clipboard for NoClipboard {
+            fn copy(&self, _text: &str) -> Result<()> {
+                Err(Error::ClipboardUnavailable {
+                    message: "no display".to_string(),
+                })
+            }
+        }
+        
+        copy_title(&NoClipboard, "CRU-310: Fix bottle stuck");
+    }
+    
     #[tokio::test]
     async fn test_non_git_directory() {
         let temp_dir = TempDir::new().unwrap();
+        let _working_dir = WORKING_DIR.lock().await;
         let original_dir = env::current_dir().unwrap();
         
         env::set_current_dir(temp_dir.path()).unwrap();
@@ -249,4 +554,37 @@ mod tests {
         
         assert!(matches!(result, Err(Error::NotGitRepository { .. })));
     }
+    
+    #[tokio::test]
+    async fn test_bare_clone_from_server_side_hook() {
+        let (_temp_dir, repo_path) = create_test_repo_with_commits();
+        let bare_dir = TempDir::new().unwrap();
+        Command::new("git")
+            .args(["clone", "-q", "--bare", &repo_path, &bare_dir.path().to_string_lossy()])
+            .output()
+            .unwrap();
+        // `git init` names the first branch after the user's `init.defaultBranch`
+        let branches = Command::new("git")
+            .args(["for-each-ref", "--format=%(refname:short)", "refs/heads"])
+            .current_dir(&repo_path)
+            .output()
+            .unwrap();
+        let branches = String::from_utf8_lossy(&branches.stdout).to_string();
+        let base = branches.lines().find(|branch| !branch.starts_with("feature/")).unwrap().to_string();
+        
+        let _working_dir = WORKING_DIR.lock().await;
+        let original_dir = env::current_dir().unwrap();
+        env::set_current_dir(bare_dir.path()).unwrap();
+        
+        let cli = Cli {
+            branch: Some("feature/CRU-310-fix-bottle-stuck".to_string()),
+            base: Some(base),
+            ..Default::default()
+        };
+        let result = run(cli).await;
+        
+        env::set_current_dir(original_dir).unwrap();
+        
+        assert!(result.is_ok(), "{:?}", result);
+    }
 }
\ No newline at end of file
@@ -1,15 +1,222 @@
 //! Machine learning model integration for PR title generation
 
-use crate::{context::CleanContext, Error, GeneratorConfig, Result};
+use crate::output::{Explanation, TitleCandidate, SCHEMA_VERSION};
+use crate::patterns::{ActionTemplate, PatternFile, PatternMode, TemplateSource};
+use crate::{context::{classify_commits, module_name, scaffold_intent, Audience, CleanContext}, hooks, Error, GeneratorConfig, LengthMetric, ListStyle, Model, RankBy, Result, TicketPlacement};
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, info_span, warn, Instrument};
+
+/// Hard cap on the final title length, matching GitHub's PR title display
+const MAX_TITLE_LENGTH: usize = 72;
+
+/// Words that say nothing about what a change actually does
+const GENERIC_TERMS: [&str; 5] = ["update", "change", "modify", "fix", "improve"];
+
+/// Filler words ignored when judging how specific a title is
+const STOP_WORDS: [&str; 8] = ["with", "from", "into", "that", "this", "for", "the", "and"];
+
+/// Verbs accepted as an imperative title opener
+const IMPERATIVE_VERBS: [&str; 27] = [
+    "add", "fix", "implement", "introduce", "refactor", "improve", "optimize", "remove",
+    "update", "resolve", "correct", "support", "migrate", "rename", "document", "bump",
+    "replace", "handle", "enable", "disable", "allow", "prevent", "simplify", "release",
+    "initialize", "scaffold", "merge",
+];
+
+/// `score_title` score at which a single commit subject is kept as the title
+const PREFERRED_SUBJECT_MIN_SCORE: f32 = 0.75;
+
+/// Backend name of titles kept from a commit subject with `prefer_commit_subject`
+pub const COMMIT_SUBJECT_BACKEND: &str = "commit";
+
+/// Titles shorter than this are penalized as too terse
+const MIN_DESCRIPTIVE_LENGTH: usize = 15;
+
+/// Verbs accepted as a forced action
+pub const KNOWN_ACTIONS: [&str; 5] = ["add", "fix", "refactor", "remove", "update"];
+
+/// Extensions of prose files, named without the extension in titles
+const PROSE_EXTENSIONS: [&str; 4] = ["md", "txt", "rst", "adoc"];
+
+/// Temperatures sampled when listing alternative titles
+const CANDIDATE_TEMPERATURES: [f32; 3] = [0.1, 0.5, 0.9];
+
+/// Verbs more specific than the change type's action, used when they open most commits
+pub const DEFAULT_STRONG_VERBS: [&str; 12] = [
+    "migrate", "rename", "deprecate", "upgrade", "downgrade", "extract", "split", "convert", "port", "vendor",
+    "inline", "rewrite",
+];
+
+/// Words that open test commits without naming what is tested, e.g. `cover`
+/// in `cover retries` or `add unit tests for` in `add unit tests for retries`
+const TEST_LEAD_WORDS: [&str; 14] = [
+    "add", "cover", "test", "tests", "check", "verify", "more", "unit", "integration", "e2e", "coverage", "missing",
+    "for", "of",
+];
+
+/// Words spelled this way wherever they appear in a title
+pub const DEFAULT_CASE_EXCEPTIONS: [&str; 6] = ["iOS", "macOS", "GitHub", "npm", "API", "URL"];
+
+const PREFIX_TICKET_TEMPLATE: &str = "{ticket}{separator}{title}";
+const SUFFIX_TICKET_TEMPLATE: &str = "{title} ({ticket})";
+
+/// Version of the built-in templates, bumped whenever their wording changes,
+/// so a lock file can tell that titles would come out differently
+pub const TEMPLATE_VERSION: u32 = 1;
+
+/// Seed of the random choices when `GeneratorConfig::seed` is unset
+pub const DEFAULT_SEED: u64 = 0;
+
+/// Source of the random choices behind titles
+///
+/// `TitleGenerator` makes one per call, e.g. per `generate` or
+/// `generate_candidates`, and each generation of the call takes one draw
+/// from it for `SamplingParams::draw`.
+pub trait TitleRng: Send {
+    /// Next draw, uniform over all `u64` values
+    fn next_u64(&mut self) -> u64;
+}
+
+/// SplitMix64: small, fast and the same on every platform
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl TitleRng for SeededRng {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+type RngFactory = Box<dyn Fn() -> Box<dyn TitleRng> + Send + Sync>;
+
+/// Sampling settings for one generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplingParams {
+    /// Softmax temperature for backends that decode tokens from a model
+    pub temperature: f32,
+    /// Random draw for this generation; the pattern backend picks a template
+    /// with it and a model backend would seed its sampler with it
+    pub draw: u64,
+}
+
+impl SamplingParams {
+    /// The first draw of a call, as `TitleGenerator` takes it with the default RNG
+    pub fn from_config(config: &GeneratorConfig) -> Self {
+        Self {
+            temperature: config.temperature,
+            draw: SeededRng::new(config.seed.unwrap_or(DEFAULT_SEED)).next_u64(),
+        }
+    }
+}
+
+/// Input handed to a title backend for a single generation
+pub struct GenerationRequest<'a> {
+    pub context: &'a CleanContext,
+    pub config: &'a GeneratorConfig,
+    /// Sampling to use for this generation; may differ from `config`, e.g.
+    /// when candidates are spread across temperatures
+    pub sampling: SamplingParams,
+    /// Earlier title and what the user wants changed, when revising
+    pub feedback: Option<Feedback<'a>>,
+    /// Who the title is written for
+    pub audience: Audience,
+}
+
+/// A title the user rejected and their note on it, e.g. "too vague"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Feedback<'a> {
+    pub previous: &'a str,
+    pub feedback: &'a str,
+}
+
+impl<'a> GenerationRequest<'a> {
+    /// Request sampled with the settings from `config`
+    pub fn new(context: &'a CleanContext, config: &'a GeneratorConfig) -> Self {
+        Self {
+            context,
+            config,
+            sampling: SamplingParams::from_config(config),
+            feedback: None,
+            audience: Audience::UserFacing,
+        }
+    }
+    
+    /// The context rendered in the chat template of the configured model,
+    /// with the previous title and feedback when revising
+    pub fn prompt(&self) -> String {
+        let model = Model::parse(&self.config.model_name).unwrap_or_default();
+        match &self.feedback {
+            Some(feedback) => self.context.to_revision_prompt_for(&model, feedback.previous, feedback.feedback),
+            None => self.context.to_audience_prompt_for(&model, self.audience),
+        }
+    }
+}
+
+/// Source of raw titles, before `TitleGenerator` applies post-processing
+/// such as truncation, capitalization and ticket placement
+pub trait TitleBackend: Send + Sync {
+    /// Short identifier of the backend, e.g. "pattern"
+    fn name(&self) -> &'static str;
+    
+    /// Produce a raw title for the request
+    fn generate(&self, request: &GenerationRequest<'_>) -> Result<String>;
+}
+
+/// A post-processed title, the backend output it came from, and what
+/// truncation cut from it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedTitle {
+    pub title: String,
+    /// Backend output before truncation, capitalization, ticket placement and user hooks
+    pub raw_title: String,
+    /// Whether a length limit shortened the title
+    pub truncated: bool,
+    /// Text removed by truncation, so callers can offer the full title
+    pub removed_suffix: Option<String>,
+    /// `TitleBackend::name` of the backend that produced the raw title
+    pub backend: String,
+}
+
+impl GeneratedTitle {
+    fn new(backend: &str, raw_title: String, title: String, removed_suffix: Option<String>) -> Self {
+        let removed_suffix = removed_suffix
+            .map(|suffix| suffix.trim().to_string())
+            .filter(|suffix| !suffix.is_empty());
+        Self {
+            title,
+            raw_title,
+            truncated: removed_suffix.is_some(),
+            removed_suffix,
+            backend: backend.to_string(),
+        }
+    }
+}
+
+/// User-supplied transform applied after built-in post-processing
+pub type PostProcessor = Box<dyn Fn(String, &CleanContext) -> String + Send + Sync>;
 
 /// ML-based PR title generator
 pub struct TitleGenerator {
     config: GeneratorConfig,
-    // For now, we'll use pattern-based generation
+    // For now, we'll use pattern-based generation unless a custom backend is set
     // TODO: Replace with actual ML model integration using candle-rs
     patterns: PatternMatcher,
+    backend: Option<Box<dyn TitleBackend>>,
+    post_processors: Vec<PostProcessor>,
+    rng: RngFactory,
 }
 
 impl TitleGenerator {
@@ -27,74 +234,559 @@ impl TitleGenerator {
             });
         }
         
+        if config.max_words == Some(0) {
+            return Err(Error::InvalidMaxWords { words: 0 });
+        }
+        
+        if config.max_tickets == Some(0) {
+            return Err(Error::InvalidMaxTickets { tickets: 0 });
+        }
+        
+        if let Some(threshold) = config.emphasize_dominant.filter(|share| !(*share > 0.0 && *share <= 1.0)) {
+            return Err(Error::InvalidDominanceThreshold { threshold });
+        }
+        
+        if let Some(&length) = config.max_length_by_type.values().find(|&&length| length == 0) {
+            return Err(Error::InvalidMaxLength { length });
+        }
+        
+        let shortest_limit = config.max_length_by_type.values().fold(config.max_length, |a, &b| a.min(b));
+        if config.length_metric.measure(&config.ellipsis) >= shortest_limit {
+            return Err(Error::InvalidEllipsis {
+                ellipsis: config.ellipsis.clone(),
+                limit: shortest_limit,
+            });
+        }
+        
+        if let Some(template) = &config.ticket_template {
+            if !template.contains("{ticket}") || !template.contains("{title}") {
+                return Err(Error::InvalidTicketTemplate {
+                    template: template.clone(),
+                });
+            }
+        }
+        
+        if let Some(action) = &config.action {
+            validate_action(action)?;
+        }
+        
+        let uses_separator = match &config.ticket_template {
+            Some(template) => template.contains("{separator}"),
+            None => config.ticket_placement == TicketPlacement::Prefix,
+        };
+        if uses_separator && config.ticket_separator.is_empty() {
+            return Err(Error::EmptyTicketSeparator);
+        }
+        
         // Validate model name
-        let supported_models = ["tiny-llama", "phi-2", "gemma-2b", "llama-2-7b"];
-        if !supported_models.contains(&config.model_name.as_str()) {
+        if Model::parse(&config.model_name).is_none() {
             return Err(Error::UnsupportedModel {
                 name: config.model_name.clone(),
             });
         }
         
-        let patterns = PatternMatcher::new()?;
+        let mut patterns = PatternMatcher::new()?;
+        if let Some(path) = &config.pattern_file {
+            patterns.apply_pattern_file(PatternFile::load(path)?);
+        }
+        
+        debug!(model = %config.model_name, "initialized title generator");
         
-        if config.verbose {
-            println!("Initialized title generator with model: {}", config.model_name);
+        let seed = config.seed.unwrap_or(DEFAULT_SEED);
+        Ok(Self {
+            config,
+            patterns,
+            backend: None,
+            post_processors: Vec::new(),
+            rng: Box::new(move || Box::new(SeededRng::new(seed))),
+        })
+    }
+    
+    /// Create a generator with the default config that delegates raw title
+    /// generation to `backend`
+    pub fn with_backend(backend: impl TitleBackend + 'static) -> Self {
+        Self::new(GeneratorConfig::default())
+            .expect("default generator config is valid")
+            .using_backend(backend)
+    }
+    
+    /// Replace the backend used for raw title generation
+    pub fn using_backend(mut self, backend: impl TitleBackend + 'static) -> Self {
+        self.backend = Some(Box::new(backend));
+        self
+    }
+    
+    /// Register a transform that runs after the built-in post-processing
+    pub fn with_post_processor(
+        mut self,
+        post_processor: impl Fn(String, &CleanContext) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.post_processors.push(Box::new(post_processor));
+        self
+    }
+    
+    /// Make the random choices with a fresh RNG from `rng` for every call,
+    /// instead of one seeded with `seed`, e.g. a fixed sequence in tests
+    pub fn with_rng<R: TitleRng + 'static>(mut self, rng: impl Fn() -> R + Send + Sync + 'static) -> Self {
+        self.rng = Box::new(move || Box::new(rng()));
+        self
+    }
+    
+    /// Sampling at `temperature` with the next draw of `rng`
+    fn sampling(rng: &mut dyn TitleRng, temperature: f32) -> SamplingParams {
+        SamplingParams {
+            temperature,
+            draw: rng.next_u64(),
+        }
+    }
+    
+    /// Get the backend that produces raw titles
+    fn active_backend(&self) -> &dyn TitleBackend {
+        self.backend.as_deref().unwrap_or(&self.patterns)
+    }
+    
+    /// Raw title for `request` and the name of the backend that produced it
+    ///
+    /// Without a model backend, or when it fails, the pattern backend takes
+    /// over unless `pattern_fallback` is off or `backend` pins another one.
+    fn raw_title(&self, request: &GenerationRequest<'_>) -> Result<(&'static str, String)> {
+        match self.config.backend.as_deref() {
+            Some(pinned) if pinned == self.patterns.name() => {
+                return Ok((self.patterns.name(), self.patterns.generate(request)?));
+            }
+            Some(pinned) if self.backend.as_deref().map(TitleBackend::name) != Some(pinned) => {
+                return Err(Error::ModelError {
+                    message: format!("pinned backend '{}' is not available", pinned),
+                });
+            }
+            Some(_) => return Ok((self.active_backend().name(), self.active_backend().generate(request)?)),
+            None => {}
         }
+        let Some(backend) = self.backend.as_deref() else {
+            if !self.config.pattern_fallback {
+                return Err(Error::ModelError {
+                    message: format!("no backend for model '{}' and pattern fallback is disabled", self.config.model_name),
+                });
+            }
+            return Ok((self.patterns.name(), self.patterns.generate(request)?));
+        };
         
-        Ok(Self { config, patterns })
+        match backend.generate(request) {
+            Ok(title) => Ok((backend.name(), title)),
+            Err(error) if self.config.pattern_fallback => {
+                warn!(backend = backend.name(), %error, "title backend failed, falling back to patterns");
+                Ok((self.patterns.name(), self.patterns.generate(request)?))
+            }
+            Err(error) => Err(error),
+        }
     }
     
     /// Generate a PR title from the given context
     pub async fn generate_title(&self, context: &CleanContext) -> Result<String> {
-        if self.config.verbose {
-            println!("Generating title with context: {:#?}", context);
+        self.generate(context).await.map(|generated| generated.title)
+    }
+    
+    /// Generate a PR title, reporting whether it was truncated
+    pub async fn generate(&self, context: &CleanContext) -> Result<GeneratedTitle> {
+        self.generate_for(context, Audience::UserFacing).await
+    }
+    
+    /// Generate a PR title written for `audience`
+    ///
+    /// Model backends get a prompt with audience-specific instructions. The
+    /// pattern backend uses its technical templates for `Audience::Technical`
+    /// and names the dominant changed file whenever there is one.
+    pub async fn generate_for(&self, context: &CleanContext, audience: Audience) -> Result<GeneratedTitle> {
+        let generated = self.generate_before_hook(context, audience).await?;
+        self.apply_post_process_command(generated)
+    }
+    
+    /// Generate a title without running `post_process_command`, so a cached
+    /// title can still go through the command
+    pub(crate) async fn generate_before_hook(&self, context: &CleanContext, audience: Audience) -> Result<GeneratedTitle> {
+        let backend = self.active_backend().name();
+        self.generate_inner(context, audience)
+            .instrument(info_span!("generate", backend, ?audience))
+            .await
+            .inspect_err(|error| debug!(%error))
+    }
+    
+    async fn generate_inner(&self, context: &CleanContext, audience: Audience) -> Result<GeneratedTitle> {
+        debug!(?context, "generating title");
+        
+        let request = GenerationRequest {
+            audience,
+            sampling: Self::sampling((self.rng)().as_mut(), self.config.temperature),
+            ..GenerationRequest::new(context, &self.config)
+        };
+        let raw_title = match self.preferred_subject(context) {
+            Some(subject) => (COMMIT_SUBJECT_BACKEND, subject),
+            None => self.raw_title(&request)?,
+        };
+        let generated = self.finish_title(raw_title, context)?;
+        
+        let title_text = title_words(&generated.title, context).join(" ");
+        if self.config.fail_on_generic && self.is_generic_title(&title_text) {
+            return Err(Error::GenericTitle {
+                title: generated.title,
+            });
         }
         
-        // For now, use pattern-based generation
-        // TODO: Replace with actual ML model inference
-        let title = self.patterns.generate_title(context, &self.config)?;
+        debug!(raw = %generated.raw_title, title = %generated.title, truncated = generated.truncated, "generated title");
         
-        let processed_title = self.post_process_title(title, context)?;
+        Ok(generated)
+    }
+    
+    /// Generate a revised title for `context` that takes `feedback` on the
+    /// `previous` title into account, e.g. "too vague" or "mention auth"
+    ///
+    /// Model backends see both in the prompt. The pattern backend prefers a
+    /// domain named in the feedback. If the revision comes out identical to
+    /// `previous`, the candidate temperatures are tried for a different one.
+    pub async fn regenerate(&self, context: &CleanContext, previous: &str, feedback: &str) -> Result<String> {
+        let mut rng = (self.rng)();
+        let request = GenerationRequest {
+            feedback: Some(Feedback { previous, feedback }),
+            sampling: Self::sampling(rng.as_mut(), self.config.temperature),
+            ..GenerationRequest::new(context, &self.config)
+        };
+        let mut generated = self.finish_title(self.raw_title(&request)?, context)?;
         
-        if self.config.verbose {
-            println!("Generated title: {}", processed_title);
+        for temperature in CANDIDATE_TEMPERATURES {
+            if generated.title != previous {
+                break;
+            }
+            let request = GenerationRequest {
+                sampling: Self::sampling(rng.as_mut(), temperature),
+                ..request
+            };
+            generated = self.finish_title(self.raw_title(&request)?, context)?;
         }
         
-        Ok(processed_title)
+        let title = self.apply_post_process_command(generated)?.title;
+        debug!(previous, feedback, title = %title, "regenerated title");
+        Ok(title)
     }
     
-    /// Post-process the generated title
-    fn post_process_title(&self, mut title: String, context: &CleanContext) -> Result<String> {
-        // Ensure title is not too long
-        if title.len() > self.config.max_length {
-            title = format!("{}...", &title[..self.config.max_length.saturating_sub(3)]);
+    /// Generate distinct titles across a spread of temperatures, ordered by
+    /// `config.rank_by`: best score first unless configured otherwise
+    ///
+    /// Candidates go through the same post-processing as `generate_title`
+    /// but are never rejected as generic; their scores say how they compare.
+    /// `post_process_command` only runs on the title that is picked, not on
+    /// every candidate.
+    pub fn generate_candidates(&self, context: &CleanContext) -> Result<Vec<TitleCandidate>> {
+        let mut temperatures = vec![self.config.temperature];
+        temperatures.extend(CANDIDATE_TEMPERATURES);
+        
+        let mut rng = (self.rng)();
+        let mut candidates: Vec<TitleCandidate> = Vec::new();
+        for temperature in temperatures {
+            let request = GenerationRequest {
+                sampling: Self::sampling(rng.as_mut(), temperature),
+                ..GenerationRequest::new(context, &self.config)
+            };
+            let title = self.finish_title(self.raw_title(&request)?, context)?.title;
+            
+            if candidates.iter().all(|candidate| candidate.title != title) {
+                let score = self.score_title(&title, context);
+                candidates.push(TitleCandidate { title, score });
+            }
         }
         
-        // Add ticket number if not present and we have one
-        if let Some(ticket) = &context.ticket {
-            if !title.contains(ticket) && !self.is_generic_title(&title) {
-                title = format!("{}: {}", ticket, title);
+        // Stable sorts, so ties keep the score order
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        match self.config.rank_by {
+            RankBy::Score => {}
+            RankBy::Length => candidates.sort_by_key(|candidate| {
+                effective_length(&candidate.title, context, self.config.length_metric)
+            }),
+            RankBy::Specificity => candidates.sort_by(|a, b| {
+                specificity(&b.title, context).total_cmp(&specificity(&a.title, context))
+            }),
+        }
+        Ok(candidates)
+    }
+    
+    /// Trace the decisions behind the title for `context`
+    ///
+    /// Action, domain and template are only known for the pattern backend.
+    /// Unlike `generate_title`, a generic title is traced rather than rejected.
+    pub fn explain(&self, context: &CleanContext) -> Result<Explanation> {
+        let request = GenerationRequest {
+            sampling: Self::sampling((self.rng)().as_mut(), self.config.temperature),
+            ..GenerationRequest::new(context, &self.config)
+        };
+        let patterns_only = match self.config.backend.as_deref() {
+            Some(pinned) => pinned == self.patterns.name(),
+            None => self.backend.is_none() && self.config.pattern_fallback,
+        };
+        let (title, decision) = match self.preferred_subject(context) {
+            Some(subject) => ((COMMIT_SUBJECT_BACKEND, subject), None),
+            None if patterns_only => {
+                let decision = self.patterns.decide(&request);
+                ((self.patterns.name(), decision.title.clone()), Some(decision))
             }
+            _ => (self.raw_title(&request)?, None),
+        };
+        let generated = self.apply_post_process_command(self.finish_title(title, context)?)?;
+        
+        Ok(Explanation {
+            schema_version: SCHEMA_VERSION,
+            backend: generated.backend,
+            ticket: context.ticket.clone(),
+            change_type: context.change_type.clone(),
+            action: decision.as_ref().map(|decision| decision.action.clone()),
+            domain: decision.as_ref().map(|decision| decision.domain.clone()),
+            template: decision.and_then(|decision| decision.template),
+            raw_title: generated.raw_title,
+            title: generated.title,
+            truncated: generated.truncated,
+            removed_suffix: generated.removed_suffix,
+            prompt_size: self.prompt_size(context),
+        })
+    }
+    
+    /// Size of `context`'s prompt in the configured model's chat template in characters
+    pub fn prompt_size(&self, context: &CleanContext) -> usize {
+        context.prompt_size_for(&Model::parse(&self.config.model_name).unwrap_or_default())
+    }
+    
+    /// Templates the pattern backend picks from: the built-in set with the
+    /// pattern file applied
+    pub fn templates(&self) -> Vec<ActionTemplate> {
+        self.patterns.templates()
+    }
+    
+    /// Apply built-in, then registered post-processing to a raw title
+    ///
+    /// `post_process_command` is left to the caller, which runs it once on
+    /// the title it settles on.
+    fn finish_title(&self, (backend, title): (&str, String), context: &CleanContext) -> Result<GeneratedTitle> {
+        let processed = self.post_process_title(title, context)?;
+        Ok(GeneratedTitle {
+            title: self.apply_post_processors(processed.title, context),
+            backend: backend.to_string(),
+            ..processed
+        })
+    }
+    
+    /// Post-process the generated title, recording any text truncation removes
+    fn post_process_title(&self, raw_title: String, context: &CleanContext) -> Result<GeneratedTitle> {
+        let mut removed = None;
+        let title = match &context.ticket {
+            // With `max_tickets` the label lists the other tickets too, so
+            // their inline mentions go whether or not `dedupe_ticket` is set
+            Some(ticket) if self.config.dedupe_ticket || self.lists_tickets(context) => std::iter::once(ticket)
+                .chain(context.extra_tickets.iter().filter(|_| self.config.max_tickets.is_some()))
+                .fold(raw_title.clone(), |title, ticket| self.remove_ticket_mentions(&title, ticket)),
+            _ => raw_title.clone(),
+        };
+        
+        // Ensure title is not too long and properly capitalized
+        let title = match self.config.max_words {
+            Some(max_words) => truncate_words(&title, max_words, &self.config.ellipsis, &mut removed),
+            None => title,
+        };
+        // The issue reference counts against the length limit, so the text makes room for it
+        let issue_ref = context.issue_ref.as_ref().map(|issue_ref| format!(" ({})", issue_ref));
+        let metric = self.config.length_metric;
+        let limit = self.max_length_for(context).saturating_sub(issue_ref.as_ref().map_or(0, |suffix| metric.measure(suffix)));
+        let title = truncate_title(&title, limit, &self.config.ellipsis, metric, &mut removed) + issue_ref.as_deref().unwrap_or("");
+        let title = if self.config.capitalize {
+            self.capitalize_title(&title)
+        } else {
+            title
+        };
+        let title = apply_case_exceptions(&title, &self.config.case_exceptions);
+        let title = match &context.ticket {
+            Some(ticket) => normalize_ticket_case(&title, ticket),
+            None => title,
+        };
+        
+        // Add ticket number if not present and we have one
+        let title = match self.ticket_to_apply(&title, context) {
+            Some(ticket) => self.apply_ticket(&title, &self.ticket_label(ticket, context), &mut removed),
+            None => truncate_title(&title, MAX_TITLE_LENGTH, &self.config.ellipsis, metric, &mut removed),
+        };
+        
+        Ok(GeneratedTitle::new(self.active_backend().name(), raw_title, title, removed))
+    }
+    
+    /// Run registered transforms
+    fn apply_post_processors(&self, title: String, context: &CleanContext) -> String {
+        self.post_processors
+            .iter()
+            .fold(title, |title, post_processor| post_processor(title, context))
+    }
+    
+    /// Pipe the title through the configured external command, if any
+    pub(crate) fn apply_post_process_command(&self, generated: GeneratedTitle) -> Result<GeneratedTitle> {
+        match &self.config.post_process_command {
+            Some(command) => Ok(GeneratedTitle {
+                title: hooks::run_post_process_command(command, &generated.title)?,
+                ..generated
+            }),
+            None => Ok(generated),
+        }
+    }
+    
+    /// Remove `ticket` from the title, along with brackets and a colon around it
+    ///
+    /// The title is kept as it is when nothing but generic words would be
+    /// left, since the ticket would then not be placed again.
+    fn remove_ticket_mentions(&self, title: &str, ticket: &str) -> String {
+        if self.config.ticket_placement == TicketPlacement::None {
+            return title.to_string();
+        }
+        
+        let pattern = format!(r"(?i)(^|\s)[\[(]?{}[\])]?:?(\s|$)", regex::escape(ticket));
+        let Ok(pattern) = Regex::new(&pattern) else {
+            return title.to_string();
+        };
+        // Adjacent mentions share the space between them, so replace until none are left
+        let mut stripped = title.to_string();
+        while pattern.is_match(&stripped) {
+            stripped = pattern.replace_all(&stripped, " ").to_string();
+        }
+        let stripped = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+        
+        if self.is_generic_title(&stripped) {
+            title.to_string()
+        } else {
+            stripped
+        }
+    }
+    
+    /// Get the ticket that should be added to the title, if any
+    fn ticket_to_apply<'a>(&self, title: &str, context: &'a CleanContext) -> Option<&'a str> {
+        if self.config.ticket_placement == TicketPlacement::None {
+            return None;
         }
         
-        // Ensure proper capitalization
-        title = self.capitalize_title(&title);
+        context
+            .ticket
+            .as_deref()
+            .filter(|ticket| (self.lists_tickets(context) || !title.contains(ticket)) && !self.is_generic_title(title))
+    }
+    
+    /// Whether the ticket label lists other tickets besides the context's own
+    fn lists_tickets(&self, context: &CleanContext) -> bool {
+        self.config.max_tickets.is_some() && !context.extra_tickets.is_empty()
+    }
+    
+    /// The ticket, then up to `max_tickets - 1` of the context's other tickets
+    /// and a count of the rest, e.g. `CRU-310, CRU-311 +2 more`
+    fn ticket_label(&self, ticket: &str, context: &CleanContext) -> String {
+        let ticket = ticket.to_uppercase();
+        let Some(max_tickets) = self.config.max_tickets else {
+            return ticket;
+        };
+        let listed = max_tickets.saturating_sub(1).min(context.extra_tickets.len());
+        let tickets: Vec<String> = std::iter::once(ticket)
+            .chain(context.extra_tickets[..listed].iter().map(|ticket| ticket.to_uppercase()))
+            .collect();
         
-        // Final length check after adding ticket
-        if title.len() > 72 {
-            title = format!("{}...", &title[..69]);
+        let label = tickets.join(&self.config.list_style.separator);
+        match context.extra_tickets.len() - listed {
+            0 => label,
+            more => format!("{} +{} more", label, more),
         }
+    }
+    
+    /// Place the ticket label using the configured template, shortening the
+    /// title first so the tickets themselves never get truncated away
+    fn apply_ticket(&self, title: &str, ticket: &str, removed: &mut Option<String>) -> String {
+        let template = self.ticket_template();
+        let separator = &self.config.ticket_separator;
+        let metric = self.config.length_metric;
+        let overhead = metric.measure(&render_ticket_template(template, ticket, separator, ""));
+        let available = MAX_TITLE_LENGTH.saturating_sub(overhead);
         
-        Ok(title)
+        render_ticket_template(template, ticket, separator, &truncate_title(title, available, &self.config.ellipsis, metric, removed))
+    }
+    
+    /// Get the ticket template, falling back to the placement's default
+    fn ticket_template(&self) -> &str {
+        match (&self.config.ticket_template, self.config.ticket_placement) {
+            (Some(template), _) => template,
+            (None, TicketPlacement::Suffix) => SUFFIX_TICKET_TEMPLATE,
+            (None, _) => PREFIX_TICKET_TEMPLATE,
+        }
+    }
+    
+    /// Score how specific and well-formed a title is, from 0.0 to 1.0
+    ///
+    /// Combines the share of non-generic words, overlap with terms from the
+    /// context, length within the configured limit, and an imperative opening
+    /// verb. Works on any title, not just ones produced by this generator.
+    pub fn score_title(&self, title: &str, context: &CleanContext) -> f32 {
+        let words = title_words(title, context);
+        let Some(first_word) = words.first() else {
+            return 0.0;
+        };
+        
+        let specific_words: Vec<&String> = words.iter().filter(|word| is_specific_word(word)).collect();
+        let specificity = specific_words.len() as f32 / words.len() as f32;
+        let relevance = self.patterns.relevance(&specific_words, context);
+        let length = self.length_score(title);
+        let imperative = if IMPERATIVE_VERBS.contains(&first_word.as_str()) || self.config.strong_verbs.contains(first_word) {
+            1.0
+        } else {
+            0.0
+        };
+        
+        0.35 * specificity + 0.25 * relevance + 0.2 * length + 0.2 * imperative
+    }
+    
+    /// The subject of the context's only commit, when `prefer_commit_subject`
+    /// is set and the subject fits the length limit and scores at least
+    /// `PREFERRED_SUBJECT_MIN_SCORE`
+    ///
+    /// Forced actions, releases and batches are titled as usual.
+    fn preferred_subject(&self, context: &CleanContext) -> Option<String> {
+        let forced = context.action.is_some() || self.config.action.is_some();
+        if !self.config.prefer_commit_subject || forced || context.release_version.is_some() || !context.batch_tickets.is_empty() {
+            return None;
+        }
+        let [subject] = context.commits.as_slice() else {
+            return None;
+        };
+        // A folded body follows the subject's first sentence break and is no title
+        let subject = if self.config.commit_bodies { first_sentence(subject) } else { subject.as_str() };
+        let fits = self.config.length_metric.measure(subject) <= self.max_length_for(context);
+        (fits && self.score_title(subject, context) >= PREFERRED_SUBJECT_MIN_SCORE).then(|| subject.to_string())
+    }
+    
+    /// Length limit for the context's change type, falling back to `max_length`
+    fn max_length_for(&self, context: &CleanContext) -> usize {
+        context
+            .change_type
+            .as_ref()
+            .and_then(|change_type| self.config.max_length_by_type.get(change_type).copied())
+            .unwrap_or(self.config.max_length)
+    }
+    
+    /// Score the title length: full marks up to the configured limit
+    fn length_score(&self, title: &str) -> f32 {
+        let length = self.config.length_metric.measure(title);
+        let limit = self.config.max_length.min(MAX_TITLE_LENGTH);
+        
+        if length < MIN_DESCRIPTIVE_LENGTH {
+            length as f32 / MIN_DESCRIPTIVE_LENGTH as f32
+        } else if length <= limit {
+            1.0
+        } else {
+            (1.0 - (length - limit) as f32 / limit as f32).max(0.0)
+        }
     }
     
     /// Check if a title is too generic
     fn is_generic_title(&self, title: &str) -> bool {
-        let generic_terms = ["update", "change", "modify", "fix", "improve"];
         let words: Vec<&str> = title.split_whitespace().collect();
         
         words.len() <= 2 || words.iter().all(|word| {
-            generic_terms.contains(&word.to_lowercase().as_str()) || word.len() <= 3
+            GENERIC_TERMS.contains(&word.to_lowercase().as_str()) || word.len() <= 3
         })
     }
     
@@ -108,17 +800,264 @@ impl TitleGenerator {
     }
 }
 
+/// Respell each word listed in `exceptions`, matched ignoring case, e.g. `Ios` as `iOS`
+///
+/// Words that are part of a file name or path, such as `api` in `api.rs`,
+/// and scopes such as `(api)` keep their spelling.
+fn apply_case_exceptions(title: &str, exceptions: &[String]) -> String {
+    let respell = |word: &str, before: Option<char>, after: Option<char>| {
+        let in_path = [before, after].iter().any(|c| matches!(c, Some('.' | '/' | '_')));
+        let scope = before == Some('(') && after == Some(')');
+        match exceptions.iter().find(|exception| exception.eq_ignore_ascii_case(word)) {
+            Some(exception) if !in_path && !scope => exception.clone(),
+            _ => word.to_string(),
+        }
+    };
+    
+    let mut respelled = String::with_capacity(title.len());
+    let mut word = String::new();
+    let mut before = None;
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            respelled.push_str(&respell(&word, before, Some(c)));
+            respelled.push(c);
+            word.clear();
+            before = Some(c);
+        }
+    }
+    respelled.push_str(&respell(&word, before, None));
+    respelled
+}
+
+/// Check that a forced action is one of `KNOWN_ACTIONS`
+pub fn validate_action(action: &str) -> Result<()> {
+    if KNOWN_ACTIONS.contains(&action) {
+        Ok(())
+    } else {
+        Err(Error::InvalidAction {
+            action: action.to_string(),
+            expected: KNOWN_ACTIONS.join(", "),
+        })
+    }
+}
+
+/// `Scaffold billing service` for a `scaffold/billing-service` branch, or
+/// `Initialize project` when the branch name says nothing about it
+fn scaffold_title(context: &CleanContext) -> String {
+    match scaffold_intent(context) {
+        Some((verb, subject)) if !subject.is_empty() => format!("{} {}", verb, subject),
+        Some((verb, _)) => format!("{} project", verb),
+        None => "Initialize project".to_string(),
+    }
+}
+
+/// `Merge 3 PRs: CRU-310, CRU-311, and CRU-312`, naming at most three of the tickets
+fn batch_title(tickets: &[String], list_style: &ListStyle) -> String {
+    let mut listed: Vec<String> = tickets.iter().take(3).cloned().collect();
+    if tickets.len() > 3 {
+        listed.push(format!("{} more", tickets.len() - 3));
+    }
+    format!("Merge {} PRs: {}", tickets.len(), list_style.join(&listed))
+}
+
+/// Name a file for a title: `README.md` reads as `README`, code keeps its extension
+fn file_label(path: &str) -> String {
+    let is_prose = is_prose_file(path);
+    let path = std::path::Path::new(path);
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    
+    match path.file_stem() {
+        Some(stem) if is_prose => stem.to_string_lossy().to_string(),
+        _ => name.to_string(),
+    }
+}
+
+/// Check if a path is documentation rather than code, e.g. `README.md`
+pub(crate) fn is_prose_file(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .map(|ext| PROSE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Truncate a title to `limit` in `metric`, marking the cut with `ellipsis`
+///
+/// The ellipsis counts against the limit, and a character that would cross
+/// it is cut whole. The cut text is added in front of
+/// `removed`. A title already cut by an earlier limit ends in the ellipsis,
+/// which is dropped before cutting again so `removed` stays contiguous text.
+fn truncate_title(title: &str, limit: usize, ellipsis: &str, metric: LengthMetric, removed: &mut Option<String>) -> String {
+    if metric.measure(title) <= limit {
+        return title.to_string();
+    }
+    
+    let text = match removed {
+        Some(_) => title.strip_suffix(ellipsis).unwrap_or(title),
+        None => title,
+    };
+    let budget = limit.saturating_sub(metric.measure(ellipsis));
+    let mut used = 0;
+    let end = text
+        .char_indices()
+        .take_while(|&(_, c)| {
+            used += metric.measure_char(c);
+            used <= budget
+        })
+        .last()
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let kept = text[..end].trim_end();
+    let cut = text[kept.len()..].to_string();
+    *removed = Some(cut + removed.as_deref().unwrap_or(""));
+    
+    format!("{}{}", kept, ellipsis)
+}
+
+/// The verb of `verbs` opening more than half of `commits`, and the longest
+/// subject it opens without the verb, e.g. `auth to OAuth` for `migrate`
+fn dominant_verb<'a>(commits: &'a [String], verbs: &[String]) -> Option<(String, &'a str)> {
+    let mut opened: HashMap<String, Vec<&str>> = HashMap::new();
+    for commit in commits {
+        let Some((first, rest)) = commit.split_once(' ') else { continue };
+        let first = first.to_lowercase();
+        if let Some(verb) = verbs.iter().map(|verb| verb.to_lowercase()).find(|verb| inflects(&first, verb)) {
+            opened.entry(verb).or_default().push(rest.trim());
+        }
+    }
+    
+    let (verb, subjects) = opened.into_iter().max_by_key(|(_, subjects)| subjects.len())?;
+    if subjects.len() * 2 <= commits.len() {
+        return None;
+    }
+    // Ties go to the newest commit
+    let subject = subjects.into_iter().rev().max_by_key(|subject| subject.len())?;
+    Some((verb, subject))
+}
+
+/// Whether `word` is `verb` or one of its inflections, e.g. `migrated` or `migrating`
+pub(crate) fn inflects(word: &str, verb: &str) -> bool {
+    let stem = verb.strip_suffix('e').unwrap_or(verb);
+    word == verb
+        || ["s", "d", "ed", "es", "ing"]
+            .iter()
+            .any(|suffix| [stem, verb].iter().any(|base| word.strip_prefix(base) == Some(suffix)))
+}
+
+/// What a test commit covers, e.g. `retries in fetch client` for
+/// `cover retries in fetch client`; the subject itself when nothing is left
+fn tested_subject(subject: &str) -> &str {
+    let mut rest = subject;
+    while let Some((word, tail)) = rest.split_once(' ') {
+        let word = word.to_lowercase();
+        if !TEST_LEAD_WORDS.iter().any(|lead| inflects(&word, lead)) {
+            break;
+        }
+        rest = tail.trim_start();
+    }
+    if rest.is_empty() { subject } else { rest }
+}
+
+/// `text` up to its first sentence break, e.g. `crash on startup` for
+/// `crash on startup. The config loader panicked`
+fn first_sentence(text: &str) -> &str {
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| matches!(c, '.' | '!' | '?') && text[i + c.len_utf8()..].starts_with(' '))
+        .map_or(text.len(), |(i, _)| i);
+    &text[..end]
+}
+
+/// Cut `title` after its first `limit` words, ending it with `ellipsis`
+fn truncate_words(title: &str, limit: usize, ellipsis: &str, removed: &mut Option<String>) -> String {
+    let mut word_starts = title
+        .char_indices()
+        .filter(|&(i, c)| !c.is_whitespace() && title[..i].chars().next_back().is_none_or(char::is_whitespace))
+        .map(|(i, _)| i);
+    let Some(cut_at) = word_starts.nth(limit) else {
+        return title.to_string();
+    };
+    
+    let kept = title[..cut_at].trim_end();
+    *removed = Some(title[kept.len()..].to_string());
+    format!("{}{}", kept, ellipsis)
+}
+
+/// Rewrite any differently-cased occurrence of the ticket to its canonical form
+fn normalize_ticket_case(title: &str, ticket: &str) -> String {
+    let canonical = ticket.to_uppercase();
+    match Regex::new(&format!("(?i){}", regex::escape(&canonical))) {
+        Ok(pattern) => pattern.replace_all(title, canonical.as_str()).to_string(),
+        Err(_) => title.to_string(),
+    }
+}
+
+/// Lowercased words of a title, without punctuation or the context's ticket
+fn title_words(title: &str, context: &CleanContext) -> Vec<String> {
+    let ticket = context.ticket.as_deref().unwrap_or("").to_lowercase();
+    
+    title
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|word| !word.is_empty() && *word != ticket)
+        .collect()
+}
+
+/// Share of a title's words that are specific, from 0.0 to 1.0
+fn specificity(title: &str, context: &CleanContext) -> f32 {
+    let words = title_words(title, context);
+    if words.is_empty() {
+        return 0.0;
+    }
+    words.iter().filter(|word| is_specific_word(word)).count() as f32 / words.len() as f32
+}
+
+/// Length of a title's words, without the ticket and punctuation
+fn effective_length(title: &str, context: &CleanContext, metric: LengthMetric) -> usize {
+    metric.measure(&title_words(title, context).join(" "))
+}
+
+/// Check if a word carries meaning beyond generic or filler vocabulary
+fn is_specific_word(word: &str) -> bool {
+    let singular = word.strip_suffix('s').unwrap_or(word);
+    
+    word.len() > 3
+        && !STOP_WORDS.contains(&word)
+        && !GENERIC_TERMS.contains(&word)
+        && !GENERIC_TERMS.contains(&singular)
+}
+
+fn render_ticket_template(template: &str, ticket: &str, separator: &str, title: &str) -> String {
+    template
+        .replace("{ticket}", ticket)
+        .replace("{separator}", separator)
+        .replace("{title}", title)
+}
+
+/// What the pattern backend chose for one title
+struct PatternDecision {
+    action: String,
+    domain: String,
+    /// Template the title was filled from; `None` for fallback titles
+    template: Option<String>,
+    title: String,
+}
+
 /// Pattern-based title generator (temporary replacement for ML model)
 struct PatternMatcher {
     action_patterns: HashMap<String, Vec<String>>,
-    domain_patterns: HashMap<String, Vec<String>>,
+    /// Implementation-focused templates for `Audience::Technical`
+    technical_patterns: HashMap<String, Vec<String>>,
+    /// Actions whose templates came from a pattern file
+    file_actions: HashSet<String>,
+    /// Domain keys and their aliases, in matching priority order
+    domain_patterns: Vec<(String, Vec<String>)>,
     cleanup_regex: Vec<Regex>,
 }
 
 impl PatternMatcher {
     fn new() -> Result<Self> {
         let mut action_patterns = HashMap::new();
-        let mut domain_patterns = HashMap::new();
         
         // Common action patterns
         action_patterns.insert("fix".to_string(), vec![
@@ -139,26 +1078,62 @@ impl PatternMatcher {
             "Optimize {domain} {component}".to_string(),
         ]);
         
-        // Domain-specific patterns
-        domain_patterns.insert("auth".to_string(), vec![
-            "authentication".to_string(),
-            "authorization".to_string(),
-            "login".to_string(),
-            "security".to_string(),
+        // Docs-only branches always read `Document ...`, whatever the draw
+        action_patterns.insert("docs".to_string(), vec![
+            "Document {domain} {component}".to_string(),
         ]);
         
-        domain_patterns.insert("crypto".to_string(), vec![
-            "cryptocurrency".to_string(),
-            "blockchain".to_string(),
-            "wallet".to_string(),
+        // Test-only and CI-only branches say what they touch rather than
+        // passing for a feature
+        action_patterns.insert("test".to_string(), vec![
+            "Add tests for {component}".to_string(),
         ]);
         
-        domain_patterns.insert("api".to_string(), vec![
-            "API".to_string(),
-            "endpoint".to_string(),
-            "service".to_string(),
+        action_patterns.insert("ci".to_string(), vec![
+            "Update CI to {component}".to_string(),
         ]);
         
+        action_patterns.insert("remove".to_string(), vec![
+            "Remove {domain} {component}".to_string(),
+        ]);
+        
+        action_patterns.insert("update".to_string(), vec![
+            "Update {domain} {component}".to_string(),
+        ]);
+        
+        // One template per action, since reviewers want the same wording every run
+        let technical_patterns = HashMap::from([
+            ("fix".to_string(), vec!["Handle {domain} {issue}".to_string()]),
+            ("feature".to_string(), vec!["Implement {domain} {feature}".to_string()]),
+            ("refactor".to_string(), vec!["Simplify {domain} {component}".to_string()]),
+            ("docs".to_string(), vec!["Document {domain} {component}".to_string()]),
+            ("test".to_string(), vec!["Add tests for {domain} {component}".to_string()]),
+            ("ci".to_string(), vec!["Update CI to {component}".to_string()]),
+            ("remove".to_string(), vec!["Remove {domain} {component}".to_string()]),
+            ("update".to_string(), vec!["Update {domain} {component}".to_string()]),
+        ]);
+        
+        // Domain-specific patterns; the first matching domain wins, so the
+        // most specific domains come before broad ones like "api"
+        let domain_patterns = vec![
+            ("auth".to_string(), vec![
+                "authentication".to_string(),
+                "authorization".to_string(),
+                "login".to_string(),
+                "security".to_string(),
+            ]),
+            ("crypto".to_string(), vec![
+                "cryptocurrency".to_string(),
+                "blockchain".to_string(),
+                "wallet".to_string(),
+            ]),
+            ("api".to_string(), vec![
+                "API".to_string(),
+                "endpoint".to_string(),
+                "service".to_string(),
+            ]),
+        ];
+        
         let cleanup_regex = vec![
             Regex::new(r"\b(the|a|an)\b")?,
             Regex::new(r"\s+")?,
@@ -166,27 +1141,166 @@ impl PatternMatcher {
         
         Ok(Self {
             action_patterns,
+            technical_patterns,
+            file_actions: HashSet::new(),
             domain_patterns,
             cleanup_regex,
         })
     }
     
-    fn generate_title(&self, context: &CleanContext, config: &GeneratorConfig) -> Result<String> {
-        // Extract key information
-        let action = self.determine_action(context);
-        let domain = self.extract_domain(context);
+    /// Merge templates from a pattern file into the built-in ones
+    fn apply_pattern_file(&mut self, file: PatternFile) {
+        if file.mode == PatternMode::Replace {
+            self.action_patterns.clear();
+        }
+        
+        self.file_actions.extend(file.actions.keys().cloned());
+        self.action_patterns.extend(file.actions);
+    }
+    
+    /// Every action's templates, sorted by action and in the order they are picked
+    fn templates(&self) -> Vec<ActionTemplate> {
+        let mut actions: Vec<&String> = self.action_patterns.keys().collect();
+        actions.sort();
+        
+        actions
+            .into_iter()
+            .flat_map(|action| {
+                let source = if self.file_actions.contains(action) {
+                    TemplateSource::PatternFile
+                } else {
+                    TemplateSource::BuiltIn
+                };
+                self.action_patterns[action].iter().map(move |template| ActionTemplate {
+                    action: action.clone(),
+                    template: template.clone(),
+                    source,
+                })
+            })
+            .collect()
+    }
+    
+    /// Share of specific title words that also appear in the context or known domains
+    fn relevance(&self, specific_words: &[&String], context: &CleanContext) -> f32 {
+        if specific_words.is_empty() {
+            return 0.0;
+        }
+        
+        let context_text = format!(
+            "{} {}",
+            context.description.as_deref().unwrap_or(""),
+            context.commits.join(" ")
+        ).to_lowercase();
+        let context_terms: HashSet<&str> = context_text.split_whitespace().collect();
+        
+        let relevant = specific_words
+            .iter()
+            .filter(|word| context_terms.contains(word.as_str()) || self.is_domain_term(word))
+            .count();
+        
+        (relevant as f32 / specific_words.len().min(2) as f32).min(1.0)
+    }
+    
+    fn is_domain_term(&self, word: &str) -> bool {
+        self.domain_patterns.iter().any(|(key, aliases)| {
+            key == word || aliases.iter().any(|alias| alias.to_lowercase() == word)
+        })
+    }
+    
+    /// Pick the action, domain and template, and fill in the raw title
+    ///
+    /// There is nothing to sample, so the temperature only picks which of an
+    /// action's templates is used.
+    fn decide(&self, request: &GenerationRequest<'_>) -> PatternDecision {
+        let (context, config) = (request.context, request.config);
+        // Extract key information; a forced action always uses its own verb
+        let forced_action = context.action.as_deref().or(config.action.as_deref());
+        if let (Some(version), None) = (&context.release_version, forced_action) {
+            return PatternDecision {
+                title: format!("Release {}", version),
+                action: "release".to_string(),
+                domain: String::new(),
+                template: None,
+            };
+        }
+        if !context.batch_tickets.is_empty() && forced_action.is_none() {
+            return PatternDecision {
+                title: batch_title(&context.batch_tickets, &config.list_style),
+                action: "merge".to_string(),
+                domain: String::new(),
+                template: None,
+            };
+        }
+        if context.scaffolding && forced_action.is_none() {
+            let title = scaffold_title(context);
+            return PatternDecision {
+                action: title.split(' ').next().unwrap_or_default().to_lowercase(),
+                title,
+                domain: String::new(),
+                template: None,
+            };
+        }
+        if let (Some((verb, subject)), None) = (dominant_verb(&context.commits, &config.strong_verbs), forced_action) {
+            return PatternDecision {
+                title: self.clean_title(&format!("{} {}", self.capitalize_first(&verb), subject)),
+                action: verb,
+                domain: self.extract_domain(context, &config.domain_vocabulary),
+                template: None,
+            };
+        }
+        let action = match forced_action {
+            Some("add") => "feature".to_string(),
+            Some(action) => action.to_string(),
+            None => self.determine_action(context),
+        };
+        let technical = request.audience == Audience::Technical;
+        // Without commits, e.g. for uncommitted changes, the file is the best subject there is
+        let file_label = context
+            .focused_file()
+            .filter(|_| config.use_filenames || technical || context.commits.is_empty())
+            .map(|file| file_label(&file.path));
+        let dominant_module = config
+            .emphasize_dominant
+            .and_then(|share| context.dominant_file(share))
+            .and_then(|file| module_name(&file.path));
+        let domain = file_label
+            .clone()
+            .or_else(|| request.feedback.and_then(|feedback| self.feedback_domain(feedback.feedback)))
+            .or(dominant_module)
+            .unwrap_or_else(|| self.extract_domain(context, &config.domain_vocabulary));
         let main_subject = self.extract_main_subject(context);
+        // `Add tests for cover retries` would say the tests twice
+        let main_subject = match action.as_str() {
+            "test" => tested_subject(&main_subject).to_string(),
+            _ => main_subject,
+        };
         
         // Generate title based on patterns
-        let title = if let Some(patterns) = self.action_patterns.get(&action) {
-            let pattern_index = (config.temperature * patterns.len() as f32) as usize;
+        let mut template = None;
+        // Unclassified changes keep the plain `Update ...` fallback unless
+        // `--action update` or a pattern file asks for the templates
+        let unclassified = forced_action.is_none() && action == "update" && !self.file_actions.contains(&action);
+        // Templates from a pattern file apply to every audience
+        let patterns = match self.technical_patterns.get(&action) {
+            _ if unclassified => None,
+            Some(patterns) if technical && !self.file_actions.contains(&action) => Some(patterns),
+            _ => self.action_patterns.get(&action),
+        };
+        let title = if let Some(patterns) = patterns {
+            let pattern_index = match forced_action {
+                Some(_) => 0,
+                None => (request.sampling.draw % patterns.len() as u64) as usize,
+            };
             let pattern = patterns.get(pattern_index).unwrap_or(&patterns[0]);
+            template = Some(pattern.clone());
             
             pattern
                 .replace("{domain}", &domain)
                 .replace("{issue}", &main_subject)
                 .replace("{feature}", &main_subject)
                 .replace("{component}", &main_subject)
+        } else if let Some(label) = file_label {
+            format!("{} {}", self.capitalize_first(&action), label)
         } else {
             // Fallback to simple pattern
             if domain.is_empty() {
@@ -196,50 +1310,82 @@ impl PatternMatcher {
             }
         };
         
-        Ok(self.clean_title(&title))
+        PatternDecision {
+            title: self.clean_title(&title),
+            action,
+            domain,
+            template,
+        }
     }
     
     fn determine_action(&self, context: &CleanContext) -> String {
-        if let Some(change_type) = &context.change_type {
-            change_type.as_str().to_string()
-        } else {
-            // Infer from commits
-            let all_text = context.commits.join(" ").to_lowercase();
-            
-            if all_text.contains("fix") || all_text.contains("bug") || all_text.contains("issue") {
-                "fix".to_string()
-            } else if all_text.contains("add") || all_text.contains("implement") || all_text.contains("feature") {
-                "feature".to_string()
-            } else if all_text.contains("refactor") || all_text.contains("improve") {
-                "refactor".to_string()
-            } else {
-                "update".to_string()
-            }
-        }
+        // Same commit classification as ContextProcessor::suggest_change_type
+        context
+            .change_type
+            .clone()
+            .or_else(|| classify_commits(&context.commits))
+            .map_or_else(|| "update".to_string(), |change_type| change_type.as_str().to_string())
     }
     
-    fn extract_domain(&self, context: &CleanContext) -> String {
+    /// Built-in domain, else learned `vocabulary` term, else first meaningful word
+    fn extract_domain(&self, context: &CleanContext, vocabulary: &[String]) -> String {
         let all_text = format!(
             "{} {}",
             context.description.as_deref().unwrap_or(""),
             context.commits.join(" ")
         ).to_lowercase();
         
-        // Look for domain keywords
+        let words: HashSet<&str> = all_text.split(|c: char| !c.is_alphanumeric()).collect();
+        
+        // Look for domain keywords in priority order, as whole words so
+        // `api` does not match `rapid`
         for (key, aliases) in &self.domain_patterns {
-            if aliases.iter().any(|alias| all_text.contains(&alias.to_lowercase())) {
+            let alias_used = aliases
+                .iter()
+                .any(|alias| words.iter().any(|word| inflects(word, &alias.to_lowercase())));
+            if alias_used {
                 return key.clone();
             }
         }
         
-        // Extract first meaningful word
+        if let Some(term) = vocabulary.iter().find(|term| words.contains(term.to_lowercase().as_str())) {
+            return term.clone();
+        }
+        
+        // Extract first meaningful word; tickets read from commits are not one
+        let is_ticket = |word: &str| {
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+            context
+                .ticket
+                .iter()
+                .chain(&context.tickets)
+                .chain(&context.extra_tickets)
+                .chain(&context.batch_tickets)
+                .any(|ticket| ticket.to_lowercase() == word)
+        };
         all_text
             .split_whitespace()
-            .find(|word| word.len() > 3 && !word.chars().all(|c| c.is_ascii_digit()))
+            .find(|word| word.len() > 3 && !word.chars().all(|c| c.is_ascii_digit()) && !is_ticket(word))
             .unwrap_or("")
             .to_string()
     }
     
+    /// Domain whose key or alias the feedback names, e.g. "auth" for "mention login"
+    fn feedback_domain(&self, feedback: &str) -> Option<String> {
+        let feedback = feedback.to_lowercase();
+        let words: HashSet<&str> = feedback
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        
+        self.domain_patterns
+            .iter()
+            .find(|(key, aliases)| {
+                words.contains(key.as_str()) || aliases.iter().any(|alias| words.contains(alias.to_lowercase().as_str()))
+            })
+            .map(|(key, _)| key.clone())
+    }
+    
     fn extract_main_subject(&self, context: &CleanContext) -> String {
         // Combine description and commits
         let mut subjects = Vec::new();
@@ -280,6 +1426,16 @@ impl PatternMatcher {
     }
 }
 
+impl TitleBackend for PatternMatcher {
+    fn name(&self) -> &'static str {
+        "pattern"
+    }
+    
+    fn generate(&self, request: &GenerationRequest<'_>) -> Result<String> {
+        Ok(self.decide(request).title)
+    }
+}
+
 // TODO: Future ML model integration using candle-rs
 #[allow(dead_code)]
 struct CandeModel {
@@ -298,8 +1454,9 @@ impl CandeModel {
         })
     }
     
-    async fn generate(&self, _prompt: &str, _config: &GeneratorConfig) -> Result<String> {
-        // Implementation will perform actual ML inference
+    async fn generate(&self, _prompt: &str, _sampling: SamplingParams) -> Result<String> {
+        // Implementation will perform actual ML inference, sampling the
+        // softmax at `sampling.temperature`
         Err(Error::ModelError {
             message: "ML inference not yet implemented".to_string(),
         })
@@ -310,6 +1467,7 @@ impl CandeModel {
 mod tests {
     use super::*;
     use crate::context::ChangeType;
+    use crate::git::ChangedFile;
     
     #[test]
     fn test_title_generator_creation() {
@@ -332,6 +1490,26 @@ mod tests {
         assert!(matches!(result, Err(Error::UnsupportedModel { .. })));
     }
     
+    #[test]
+    fn test_domain_priority_is_deterministic() {
+        let context = CleanContext {
+            commits: vec!["route login through the new endpoint".to_string()],
+            ..Default::default()
+        };
+        
+        // Fresh matchers so any per-instance ordering would show up
+        for _ in 0..10 {
+            let patterns = PatternMatcher::new().unwrap();
+            assert_eq!(patterns.extract_domain(&context, &[]), "auth");
+        }
+        
+        let rapid = CleanContext {
+            commits: vec!["rapid retries for uploads".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(PatternMatcher::new().unwrap().extract_domain(&rapid, &[]), "rapid");
+    }
+    
     #[tokio::test]
     async fn test_pattern_based_generation() {
         let config = GeneratorConfig::default();
@@ -342,10 +1520,812 @@ mod tests {
             change_type: Some(ChangeType::Fix),
             description: Some("bottle stuck issue".to_string()),
             commits: vec!["fix bottle stuck with remediation".to_string()],
+            ..Default::default()
         };
         
         let title = generator.generate_title(&context).await.unwrap();
         assert!(!title.is_empty());
         assert!(title.len() <= 72);
     }
+    
+    fn ticket_context() -> CleanContext {
+        CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            change_type: Some(ChangeType::Fix),
+            ..Default::default()
+        }
+    }
+    
+    fn post_process_with(config: GeneratorConfig, title: &str) -> String {
+        let generator = TitleGenerator::new(config).unwrap();
+        generator
+            .post_process_title(title.to_string(), &ticket_context())
+            .unwrap()
+            .title
+    }
+    
+    #[test]
+    fn test_ticket_prefix_placement() {
+        let title = post_process_with(GeneratorConfig::default(), "fix login crash on startup");
+        assert_eq!(title, "CRU-310: Fix login crash on startup");
+    }
+    
+    #[test]
+    fn test_ticket_suffix_placement() {
+        let config = GeneratorConfig::default().with_ticket_placement(TicketPlacement::Suffix);
+        let title = post_process_with(config, "fix login crash on startup");
+        assert_eq!(title, "Fix login crash on startup (CRU-310)");
+    }
+    
+    #[test]
+    fn test_ticket_none_placement() {
+        let config = GeneratorConfig::default().with_ticket_placement(TicketPlacement::None);
+        let title = post_process_with(config, "fix login crash on startup");
+        assert_eq!(title, "Fix login crash on startup");
+    }
+    
+    #[test]
+    fn test_custom_ticket_separator() {
+        let config = GeneratorConfig::default().with_ticket_separator(" - ");
+        let title = post_process_with(config, "fix login crash on startup");
+        assert_eq!(title, "CRU-310 - Fix login crash on startup");
+    }
+    
+    #[test]
+    fn test_empty_ticket_separator_is_rejected() {
+        let config = GeneratorConfig::default().with_ticket_separator("");
+        let result = TitleGenerator::new(config);
+        assert!(matches!(result, Err(Error::EmptyTicketSeparator)));
+    }
+    
+    #[test]
+    fn test_custom_ticket_template() {
+        let config = GeneratorConfig::default().with_ticket_template("[{ticket}] {title}");
+        let title = post_process_with(config, "fix login crash on startup");
+        assert_eq!(title, "[CRU-310] Fix login crash on startup");
+    }
+    
+    #[test]
+    fn test_ticket_placement_respects_length_cap() {
+        let long_title = "fix login crash ".repeat(6);
+        
+        for placement in [TicketPlacement::Prefix, TicketPlacement::Suffix] {
+            let config = GeneratorConfig::default()
+                .with_max_length(100)
+                .with_ticket_placement(placement);
+            let title = post_process_with(config, &long_title);
+            
+            assert!(title.chars().count() <= MAX_TITLE_LENGTH);
+            assert!(title.contains("CRU-310"));
+        }
+        
+        let config = GeneratorConfig::default()
+            .with_max_length(100)
+            .with_ticket_placement(TicketPlacement::Suffix);
+        assert!(post_process_with(config, &long_title).ends_with("... (CRU-310)"));
+    }
+    
+    #[test]
+    fn test_change_type_length_limit() {
+        let config = GeneratorConfig::default().with_type_max_length(ChangeType::Docs, 20);
+        let generator = TitleGenerator::new(config).unwrap();
+        let title = "document the release checklist steps".to_string();
+        
+        let docs = CleanContext {
+            change_type: Some(ChangeType::Docs),
+            ..Default::default()
+        };
+        let feature = CleanContext {
+            change_type: Some(ChangeType::Feature),
+            ..Default::default()
+        };
+        
+        assert_eq!(generator.post_process_title(title.clone(), &docs).unwrap().title, "Document the rele...");
+        assert_eq!(generator.post_process_title(title, &feature).unwrap().title, "Document the release checklist steps");
+    }
+    
+    #[test]
+    fn test_truncation_is_reported() {
+        let config = GeneratorConfig::default().with_max_length(20);
+        let generator = TitleGenerator::new(config).unwrap();
+        let context = CleanContext::default();
+        
+        let long = generator.post_process_title("document the release checklist steps".to_string(), &context).unwrap();
+        assert_eq!(long.title, "Document the rele...");
+        assert!(long.truncated);
+        assert_eq!(long.removed_suffix.as_deref(), Some("ase checklist steps"));
+        
+        let short = generator.post_process_title("fix bottle stuck".to_string(), &context).unwrap();
+        assert!(!short.truncated);
+        assert_eq!(short.removed_suffix, None);
+    }
+    
+    #[test]
+    fn test_word_limit_composes_with_length_limit() {
+        let context = CleanContext::default();
+        let title = "document the release checklist steps for hotfix branches".to_string();
+        
+        let generator = TitleGenerator::new(GeneratorConfig::default().with_max_words(5)).unwrap();
+        let capped = generator.post_process_title(title.clone(), &context).unwrap();
+        assert_eq!(capped.title, "Document the release checklist steps...");
+        assert_eq!(capped.removed_suffix.as_deref(), Some("for hotfix branches"));
+        
+        // The stricter limit wins, whichever it is
+        let generator = TitleGenerator::new(GeneratorConfig::default().with_max_words(5).with_max_length(25)).unwrap();
+        let capped = generator.post_process_title(title.clone(), &context).unwrap();
+        assert_eq!(capped.title, "Document the release c...");
+        assert_eq!(capped.removed_suffix.as_deref(), Some("hecklist steps for hotfix branches"));
+        
+        let generator = TitleGenerator::new(GeneratorConfig::default().with_max_words(3).with_max_length(40)).unwrap();
+        assert_eq!(generator.post_process_title(title, &context).unwrap().title, "Document the release...");
+        
+        let short = generator.post_process_title("fix bottle  stuck".to_string(), &context).unwrap();
+        assert_eq!(short.title, "Fix bottle  stuck");
+        assert!(!short.truncated);
+        
+        let zero = GeneratorConfig::default().with_max_words(0);
+        assert!(matches!(TitleGenerator::new(zero), Err(Error::InvalidMaxWords { words: 0 })));
+    }
+    
+    #[test]
+    fn test_unicode_ellipsis_counts_as_one_character() {
+        let config = GeneratorConfig::default().with_max_length(20).with_ellipsis("…");
+        let generator = TitleGenerator::new(config).unwrap();
+        let context = CleanContext::default();
+        
+        let long = generator.post_process_title("document the release checklist steps".to_string(), &context).unwrap();
+        assert_eq!(long.title, "Document the releas…");
+        assert_eq!(long.title.chars().count(), 20);
+        assert_eq!(long.removed_suffix.as_deref(), Some("e checklist steps"));
+        
+        let too_long = GeneratorConfig::default().with_max_length(5).with_ellipsis(" [...]");
+        assert!(matches!(TitleGenerator::new(too_long), Err(Error::InvalidEllipsis { limit: 5, .. })));
+    }
+    
+    #[test]
+    fn test_cjk_title_truncates_at_display_width() {
+        let config = GeneratorConfig::default().with_max_length(20).with_ellipsis("…");
+        let context = CleanContext::default();
+        let title = "修复登录页面在移动端无法提交表单的问题";
+        
+        // 19 characters fit 20, but they take 38 columns
+        let by_chars = TitleGenerator::new(config.clone()).unwrap();
+        assert_eq!(by_chars.post_process_title(title.to_string(), &context).unwrap().title, title);
+        
+        let by_width = TitleGenerator::new(config.clone().with_length_metric(LengthMetric::Width)).unwrap();
+        let truncated = by_width.post_process_title(title.to_string(), &context).unwrap();
+        assert_eq!(truncated.title, "修复登录页面在移动…");
+        assert_eq!(LengthMetric::Width.measure(&truncated.title), 19);
+        assert_eq!(truncated.removed_suffix.as_deref(), Some("端无法提交表单的问题"));
+        
+        let by_bytes = TitleGenerator::new(config.with_length_metric(LengthMetric::Bytes)).unwrap();
+        let truncated = by_bytes.post_process_title(title.to_string(), &context).unwrap();
+        assert_eq!(truncated.title, "修复登录页…");
+        assert!(truncated.title.len() <= 20);
+    }
+    
+    #[tokio::test]
+    async fn test_backend_name_is_reported() {
+        let context = CleanContext {
+            commits: vec!["bottle stuck with remediation system".to_string()],
+            ..Default::default()
+        };
+        
+        // No model backend is wired in yet, so a configured model falls back to patterns
+        let fallback = TitleGenerator::new(GeneratorConfig::default().with_model("phi-2")).unwrap();
+        assert_eq!(fallback.generate(&context).await.unwrap().backend, "pattern");
+        assert_eq!(fallback.explain(&context).unwrap().backend, "pattern");
+        
+        let mock = TitleGenerator::with_backend(crate::testing::MockBackend::new("fix bottle stuck"));
+        assert_eq!(mock.generate(&context).await.unwrap().backend, "mock");
+        assert_eq!(mock.explain(&context).unwrap().backend, "mock");
+        
+        // A pinned backend is the only one used
+        let pinned = |backend: &str| {
+            TitleGenerator::new(GeneratorConfig::default().with_backend(backend))
+                .unwrap()
+                .using_backend(crate::testing::MockBackend::new("fix bottle stuck"))
+        };
+        assert_eq!(pinned("pattern").generate(&context).await.unwrap().backend, "pattern");
+        assert_eq!(pinned("pattern").explain(&context).unwrap().backend, "pattern");
+        assert_eq!(pinned("mock").generate(&context).await.unwrap().backend, "mock");
+        assert!(matches!(pinned("llama-cpp").generate(&context).await, Err(Error::ModelError { .. })));
+        assert!(pinned("llama-cpp").explain(&context).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_failing_backend_falls_back_unless_disabled() {
+        let context = CleanContext {
+            change_type: Some(ChangeType::Fix),
+            commits: vec!["bottle stuck with remediation system".to_string()],
+            ..Default::default()
+        };
+        let failing = || crate::testing::MockBackend::failing("model weights not found");
+        
+        let fallback = TitleGenerator::with_backend(failing());
+        let generated = fallback.generate(&context).await.unwrap();
+        assert_eq!(generated.backend, "pattern");
+        assert!(generated.title.contains("bottle stuck"), "{}", generated.title);
+        
+        let strict = TitleGenerator::new(GeneratorConfig::default().with_pattern_fallback(false))
+            .unwrap()
+            .using_backend(failing());
+        let error = strict.generate(&context).await.unwrap_err();
+        assert!(matches!(&error, Error::ModelError { message } if message == "model weights not found"));
+        assert!(strict.generate_candidates(&context).is_err());
+        
+        // Without any model backend, the patterns would be a silent fallback too
+        let no_backend = TitleGenerator::new(GeneratorConfig::default().with_pattern_fallback(false)).unwrap();
+        assert!(matches!(no_backend.generate(&context).await, Err(Error::ModelError { .. })));
+        assert!(no_backend.explain(&context).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_raw_title_is_kept_before_ticket_is_added() {
+        let context = CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            change_type: Some(ChangeType::Fix),
+            commits: vec!["bottle stuck with remediation system".to_string()],
+            ..Default::default()
+        };
+        let generator = TitleGenerator::with_backend(crate::testing::MockBackend::new("fix bottle stuck"));
+        
+        let generated = generator.generate(&context).await.unwrap();
+        assert_eq!(generated.raw_title, "fix bottle stuck");
+        assert_eq!(generated.title, "CRU-310: Fix bottle stuck");
+        assert_ne!(generated.raw_title, generated.title);
+    }
+    
+    #[test]
+    fn test_zero_type_length_limit_is_rejected() {
+        let config = GeneratorConfig::default().with_type_max_length(ChangeType::Docs, 0);
+        assert!(matches!(TitleGenerator::new(config), Err(Error::InvalidMaxLength { .. })));
+    }
+    
+    #[test]
+    fn test_lowercase_ticket_in_title_is_normalized() {
+        let title = post_process_with(GeneratorConfig::default(), "fix cru-310 login crash");
+        assert_eq!(title, "Fix CRU-310 login crash");
+    }
+    
+    #[test]
+    fn test_list_style_for_batch_titles() {
+        let tickets: Vec<String> = ["CRU-310", "CRU-311", "CRU-312"].iter().map(|t| t.to_string()).collect();
+        let oxford = ListStyle::default();
+        let plain = ListStyle {
+            oxford_comma: false,
+            ..ListStyle::default()
+        };
+        
+        assert_eq!(batch_title(&tickets, &oxford), "Merge 3 PRs: CRU-310, CRU-311, and CRU-312");
+        assert_eq!(batch_title(&tickets, &plain), "Merge 3 PRs: CRU-310, CRU-311 and CRU-312");
+        assert_eq!(batch_title(&tickets[..2], &oxford), "Merge 2 PRs: CRU-310 and CRU-311");
+        
+        let semicolons = ListStyle {
+            separator: "; ".to_string(),
+            ..ListStyle::default()
+        };
+        let five: Vec<String> = (310..315).map(|n| format!("CRU-{}", n)).collect();
+        assert_eq!(batch_title(&five, &semicolons), "Merge 5 PRs: CRU-310; CRU-311; CRU-312; and 2 more");
+    }
+    
+    #[test]
+    fn test_dedupe_ticket_leaves_one_ticket() {
+        let config = GeneratorConfig::default().with_dedupe_ticket(true);
+        assert_eq!(post_process_with(config.clone(), "fix cru-310 login crash"), "CRU-310: Fix login crash");
+        assert_eq!(
+            post_process_with(config.clone(), "Correct bottle [CRU-310] fix: bottle stuck"),
+            "CRU-310: Correct bottle fix: bottle stuck"
+        );
+        assert_eq!(
+            post_process_with(config.clone().with_ticket_placement(TicketPlacement::Suffix), "CRU-310: fix login crash"),
+            "Fix login crash (CRU-310)"
+        );
+        // Nothing specific would be left, so the ticket stays where it is
+        assert_eq!(post_process_with(config, "CRU-310 fix"), "CRU-310 fix");
+    }
+    
+    #[tokio::test]
+    async fn test_dedupe_ticket_in_repeated_description() {
+        let context = CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            change_type: Some(ChangeType::Fix),
+            description: Some("cru-310 bottle stuck".to_string()),
+            commits: vec!["fix cru-310 bottle stuck in remediation".to_string()],
+            ..Default::default()
+        };
+        let generator = TitleGenerator::new(GeneratorConfig::default().with_dedupe_ticket(true)).unwrap();
+        
+        let title = generator.generate_title(&context).await.unwrap();
+        assert!(title.starts_with("CRU-310: "), "{}", title);
+        assert_eq!(title.to_uppercase().matches("CRU-310").count(), 1, "{}", title);
+    }
+    
+    #[test]
+    fn test_case_exceptions_keep_their_spelling() {
+        let config = GeneratorConfig::default().with_ticket_placement(TicketPlacement::None);
+        assert_eq!(post_process_with(config.clone(), "ios bugfix"), "iOS bugfix");
+        assert_eq!(post_process_with(config.clone(), "Sync GITHUB labels via the api"), "Sync GitHub labels via the API");
+        assert_eq!(post_process_with(config.clone().with_capitalize(false), "ios bugfix"), "iOS bugfix");
+        
+        // File names and scopes are left as written
+        assert_eq!(post_process_with(config.clone(), "Update api.rs and src/url_parser"), "Update api.rs and src/url_parser");
+        assert_eq!(post_process_with(config.clone(), "Fix(api) login via the api"), "Fix(api) login via the API");
+        
+        let config = config.with_case_exceptions(["gRPC"]);
+        assert_eq!(post_process_with(config, "grpc retries for ios"), "gRPC retries for ios");
+    }
+    
+    #[test]
+    fn test_tested_subject_drops_test_wording() {
+        assert_eq!(tested_subject("cover retries in fetch client"), "retries in fetch client");
+        assert_eq!(tested_subject("Add unit tests for retry backoff"), "retry backoff");
+        assert_eq!(tested_subject("adding more tests"), "tests");
+        assert_eq!(tested_subject("retry backoff"), "retry backoff");
+    }
+    
+    #[test]
+    fn test_no_capitalize_keeps_lowercase_title() {
+        let config = GeneratorConfig::default().with_capitalize(false);
+        let title = post_process_with(config, "fix cru-310 login crash");
+        assert_eq!(title, "fix CRU-310 login crash");
+    }
+    
+    #[tokio::test]
+    async fn test_forced_action_overrides_change_type() {
+        let config = GeneratorConfig::default().with_action("remove");
+        let generator = TitleGenerator::new(config).unwrap();
+        let context = CleanContext {
+            change_type: Some(ChangeType::Feature),
+            description: Some("legacy wallet sync".to_string()),
+            commits: vec!["add wallet sync fallback".to_string()],
+            ..Default::default()
+        };
+        
+        let title = generator.generate_title(&context).await.unwrap();
+        assert!(title.starts_with("Remove "), "unexpected title: {}", title);
+        
+        let update = TitleGenerator::new(GeneratorConfig::default().with_action("update")).unwrap();
+        let title = update.generate_title(&context).await.unwrap();
+        assert!(title.starts_with("Update crypto "), "unexpected title: {}", title);
+    }
+    
+    #[tokio::test]
+    async fn test_dominant_commit_verb_overrides_change_type() {
+        let context = CleanContext {
+            change_type: Some(ChangeType::Refactor),
+            commits: vec![
+                "migrate auth to OAuth".to_string(),
+                "migrating session store".to_string(),
+                "tidy login form".to_string(),
+            ],
+            ..Default::default()
+        };
+        
+        let generator = TitleGenerator::new(GeneratorConfig::default()).unwrap();
+        assert_eq!(generator.generate_title(&context).await.unwrap(), "Migrate auth to OAuth");
+        
+        // One commit in three does not dominate, and the verb list is configurable
+        let tidy = GeneratorConfig::default().with_strong_verbs(["tidy"]);
+        let title = TitleGenerator::new(tidy).unwrap().generate_title(&context).await.unwrap();
+        assert!(!title.starts_with("Migrate") && !title.starts_with("Tidy"), "{}", title);
+        
+        let forced = TitleGenerator::new(GeneratorConfig::default().with_action("fix")).unwrap();
+        assert!(!forced.generate_title(&context).await.unwrap().starts_with("Migrate"));
+        assert!(inflects("migrated", "migrate") && inflects("splits", "split") && !inflects("migration", "migrate"));
+    }
+    
+    fn changed(path: &str, lines_changed: usize) -> ChangedFile {
+        ChangedFile {
+            path: path.to_string(),
+            lines_changed,
+            added: false,
+        }
+    }
+    
+    #[test]
+    fn test_single_file_named_in_title() {
+        let patterns = PatternMatcher::new().unwrap();
+        let config = GeneratorConfig::default().with_filenames(true);
+        let context = CleanContext {
+            commits: vec!["reword the install steps".to_string()],
+            files: vec![changed("docs/README.md", 12)],
+            ..Default::default()
+        };
+        
+        assert_eq!(patterns.generate(&GenerationRequest::new(&context, &config)).unwrap(), "Update README");
+        
+        let context = CleanContext {
+            change_type: Some(ChangeType::Fix),
+            files: vec![changed("src/config.rs", 8)],
+            ..context
+        };
+        let title = patterns.generate(&GenerationRequest::new(&context, &config)).unwrap();
+        assert!(title.contains("config.rs"), "unexpected title: {}", title);
+    }
+    
+    #[test]
+    fn test_multi_file_branch_names_only_dominant_file() {
+        let patterns = PatternMatcher::new().unwrap();
+        let config = GeneratorConfig::default().with_filenames(true);
+        let spread = CleanContext {
+            commits: vec!["reword the install steps".to_string()],
+            files: vec![changed("README.md", 10), changed("CONTRIBUTING.md", 10)],
+            ..Default::default()
+        };
+        let dominant = CleanContext {
+            files: vec![changed("README.md", 90), changed("CONTRIBUTING.md", 2)],
+            ..spread.clone()
+        };
+        
+        assert!(!patterns.generate(&GenerationRequest::new(&spread, &config)).unwrap().contains("README"));
+        assert_eq!(patterns.generate(&GenerationRequest::new(&dominant, &config)).unwrap(), "Update README");
+    }
+    
+    #[test]
+    fn test_emphasize_dominant_uses_the_dominant_module() {
+        let patterns = PatternMatcher::new().unwrap();
+        let lopsided = CleanContext {
+            change_type: Some(ChangeType::Feature),
+            commits: vec!["tune ranking weights for fuzzy matches".to_string()],
+            files: vec![changed("src/search/index.rs", 70), changed("src/billing.rs", 15), changed("README.md", 15)],
+            ..Default::default()
+        };
+        let title = |config: &GeneratorConfig| patterns.generate(&GenerationRequest::new(&lopsided, config)).unwrap();
+        
+        assert_eq!(title(&GeneratorConfig::default().with_emphasize_dominant(0.6)), "Implement search tune ranking weights for fuzzy matches");
+        // 70% of the lines is not enough for a stricter threshold, or without the option
+        assert!(!title(&GeneratorConfig::default().with_emphasize_dominant(0.75)).contains("search"));
+        assert!(!title(&GeneratorConfig::default()).contains("search"));
+        
+        let invalid = GeneratorConfig::default().with_emphasize_dominant(1.5);
+        assert!(matches!(TitleGenerator::new(invalid), Err(Error::InvalidDominanceThreshold { .. })));
+    }
+    
+    #[tokio::test]
+    async fn test_fail_on_generic_rejects_generic_title() {
+        let config = GeneratorConfig::default().with_fail_on_generic(true);
+        let generator = TitleGenerator::new(config)
+            .unwrap()
+            .using_backend(crate::testing::MockBackend::new("update changes"));
+        
+        let error = generator.generate_title(&ticket_context()).await.unwrap_err();
+        assert!(matches!(error, Error::GenericTitle { .. }));
+        assert_eq!(error.exit_code(), 3);
+    }
+    
+    #[tokio::test]
+    async fn test_fail_on_generic_accepts_specific_title() {
+        let config = GeneratorConfig::default().with_fail_on_generic(true);
+        let generator = TitleGenerator::new(config)
+            .unwrap()
+            .using_backend(crate::testing::MockBackend::new("fix login crash on startup"));
+        
+        let title = generator.generate_title(&ticket_context()).await.unwrap();
+        assert_eq!(title, "CRU-310: Fix login crash on startup");
+    }
+    
+    #[tokio::test]
+    async fn test_pattern_file_template_is_used() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "[actions]\nfix = [\"Repair {domain} {issue}\"]\n").unwrap();
+        
+        let config = GeneratorConfig::default().with_pattern_file(file.path());
+        let generator = TitleGenerator::new(config).unwrap();
+        let context = CleanContext {
+            change_type: Some(ChangeType::Fix),
+            commits: vec!["wallet balance rounding".to_string()],
+            ..Default::default()
+        };
+        
+        let title = generator.generate_title(&context).await.unwrap();
+        assert_eq!(title, "Repair crypto wallet balance rounding");
+    }
+    
+    #[test]
+    fn test_templates_list_pattern_file_overrides() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "[actions]\nfix = [\"Repair {domain} {issue}\"]\n").unwrap();
+        
+        let config = GeneratorConfig::default().with_pattern_file(file.path());
+        let templates = TitleGenerator::new(config).unwrap().templates();
+        
+        let fix: Vec<&ActionTemplate> = templates.iter().filter(|template| template.action == "fix").collect();
+        assert_eq!(fix.len(), 1);
+        assert_eq!(fix[0].template, "Repair {domain} {issue}");
+        assert_eq!(fix[0].source, TemplateSource::PatternFile);
+        assert!(templates
+            .iter()
+            .any(|template| template.template == "Add {domain} {feature}" && template.source == TemplateSource::BuiltIn));
+    }
+    
+    #[test]
+    fn test_unknown_action_is_rejected() {
+        let config = GeneratorConfig::default().with_action("rewrite");
+        let result = TitleGenerator::new(config);
+        assert!(matches!(result, Err(Error::InvalidAction { .. })));
+    }
+    
+    #[test]
+    fn test_specific_title_scores_higher_than_generic() {
+        let generator = TitleGenerator::new(GeneratorConfig::default()).unwrap();
+        let context = CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            change_type: Some(ChangeType::Fix),
+            description: Some("bottle stuck issue".to_string()),
+            commits: vec!["bottle stuck with remediation system".to_string()],
+            ..Default::default()
+        };
+        
+        let specific = generator.score_title("CRU-310: Fix bottle stuck in remediation system", &context);
+        let generic = generator.score_title("Update changes", &context);
+        
+        assert!(specific > generic);
+        assert!((0.0..=1.0).contains(&specific));
+        assert!((0.0..=1.0).contains(&generic));
+    }
+    
+    #[test]
+    fn test_empty_title_scores_zero() {
+        let generator = TitleGenerator::new(GeneratorConfig::default()).unwrap();
+        assert_eq!(generator.score_title("", &ticket_context()), 0.0);
+    }
+    
+    #[tokio::test]
+    async fn test_custom_post_processor_runs_last() {
+        let generator = TitleGenerator::new(GeneratorConfig::default())
+            .unwrap()
+            .with_post_processor(|title, _context| format!("{} [team]", title));
+        let context = CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            change_type: Some(ChangeType::Fix),
+            description: Some("bottle stuck issue".to_string()),
+            ..Default::default()
+        };
+        
+        let title = generator.generate_title(&context).await.unwrap();
+        assert!(title.starts_with("CRU-310: "));
+        assert!(title.ends_with(" [team]"));
+    }
+    
+    #[tokio::test]
+    async fn test_post_process_command_from_config() {
+        let config = GeneratorConfig::default().with_post_process_command("tr a-z A-Z");
+        let generator = TitleGenerator::new(config).unwrap();
+        let context = CleanContext {
+            description: Some("bottle stuck issue".to_string()),
+            ..Default::default()
+        };
+        
+        let title = generator.generate_title(&context).await.unwrap();
+        assert_eq!(title, title.to_uppercase());
+        
+        // Candidates are not piped through the command, so it runs once per title
+        let failing = TitleGenerator::new(GeneratorConfig::default().with_post_process_command("exit 3")).unwrap();
+        assert!(failing.generate_title(&context).await.is_err());
+        assert!(!failing.generate_candidates(&context).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_invalid_ticket_template() {
+        let config = GeneratorConfig::default().with_ticket_template("[{ticket}]");
+        let result = TitleGenerator::new(config);
+        assert!(matches!(result, Err(Error::InvalidTicketTemplate { .. })));
+    }
+    
+    /// Backend that records the sampling temperature of each request
+    #[derive(Default)]
+    struct RecordingBackend {
+        temperatures: std::sync::Arc<std::sync::Mutex<Vec<f32>>>,
+        prompts: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    }
+    
+    impl TitleBackend for RecordingBackend {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+        
+        fn generate(&self, request: &GenerationRequest<'_>) -> Result<String> {
+            self.temperatures.lock().unwrap().push(request.sampling.temperature);
+            self.prompts.lock().unwrap().push(request.prompt());
+            Ok("Fix login crash on startup".to_string())
+        }
+    }
+    
+    #[test]
+    fn test_technical_prompt_asks_about_implementation() {
+        let context = ticket_context();
+        let config = GeneratorConfig::default();
+        let user_facing = GenerationRequest::new(&context, &config).prompt();
+        let technical = GenerationRequest {
+            audience: Audience::Technical,
+            ..GenerationRequest::new(&context, &config)
+        }
+        .prompt();
+        
+        assert!(user_facing.contains("Prioritize user-facing impact"));
+        assert!(technical.contains("Focus on how it was implemented"));
+        assert!(!technical.contains("Prioritize user-facing impact"));
+    }
+    
+    #[tokio::test]
+    async fn test_technical_title_uses_technical_templates() {
+        let generator = TitleGenerator::new(GeneratorConfig::default()).unwrap();
+        let mut context = CleanContext {
+            change_type: Some(ChangeType::Fix),
+            commits: vec!["login redirect loops forever".to_string()],
+            ..Default::default()
+        };
+        
+        let user_facing = generator.generate_for(&context, Audience::UserFacing).await.unwrap();
+        let technical = generator.generate_for(&context, Audience::Technical).await.unwrap();
+        assert_eq!(user_facing.title, "Resolve auth login redirect loops forever");
+        assert_eq!(technical.title, "Handle auth login redirect loops forever");
+        
+        // Technical titles name the dominant file even without `use_filenames`
+        context.files = vec![changed("src/session.rs", 40)];
+        assert_eq!(generator.generate_for(&context, Audience::Technical).await.unwrap().title, "Handle session.rs login redirect loops forever");
+    }
+    
+    #[test]
+    fn test_request_prompt_uses_configured_model() {
+        let context = CleanContext::default();
+        let config = GeneratorConfig::default().with_model("gemma-2b");
+        
+        let prompt = GenerationRequest::new(&context, &config).prompt();
+        assert_eq!(prompt, context.to_prompt_for(&Model::Gemma2b));
+    }
+    
+    #[tokio::test]
+    async fn test_temperature_is_passed_to_sampler() {
+        let temperatures = std::sync::Arc::default();
+        let generator = TitleGenerator::new(GeneratorConfig::default().with_temperature(0.3))
+            .unwrap()
+            .using_backend(RecordingBackend {
+                temperatures: std::sync::Arc::clone(&temperatures),
+                ..Default::default()
+            });
+        let context = CleanContext::default();
+        
+        generator.generate_title(&context).await.unwrap();
+        assert_eq!(*temperatures.lock().unwrap(), [0.3]);
+        
+        generator.generate_candidates(&context).unwrap();
+        assert_eq!(*temperatures.lock().unwrap(), [0.3, 0.3, 0.1, 0.5, 0.9]);
+    }
+    
+    /// RNG cycling through fixed draws
+    struct FixedRng {
+        draws: &'static [u64],
+        next: usize,
+    }
+    
+    impl TitleRng for FixedRng {
+        fn next_u64(&mut self) -> u64 {
+            let draw = self.draws[self.next % self.draws.len()];
+            self.next += 1;
+            draw
+        }
+    }
+    
+    #[test]
+    fn test_injected_rng_decides_candidate_order() {
+        let context = CleanContext {
+            change_type: Some(ChangeType::Fix),
+            commits: vec!["login redirect loops forever".to_string()],
+            ..Default::default()
+        };
+        let candidates = |draws: &'static [u64]| {
+            TitleGenerator::new(GeneratorConfig::default())
+                .unwrap()
+                .with_rng(move || FixedRng { draws, next: 0 })
+                .generate_candidates(&context)
+                .unwrap()
+                .into_iter()
+                .map(|candidate| candidate.title)
+                .collect::<Vec<_>>()
+        };
+        
+        let forward = candidates(&[0, 1, 2, 3]);
+        assert_eq!(forward, vec![
+            "Resolve auth login redirect loops forever",
+            "Correct auth login redirect loops forever",
+            "Fix auth login redirect loops forever",
+        ]);
+        assert_eq!(candidates(&[0, 1, 2, 3]), forward);
+        assert_eq!(candidates(&[3, 2, 1, 0]), vec![
+            "Correct auth login redirect loops forever",
+            "Resolve auth login redirect loops forever",
+            "Fix auth login redirect loops forever",
+        ]);
+        // The same draw every time leaves one distinct candidate
+        assert_eq!(candidates(&[1]).len(), 1);
+        
+        // The default RNG is seeded with `seed`
+        let seeded = |seed| TitleGenerator::new(GeneratorConfig::default().with_seed(seed)).unwrap().generate_candidates(&context).unwrap();
+        assert_eq!(seeded(7), seeded(7));
+    }
+    
+    #[tokio::test]
+    async fn test_regenerate_puts_feedback_in_prompt() {
+        let prompts = std::sync::Arc::default();
+        let generator = TitleGenerator::new(GeneratorConfig::default())
+            .unwrap()
+            .using_backend(RecordingBackend {
+                prompts: std::sync::Arc::clone(&prompts),
+                ..Default::default()
+            });
+        let context = CleanContext::default();
+        
+        let title = generator.regenerate(&context, "Update changes", "too vague").await.unwrap();
+        assert_eq!(title, "Fix login crash on startup");
+        
+        let prompts = prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].contains("The previous title was \"Update changes\""));
+        assert!(prompts[0].contains("Feedback: too vague"));
+    }
+    
+    #[tokio::test]
+    async fn test_regenerate_feedback_biases_pattern_domain() {
+        let generator = TitleGenerator::new(GeneratorConfig::default()).unwrap();
+        let context = CleanContext {
+            change_type: Some(ChangeType::Fix),
+            commits: vec!["redirect loops after session expiry".to_string()],
+            ..Default::default()
+        };
+        
+        let previous = generator.generate_title(&context).await.unwrap();
+        let revised = generator.regenerate(&context, &previous, "mention auth").await.unwrap();
+        
+        assert!(!previous.contains("auth"), "{}", previous);
+        assert!(revised.contains("auth"), "{}", revised);
+    }
+    
+    /// Returns a different title for each candidate temperature
+    struct TemperatureBackend;
+    
+    impl TitleBackend for TemperatureBackend {
+        fn name(&self) -> &'static str {
+            "temperature"
+        }
+        
+        fn generate(&self, request: &GenerationRequest<'_>) -> Result<String> {
+            let title = match request.sampling.temperature {
+                t if t < 0.3 => "Fix login redirect loop after session expiry",
+                t if t < 0.6 => "Fix the issue",
+                t if t < 0.8 => "Fix login redirect",
+                _ => "Update login session handling for the redirect flow",
+            };
+            Ok(title.to_string())
+        }
+    }
+    
+    #[test]
+    fn test_rank_candidates_by() {
+        let context = CleanContext {
+            commits: vec!["login redirect loops after session expiry".to_string()],
+            ..Default::default()
+        };
+        let ranked = |rank_by| -> Vec<String> {
+            TitleGenerator::new(GeneratorConfig::default().with_rank_by(rank_by))
+                .unwrap()
+                .using_backend(TemperatureBackend)
+                .generate_candidates(&context)
+                .unwrap()
+                .into_iter()
+                .map(|candidate| candidate.title)
+                .collect()
+        };
+        
+        let by_score = ranked(RankBy::Score);
+        let by_length = ranked(RankBy::Length);
+        let by_specificity = ranked(RankBy::Specificity);
+        
+        assert_eq!(by_length[0], "Fix the issue");
+        assert!(by_length.windows(2).all(|pair| pair[0].len() <= pair[1].len()));
+        assert_ne!(by_score, by_length);
+        assert_eq!(by_specificity[0], "Fix login redirect loop after session expiry");
+        assert_eq!(by_specificity.last().unwrap(), "Fix the issue");
+    }
 }
\ No newline at end of file
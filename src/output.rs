@@ -0,0 +1,260 @@
+//! Machine-readable output for editors and other integrations
+//!
+//! `--format json` prints an [`OutputPayload`], `--trace-json` an
+//! [`Explanation`] and `dump-context --format json` a [`ContextDump`]. The
+//! shapes are versioned by [`SCHEMA_VERSION`], which is bumped whenever a
+//! field is removed, renamed or changes meaning; adding a field is not a
+//! breaking change.
+
+use crate::context::{classify_commit, ChangeType, CleanContext, DropReason, DroppedCommit};
+use crate::git::CommitInfo;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Version of the `OutputPayload`, `Explanation` and `ContextDump` shapes
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything known about a generated title
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputPayload {
+    pub schema_version: u32,
+    /// The title `generate_branch_title` would return
+    pub title: String,
+    /// Backend output before truncation, capitalization, ticket placement and user hooks
+    pub raw_title: String,
+    /// Backend that produced the title, e.g. "pattern"
+    pub backend: String,
+    /// Whether a length limit shortened the title
+    pub truncated: bool,
+    /// Text truncation removed from the title
+    pub removed_suffix: Option<String>,
+    /// Alternative titles, best score first
+    pub candidates: Vec<TitleCandidate>,
+    /// Context the title was generated from
+    pub context: CleanContext,
+    /// Characters the context's prompt takes in the configured model's chat template
+    #[serde(default)]
+    pub prompt_size: usize,
+    pub timings: Timings,
+    /// Commits left out of the context
+    pub dropped_commits: Vec<DroppedCommit>,
+    /// Every analyzed commit, newest first, kept or not
+    pub commits: Vec<AnalyzedCommit>,
+    /// Where the branch stands against its base; unknown for commits read elsewhere
+    pub divergence: Option<Divergence>,
+    /// Titles for reviewers and for users; only generated with `audience_titles`
+    pub titles: Option<AudienceTitles>,
+}
+
+/// A branch's analysis without a title, printed by `dump-context`
+///
+/// `--from-context` generates the title from `context` alone, skipping git
+/// and commit cleaning, so a saved dump is a fixture that pins the input of a
+/// regression test while the config under test changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextDump {
+    pub schema_version: u32,
+    pub branch: String,
+    pub base: Option<String>,
+    /// Commits as read from git, newest first, before cleaning
+    pub commits: Vec<CommitInfo>,
+    /// Context a title would be generated from
+    pub context: CleanContext,
+}
+
+/// The same change titled for two audiences
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudienceTitles {
+    /// Implementation-focused, e.g. naming the changed component
+    pub technical: String,
+    /// Impact-focused; the same as `OutputPayload::title`
+    pub user_facing: String,
+}
+
+/// How far a branch and its base have moved apart since their merge base
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Divergence {
+    pub merge_base: String,
+    /// Commits on the branch that are not on the base
+    pub ahead: usize,
+    /// Commits on the base that are not on the branch
+    pub behind: usize,
+}
+
+/// A commit with the change type its subject suggests and whether it was kept
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalyzedCommit {
+    pub hash: String,
+    pub subject: String,
+    #[serde(rename = "type")]
+    pub change_type: Option<ChangeType>,
+    /// Whether the commit made it into the context
+    pub kept: bool,
+    pub drop_reason: Option<DropReason>,
+}
+
+/// A possible title with its `score_title` score from 0.0 to 1.0
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TitleCandidate {
+    pub title: String,
+    pub score: f32,
+}
+
+/// Time spent in each stage, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timings {
+    /// Reading commits and changed files; zero when commits came from elsewhere
+    pub git_ms: u64,
+    /// Building the cleaned context
+    pub context_ms: u64,
+    /// Generating the title and candidates
+    pub generation_ms: u64,
+}
+
+/// Decisions behind a title, stable enough for snapshot tests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Explanation {
+    pub schema_version: u32,
+    /// Backend that produced the raw title, e.g. "pattern"
+    pub backend: String,
+    pub ticket: Option<String>,
+    pub change_type: Option<ChangeType>,
+    /// Action the pattern backend chose, e.g. "fix" or "feature"
+    pub action: Option<String>,
+    /// Domain the pattern backend filled into the template
+    pub domain: Option<String>,
+    /// Template the raw title was filled from
+    pub template: Option<String>,
+    /// Title before truncation, capitalization, ticket placement and user hooks
+    pub raw_title: String,
+    /// Title after all post-processing
+    pub title: String,
+    /// Whether a length limit shortened the title
+    pub truncated: bool,
+    /// Text truncation removed, without the ellipsis it left behind
+    pub removed_suffix: Option<String>,
+    /// Characters the context's prompt takes in the configured model's chat template
+    #[serde(default)]
+    pub prompt_size: usize,
+}
+
+/// Label each of `commits` with its change type and why it was dropped, if it was
+pub(crate) fn analyze_commits(commits: &[CommitInfo], dropped: &[DroppedCommit]) -> Vec<AnalyzedCommit> {
+    commits
+        .iter()
+        .map(|commit| {
+            let subject = commit.subject();
+            let drop_reason = dropped
+                .iter()
+                .find(|dropped| dropped.hash == commit.hash)
+                .map(|dropped| dropped.reason);
+            AnalyzedCommit {
+                hash: commit.hash.clone(),
+                change_type: classify_commit(&subject),
+                kept: drop_reason.is_none(),
+                drop_reason,
+                subject,
+            }
+        })
+        .collect()
+}
+
+/// Whole milliseconds in `duration`, saturating at `u64::MAX`
+pub(crate) fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ml::TitleGenerator;
+    use crate::GeneratorConfig;
+
+    #[test]
+    fn test_payload_round_trip() {
+        let payload = OutputPayload {
+            schema_version: SCHEMA_VERSION,
+            title: "CRU-310: Fix bottle stuck".to_string(),
+            raw_title: "fix bottle stuck".to_string(),
+            backend: "pattern".to_string(),
+            truncated: false,
+            removed_suffix: None,
+            candidates: vec![TitleCandidate {
+                title: "CRU-310: Fix bottle stuck".to_string(),
+                score: 0.75,
+            }],
+            context: CleanContext {
+                ticket: Some("CRU-310".to_string()),
+                change_type: Some(ChangeType::Fix),
+                commits: vec!["bottle stuck with remediation system".to_string()],
+                ..Default::default()
+            },
+            prompt_size: 180,
+            timings: Timings {
+                git_ms: 3,
+                context_ms: 1,
+                generation_ms: 2,
+            },
+            dropped_commits: vec![DroppedCommit {
+                hash: "abc123".to_string(),
+                subject: "Merge branch 'main'".to_string(),
+                reason: DropReason::MergeOrRevert,
+            }],
+            commits: vec![AnalyzedCommit {
+                hash: "def456".to_string(),
+                subject: "fix: bottle stuck with remediation system".to_string(),
+                change_type: Some(ChangeType::Fix),
+                kept: true,
+                drop_reason: None,
+            }],
+            divergence: Some(Divergence {
+                merge_base: "0e5be3c".to_string(),
+                ahead: 1,
+                behind: 0,
+            }),
+            titles: Some(AudienceTitles {
+                technical: "CRU-310: Handle bottle stuck".to_string(),
+                user_facing: "CRU-310: Fix bottle stuck".to_string(),
+            }),
+        };
+
+        let json = serde_json::to_string(&payload).unwrap();
+        assert!(json.contains(r#""schema_version":1"#));
+        assert!(json.contains(r#""change_type":"fix""#));
+        assert!(json.contains(r#""reason":"merge_or_revert""#));
+
+        let parsed: OutputPayload = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, payload);
+    }
+
+    #[test]
+    fn test_explanation_snapshot() {
+        let context = CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            change_type: Some(ChangeType::Fix),
+            description: Some("login redirect".to_string()),
+            commits: vec!["login redirect loops forever".to_string()],
+            ..Default::default()
+        };
+        let generator = TitleGenerator::new(GeneratorConfig::default()).unwrap();
+
+        let trace = serde_json::to_string_pretty(&generator.explain(&context).unwrap()).unwrap();
+        assert_eq!(
+            trace,
+            r#"{
+  "schema_version": 1,
+  "backend": "pattern",
+  "ticket": "CRU-310",
+  "change_type": "fix",
+  "action": "fix",
+  "domain": "auth",
+  "template": "Resolve {domain} {issue}",
+  "raw_title": "Resolve auth login redirect loops forever",
+  "title": "CRU-310: Resolve auth login redirect loops forever",
+  "truncated": false,
+  "removed_suffix": null,
+  "prompt_size": 793
+}"#
+        );
+    }
+}
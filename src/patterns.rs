@@ -0,0 +1,125 @@
+//! Action templates loaded from a TOML pattern file
+//!
+//! ```toml
+//! mode = "extend"   # or "replace" to drop the built-in templates
+//!
+//! [actions]
+//! fix = ["Repair {domain} {issue}"]
+//! docs = ["Document {domain} {component}"]
+//! ```
+
+use crate::{Error, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Placeholders the pattern backend fills in
+pub const PLACEHOLDERS: [&str; 4] = ["domain", "issue", "feature", "component"];
+
+/// How file templates combine with the built-in ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternMode {
+    /// Actions in the file override the same built-in actions and add new ones
+    #[default]
+    Extend,
+    /// Only the actions in the file are used
+    Replace,
+}
+
+/// Where a template in the effective set comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateSource {
+    BuiltIn,
+    PatternFile,
+}
+
+/// A template the pattern backend may fill in for an action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActionTemplate {
+    pub action: String,
+    pub template: String,
+    pub source: TemplateSource,
+}
+
+/// Action to template mappings for the pattern backend
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatternFile {
+    #[serde(default)]
+    pub mode: PatternMode,
+    #[serde(default)]
+    pub actions: HashMap<String, Vec<String>>,
+}
+
+impl PatternFile {
+    /// Read and validate a pattern file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let invalid = |message: String| Error::InvalidPatternFile {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let file: PatternFile = toml::from_str(&text).map_err(|e| invalid(e.message().to_string()))?;
+        file.validate()?;
+        Ok(file)
+    }
+
+    /// Reject templates that use placeholders the backend does not fill
+    fn validate(&self) -> Result<()> {
+        let placeholder = Regex::new(r"\{([^{}]*)\}")?;
+
+        for template in self.actions.values().flatten() {
+            for captures in placeholder.captures_iter(template) {
+                if !PLACEHOLDERS.contains(&&captures[1]) {
+                    return Err(Error::UnknownPlaceholder {
+                        placeholder: captures[0].to_string(),
+                        template: template.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn write_pattern_file(content: &str) -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), content).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_load_pattern_file() {
+        let file = write_pattern_file(
+            r#"
+mode = "replace"
+
+[actions]
+fix = ["Repair {domain} {issue}"]
+"#,
+        );
+
+        let patterns = PatternFile::load(file.path()).unwrap();
+        assert_eq!(patterns.mode, PatternMode::Replace);
+        assert_eq!(patterns.actions["fix"], ["Repair {domain} {issue}"]);
+    }
+
+    #[test]
+    fn test_unknown_placeholder_is_rejected() {
+        let file = write_pattern_file("[actions]\nfix = [\"Fix {module} {issue}\"]\n");
+
+        let result = PatternFile::load(file.path());
+        assert!(matches!(result, Err(Error::UnknownPlaceholder { placeholder, .. }) if placeholder == "{module}"));
+    }
+}
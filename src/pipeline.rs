@@ -0,0 +1,1458 @@
+//! End-to-end title generation over an already opened repository
+//!
+//! These functions take a `&GitRepo` instead of a path so long-running
+//! consumers can open the repository once and title many branches.
+//! `generate_commits_title` skips git entirely for commits read elsewhere.
+//! The `_output` variants return the full `OutputPayload` instead of the title,
+//! and the `explain_` functions trace the decisions behind it.
+//! `dump_branch_context` stops before generation, and `generate_context_title`
+//! picks up from its context.
+
+use crate::{
+    cache::TitleCache,
+    context::{compile_branch_prefixes, compile_release_pattern, Audience, CleanContext, ContextProcessor, is_merge_queue_branch, BATCH_MIN_TICKETS, SCAFFOLD_MAX_BASE_FILES},
+    git::{ChangedFile, CommitFilter, CommitInfo, GitRepo, NearestTag},
+    ml::{GeneratedTitle, TitleGenerator},
+    output::{analyze_commits, millis, AudienceTitles, ContextDump, Divergence, Explanation, OutputPayload, Timings, SCHEMA_VERSION},
+    tickets::project_key,
+    vocab::learn_vocabulary,
+    error::PatternCompiler,
+    Error, GeneratorConfig, Model, PromptPolicy, Result,
+};
+use regex::Regex;
+use std::borrow::Cow;
+use std::fmt;
+use std::time::Instant;
+use tracing::{debug, info_span};
+
+/// Recent base commits searched for the branch's ticket by `find_merged_ticket`
+const MERGED_TICKET_SAMPLE: usize = 100;
+
+/// A base commit that already landed work for the branch's ticket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedTicket {
+    pub ticket: String,
+    pub hash: String,
+    pub subject: String,
+}
+
+impl fmt::Display for MergedTicket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let short_hash: String = self.hash.chars().take(7).collect();
+        write!(f, "{} already merged in {} ({})", self.ticket, short_hash, self.subject)
+    }
+}
+
+/// A step of title generation, for progress indicators in front ends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    GitReadStarted,
+    /// The branch's commits were read, after filtering
+    GitReadFinished { commits: usize },
+    /// Commits were cleaned, and `kept` of the `analyzed` ones made it into the context
+    CommitsProcessed { analyzed: usize, kept: usize },
+    GenerationStarted,
+    GenerationFinished,
+}
+
+/// Receiver of progress events; `Sync` so generation futures stay `Send`
+pub type ProgressCallback<'a> = &'a (dyn Fn(ProgressEvent) + Sync);
+
+/// Generate a PR title for `branch` compared against `base`
+pub async fn generate_branch_title(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+) -> Result<String> {
+    generate_branch_title_with_progress(repo, base, branch, config, None).await
+}
+
+/// Generate a PR title like `generate_branch_title`, reporting each step to `progress`
+pub async fn generate_branch_title_with_progress(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<String> {
+    report(progress, ProgressEvent::GitReadStarted);
+    let (commits, details) = read_branch(repo, base, branch, config)?;
+    let config = &with_learned_vocabulary(repo, base, config)?;
+    report(progress, ProgressEvent::GitReadFinished { commits: commits.len() });
+
+    generate_title(branch, Some(base), &commits, details, config, progress).await
+}
+
+/// Generate the title, candidates and diagnostics for `branch` compared against `base`
+pub async fn generate_branch_output(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+) -> Result<OutputPayload> {
+    let started = Instant::now();
+    let (commits, details) = read_branch(repo, base, branch, config)?;
+    let config = &with_learned_vocabulary(repo, base, config)?;
+    let git_ms = millis(started.elapsed());
+    
+    generate_output(branch, Some(base), &commits, details, config, git_ms).await
+}
+
+/// Cleaned subjects of the meaningful commits on `branch` compared against `base`
+///
+/// Runs the git read and commit cleaning of `generate_branch_title` without
+/// generating a title, for tools that summarize commits themselves. These are
+/// every meaningful subject, newest first, before commits on one topic are
+/// merged and the rest cut to `summary_size`.
+pub fn clean_branch_commits(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+) -> Result<Vec<String>> {
+    let (commits, _) = read_branch(repo, base, branch, config)?;
+    let context_processor = context_processor(config)?;
+    Ok(context_processor.filter_meaningful_commits(&context_processor.clean_commit_messages(&commits)))
+}
+
+/// The commits of `branch` compared against `base` and the context built from
+/// them, for replaying through `generate_context_title`
+pub fn dump_branch_context(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+) -> Result<ContextDump> {
+    let (commits, details) = read_branch(repo, base, branch, config)?;
+    let context = build_context(&context_processor(config)?, branch, Some(base), &commits, details, config)?;
+    Ok(ContextDump {
+        schema_version: SCHEMA_VERSION,
+        branch: branch.to_string(),
+        base: Some(base.to_string()),
+        commits,
+        context,
+    })
+}
+
+/// Generate a PR title from a context built earlier, e.g. by `dump_branch_context`
+pub async fn generate_context_title(context: &CleanContext, config: &GeneratorConfig) -> Result<String> {
+    let title_generator = TitleGenerator::new(config.clone())?;
+    Ok(cached_title(&title_generator, context, config).await?.title)
+}
+
+/// Generate a PR title from commits that were already read, e.g. from an API
+pub async fn generate_commits_title(
+    branch: &str,
+    commits: &[CommitInfo],
+    config: &GeneratorConfig,
+) -> Result<String> {
+    generate_title(branch, config.base_branch.as_deref(), commits, RepoDetails::default(), config, None).await
+}
+
+/// Generate the title, candidates and diagnostics from commits that were already read
+pub async fn generate_commits_output(
+    branch: &str,
+    commits: &[CommitInfo],
+    config: &GeneratorConfig,
+) -> Result<OutputPayload> {
+    generate_output(branch, config.base_branch.as_deref(), commits, RepoDetails::default(), config, 0).await
+}
+
+/// Trace the decisions behind the title for `branch` compared against `base`
+pub fn explain_branch_title(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+) -> Result<Explanation> {
+    let (commits, details) = read_branch(repo, base, branch, config)?;
+    let config = &with_learned_vocabulary(repo, base, config)?;
+    explain_title(branch, Some(base), &commits, details, config)
+}
+
+/// Trace the decisions behind the title for commits that were already read
+pub fn explain_commits_title(
+    branch: &str,
+    commits: &[CommitInfo],
+    config: &GeneratorConfig,
+) -> Result<Explanation> {
+    explain_title(branch, config.base_branch.as_deref(), commits, RepoDetails::default(), config)
+}
+
+/// Find a recent merge or squash commit on `base` for the ticket of `branch`
+///
+/// The ticket comes from the branch name, then its commits, as for the title.
+/// A match suggests the work already landed and the branch would reuse its
+/// title; `None` when the branch has no ticket or nothing on the base matches.
+pub fn find_merged_ticket(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+) -> Result<Option<MergedTicket>> {
+    let context_processor = context_processor(config)?;
+    let (commits, _) = read_branch(repo, base, branch, config)?;
+    let Some(ticket) = context_processor
+        .extract_branch_context(branch)
+        .ticket
+        .or_else(|| context_processor.extract_commit_ticket(&commits))
+    else {
+        return Ok(None);
+    };
+
+    let branch_hashes: Vec<&str> = commits.iter().map(|commit| commit.hash.as_str()).collect();
+    let merged = repo
+        .recent_commits(base, MERGED_TICKET_SAMPLE)?
+        .into_iter()
+        .filter(|commit| !branch_hashes.contains(&commit.hash.as_str()))
+        .find(|commit| context_processor.extract_commit_ticket(std::slice::from_ref(commit)).as_ref() == Some(&ticket));
+
+    Ok(merged.map(|commit| MergedTicket {
+        subject: commit.subject(),
+        hash: commit.hash,
+        ticket,
+    }))
+}
+
+/// `config` with the vocabulary learned from the history of `base` when `learn_vocab` is set
+fn with_learned_vocabulary<'a>(repo: &GitRepo, base: &str, config: &'a GeneratorConfig) -> Result<Cow<'a, GeneratorConfig>> {
+    if !config.learn_vocab {
+        return Ok(Cow::Borrowed(config));
+    }
+    let mut config = config.clone();
+    for term in learn_vocabulary(repo, base)? {
+        if !config.domain_vocabulary.contains(&term) {
+            config.domain_vocabulary.push(term);
+        }
+    }
+    Ok(Cow::Owned(config))
+}
+
+/// What only a local repository knows about a branch; empty for commits read elsewhere
+#[derive(Debug, Default)]
+struct RepoDetails {
+    files: Vec<ChangedFile>,
+    /// The branch has no commits of its own and `files` are its uncommitted changes
+    uncommitted: bool,
+    nearest_tag: Option<NearestTag>,
+    divergence: Option<Divergence>,
+    /// Files in the commit the branch forked from, counted up to just past
+    /// `SCAFFOLD_MAX_BASE_FILES`
+    base_files: Option<usize>,
+}
+
+/// Read the commits of a branch and, if needed, its nearest tag and changed files
+fn read_branch(
+    repo: &GitRepo,
+    base: &str,
+    branch: &str,
+    config: &GeneratorConfig,
+) -> Result<(Vec<CommitInfo>, RepoDetails)> {
+    // Validate that the branch exists; hooks may pass a pushed SHA instead
+    if !repo.commit_exists(branch) {
+        return Err(Error::BranchNotFound {
+            branch: branch.to_string(),
+        });
+    }
+
+    // Get commits between base and branch
+    let filter = commit_filter(config)?;
+    let (commits, files, uncommitted) = match repo.get_filtered_commits(base, branch, &filter) {
+        Ok(commits) if !needs_changed_files(config) => (commits, Vec::new(), false),
+        Ok(commits) => (commits, repo.changed_files(config.since.as_deref().unwrap_or(base), branch)?, false),
+        Err(Error::NoCommits { base, branch }) => {
+            let files = uncommitted_files(repo, &base, &branch)?;
+            if files.is_empty() {
+                return Err(Error::NoCommits { base, branch });
+            }
+            debug!(count = files.len(), "no commits of its own, titling uncommitted changes");
+            (Vec::new(), files, true)
+        }
+        Err(e) => return Err(e),
+    };
+
+    let base_files = if config.detect_scaffolding {
+        Some(repo.base_file_count(base, branch, SCAFFOLD_MAX_BASE_FILES)?)
+    } else {
+        None
+    };
+    let nearest_tag = if config.include_nearest_tag { repo.describe(branch)? } else { None };
+    let (ahead, behind) = repo.ahead_behind(base, branch)?;
+    let divergence = Divergence {
+        merge_base: repo.merge_base(base, branch)?,
+        ahead,
+        behind,
+    };
+    debug!(?divergence, "compared branch with base");
+
+    Ok((
+        commits,
+        RepoDetails {
+            files,
+            uncommitted,
+            nearest_tag,
+            divergence: Some(divergence),
+            base_files,
+        },
+    ))
+}
+
+/// Whether anything in `config` looks at the files a branch changes, which
+/// takes a full diff
+fn needs_changed_files(config: &GeneratorConfig) -> bool {
+    config.use_filenames || config.emphasize_dominant.is_some() || config.detect_file_types || config.detect_scaffolding
+}
+
+/// Every regex the user passed in `config`, compiled together
+///
+/// Both `commit_filter` and `context_processor` start here, so whichever
+/// runs first reports all invalid patterns at once, not just its own.
+struct UserPatterns {
+    author: Option<Regex>,
+    exclude_author: Option<Regex>,
+    release: Option<Regex>,
+    branch_prefixes: Vec<Regex>,
+}
+
+impl UserPatterns {
+    fn compile(config: &GeneratorConfig) -> Result<Self> {
+        let mut patterns = PatternCompiler::new(config.strict_patterns);
+        let author = match &config.author {
+            Some(pattern) => patterns.compile("--author", pattern)?,
+            None => None,
+        };
+        let exclude_author = match &config.exclude_author {
+            Some(pattern) => patterns.compile("--exclude-author", pattern)?,
+            None => None,
+        };
+        let release = match &config.release_pattern {
+            Some(pattern) => compile_release_pattern(&mut patterns, pattern)?,
+            None => None,
+        };
+        let branch_prefixes = compile_branch_prefixes(&mut patterns, &config.branch_prefixes)?;
+        patterns.finish()?;
+        Ok(Self {
+            author,
+            exclude_author,
+            release,
+            branch_prefixes,
+        })
+    }
+}
+
+/// Which of a branch's commits to read, from the commit options in `config`
+pub(crate) fn commit_filter(config: &GeneratorConfig) -> Result<CommitFilter> {
+    let mut filter = CommitFilter::default()
+        .with_max_commits(config.max_commits)
+        .with_order(config.commit_order)
+        .with_include_merges(config.include_merges);
+    let patterns = UserPatterns::compile(config)?;
+    filter.author = patterns.author;
+    filter.exclude_author = patterns.exclude_author;
+    if let Some(since) = &config.since {
+        filter = filter.with_since(since);
+    }
+    if let Some(merge_style) = config.merge_style {
+        filter = filter.with_merge_style(merge_style);
+    }
+    Ok(filter)
+}
+
+/// Uncommitted changes on `branch` when it is checked out and at or behind `base`
+///
+/// Covers work that was never committed; a branch whose commits were all
+/// filtered out has none, so its filters still apply.
+fn uncommitted_files(repo: &GitRepo, base: &str, branch: &str) -> Result<Vec<ChangedFile>> {
+    if repo.current_branch().ok().as_deref() != Some(branch) || repo.ahead_behind(base, branch)?.0 > 0 {
+        return Ok(Vec::new());
+    }
+    repo.uncommitted_files()
+}
+
+async fn generate_title(
+    branch: &str,
+    base: Option<&str>,
+    commits: &[CommitInfo],
+    details: RepoDetails,
+    config: &GeneratorConfig,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<String> {
+    debug!(count = commits.len(), "found commits to analyze");
+    for commit in commits {
+        debug!(hash = %commit.hash, message = commit.clean_message(), "commit");
+    }
+
+    let context_processor = context_processor(config)?;
+    let clean_context = info_span!("context", branch)
+        .in_scope(|| {
+            build_context(&context_processor, branch, base, commits, details, config).inspect_err(|error| debug!(%error))
+        })?;
+    report(
+        progress,
+        ProgressEvent::CommitsProcessed {
+            analyzed: commits.len(),
+            kept: clean_context.commits.len(),
+        },
+    );
+
+    report(progress, ProgressEvent::GenerationStarted);
+    let title_generator = TitleGenerator::new(config.clone())?;
+    let generated = cached_title(&title_generator, &clean_context, config).await?;
+    report(progress, ProgressEvent::GenerationFinished);
+
+    Ok(generated.title)
+}
+
+fn report(progress: Option<ProgressCallback<'_>>, event: ProgressEvent) {
+    if let Some(progress) = progress {
+        progress(event);
+    }
+}
+
+async fn generate_output(
+    branch: &str,
+    base: Option<&str>,
+    commits: &[CommitInfo],
+    details: RepoDetails,
+    config: &GeneratorConfig,
+    git_ms: u64,
+) -> Result<OutputPayload> {
+    let started = Instant::now();
+    let divergence = details.divergence.clone();
+    let context_processor = context_processor(config)?;
+    let clean_context = info_span!("context", branch)
+        .in_scope(|| {
+            build_context(&context_processor, branch, base, commits, details, config).inspect_err(|error| debug!(%error))
+        })?;
+    let dropped_commits = context_processor.dropped_commits(commits, &clean_context);
+    let context_ms = millis(started.elapsed());
+
+    let started = Instant::now();
+    let title_generator = TitleGenerator::new(config.clone())?;
+    let generated = cached_title(&title_generator, &clean_context, config).await?;
+    let candidates = title_generator.generate_candidates(&clean_context)?;
+    let titles = if config.audience_titles {
+        let technical = title_generator.generate_for(&clean_context, Audience::Technical).await?;
+        Some(AudienceTitles {
+            technical: technical.title,
+            user_facing: generated.title.clone(),
+        })
+    } else {
+        None
+    };
+    let generation_ms = millis(started.elapsed());
+
+    Ok(OutputPayload {
+        schema_version: SCHEMA_VERSION,
+        title: generated.title,
+        raw_title: generated.raw_title,
+        backend: generated.backend,
+        truncated: generated.truncated,
+        removed_suffix: generated.removed_suffix,
+        candidates,
+        prompt_size: title_generator.prompt_size(&clean_context),
+        context: clean_context,
+        timings: Timings {
+            git_ms,
+            context_ms,
+            generation_ms,
+        },
+        commits: analyze_commits(commits, &dropped_commits),
+        dropped_commits,
+        divergence,
+        titles,
+    })
+}
+
+fn explain_title(
+    branch: &str,
+    base: Option<&str>,
+    commits: &[CommitInfo],
+    details: RepoDetails,
+    config: &GeneratorConfig,
+) -> Result<Explanation> {
+    let context_processor = context_processor(config)?;
+    let clean_context = build_context(&context_processor, branch, base, commits, details, config)?;
+    TitleGenerator::new(config.clone())?.explain(&clean_context)
+}
+
+/// Generate a title, reusing a cached one when `config.cache_dir` is set
+async fn cached_title(
+    title_generator: &TitleGenerator,
+    clean_context: &CleanContext,
+    config: &GeneratorConfig,
+) -> Result<GeneratedTitle> {
+    let cache = config.cache_dir.as_ref().map(TitleCache::new);
+    let cache_key = TitleCache::key(clean_context, config);
+    // Entries hold the title before `post_process_command`, which runs on hits too
+    let generated = match cache.as_ref().and_then(|cache| cache.get(&cache_key)) {
+        Some(generated) => {
+            debug!(key = %cache_key, "using cached title");
+            generated
+        }
+        None => {
+            let generated = title_generator.generate_before_hook(clean_context, Audience::UserFacing).await?;
+            // A cache that cannot be written only costs the next run a regeneration
+            if let Some(Err(error)) = cache.as_ref().map(|cache| cache.put(&cache_key, clean_context, &generated)) {
+                debug!(key = %cache_key, %error, "could not cache title");
+            }
+            generated
+        }
+    };
+
+    title_generator.apply_post_process_command(generated)
+}
+
+pub(crate) fn context_processor(config: &GeneratorConfig) -> Result<ContextProcessor> {
+    let patterns = UserPatterns::compile(config)?;
+    let processor = ContextProcessor::new()?
+        .with_branch_context(config.include_branch_context)
+        .with_summary_size(config.summary_size)
+        .with_max_subject_chars(config.max_subject_chars)
+        .with_keep_refs(config.keep_refs)
+        .with_commit_bodies(config.commit_bodies)
+        .with_cluster_threshold(config.cluster_threshold)?
+        .with_branch_prefix_regexes(patterns.branch_prefixes);
+    let processor = if config.unicode_tickets {
+        processor.with_unicode_ticket_formats(&config.ticket_formats)?
+    } else {
+        processor.with_ticket_formats(&config.ticket_formats)?
+    };
+    let processor = processor.with_ticket_prefixes(config.ticket_prefixes.clone())?;
+    Ok(match patterns.release {
+        Some(regex) => processor.with_release_regex(regex),
+        None => processor,
+    })
+}
+
+/// Turn the branch name and commits into the cleaned model context, within
+/// `max_prompt_chars` when set
+///
+/// A ticket in the `base` name, e.g. an epic's, belongs to the base rather
+/// than the PR, so it is neither prefixed nor left in commit subjects.
+fn build_context(
+    context_processor: &ContextProcessor,
+    branch: &str,
+    base: Option<&str>,
+    commits: &[CommitInfo],
+    details: RepoDetails,
+    config: &GeneratorConfig,
+) -> Result<CleanContext> {
+    // Extract branch context
+    let branch_context = context_processor.extract_branch_context(branch);
+
+    debug!(?branch_context, "extracted branch context");
+
+    // Clean commit messages
+    let clean_commits = context_processor.clean_commit_messages(commits);
+
+    debug!(?clean_commits, "cleaned commit messages");
+
+    // Create clean context for ML model
+    let clean_context = context_processor.create_clean_context(&branch_context, &clean_commits);
+    let clean_context = if config.detect_file_types {
+        context_processor.apply_file_change_type(clean_context, &details.files)
+    } else {
+        clean_context
+    };
+    let mut clean_context = context_processor.apply_commit_trailers(clean_context, commits);
+    if clean_context.ticket.is_none() {
+        clean_context.ticket = context_processor.extract_commit_ticket(commits);
+    }
+    let base_ticket = base.and_then(|base| context_processor.extract_branch_context(base).ticket);
+    if let Some(base_ticket) = &base_ticket {
+        debug!(ticket = %base_ticket, "ignoring base branch ticket");
+        clean_context = context_processor.remove_base_ticket(clean_context, base_ticket);
+    }
+    if config.max_tickets.is_some() {
+        let mut extra_tickets: Vec<String> = Vec::new();
+        for ticket in clean_context.tickets.iter().cloned().chain(context_processor.commit_tickets(commits)) {
+            if Some(&ticket) != clean_context.ticket.as_ref()
+                && Some(&ticket) != base_ticket.as_ref()
+                && !extra_tickets.contains(&ticket)
+            {
+                extra_tickets.push(ticket);
+            }
+        }
+        clean_context.extra_tickets = extra_tickets;
+    } else if config.detect_batches && branch_context.ticket.is_none() && is_merge_queue_branch(branch) {
+        let mut tickets = context_processor.commit_tickets(commits);
+        tickets.retain(|ticket| Some(ticket) != base_ticket.as_ref());
+        if tickets.len() >= BATCH_MIN_TICKETS {
+            debug!(?tickets, "titling merge queue batch");
+            // The title lists every ticket, so none is prefixed
+            clean_context.ticket = None;
+            clean_context.tickets.clear();
+            clean_context.batch_tickets = tickets;
+        }
+    }
+    if config.keep_refs {
+        // A GitHub issue that is already the ticket is not repeated
+        clean_context.issue_ref = context_processor
+            .issue_ref(branch, commits)
+            .filter(|issue_ref| clean_context.ticket.as_ref() != Some(issue_ref));
+    }
+    if config.require_ticket && clean_context.ticket.is_none() && clean_context.batch_tickets.is_empty() {
+        let formats: Vec<&str> = config.ticket_formats.iter().map(|format| format.as_str()).collect();
+        return Err(Error::MissingTicket {
+            branch: branch.to_string(),
+            formats: formats.join(" or "),
+        });
+    }
+    if config.scope_from_ticket && clean_context.scope.is_none() {
+        clean_context.scope = clean_context.ticket.as_deref().and_then(project_key).map(str::to_lowercase);
+    }
+    clean_context.change_type = context_processor.suggest_change_type(&clean_context);
+    clean_context.action = config.action.clone();
+    clean_context.release_version = context_processor.release_version(commits);
+    clean_context.breaking_changes = context_processor.breaking_changes(commits);
+    clean_context.nearest_tag = details.nearest_tag;
+    clean_context.scaffolding = config.detect_scaffolding && context_processor.is_scaffolding(&clean_context, &details.files, details.base_files);
+    // Files only reach the prompt when titles may name them, or are all there is
+    if config.use_filenames || config.emphasize_dominant.is_some() || details.uncommitted {
+        clean_context.files = details.files;
+    }
+
+    let model = Model::parse(&config.model_name).unwrap_or_default();
+    if let Some(limit) = config.max_prompt_chars {
+        let size = clean_context.prompt_size_for(&model);
+        let fits = match config.prompt_policy {
+            PromptPolicy::Trim => clean_context.trim_to_prompt_size(limit, &model),
+            PromptPolicy::Error => size <= limit,
+        };
+        if !fits {
+            return Err(Error::PromptTooLarge { size, limit });
+        }
+    }
+
+    debug!(?clean_context, prompt_size = clean_context.prompt_size_for(&model), "built clean context");
+
+    Ok(clean_context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::ChangeType;
+    use crate::test_support::TestRepo;
+    use crate::tickets::{TicketFormat, TicketPrefixes};
+
+    #[tokio::test]
+    async fn test_generate_twice_with_same_repo() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+
+        let repo = test_repo.open();
+        let config = GeneratorConfig::default();
+        let branch = "feature/CRU-310-fix-bottle-stuck";
+
+        let first = generate_branch_title(&repo, "main", branch, &config).await.unwrap();
+        let second = generate_branch_title(&repo, "main", branch, &config).await.unwrap();
+
+        assert!(first.starts_with("CRU-310"));
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_branch_without_new_commits_has_no_commits_kind() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+
+        let repo = test_repo.open();
+        let error = generate_branch_title(&repo, "main", "feature/CRU-310-fix-bottle-stuck", &GeneratorConfig::default())
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.kind(), crate::ErrorKind::NoCommits);
+        assert_eq!(error.exit_code(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_branch_behind_base_with_staged_changes_is_titled_from_the_diff() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "install-steps"]);
+        test_repo.git(&["checkout", "-q", "main"]);
+        test_repo.commit_file("CHANGELOG.md", "# Changelog\n", "docs: start a changelog");
+        test_repo.git(&["checkout", "-q", "install-steps"]);
+        std::fs::write(test_repo.path().join("README.md"), "# Test Repo\n\nInstall with cargo.\n").unwrap();
+        test_repo.git(&["add", "README.md"]);
+
+        let repo = test_repo.open();
+        let title = generate_branch_title(&repo, "main", "install-steps", &GeneratorConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(title, "Document README changes");
+    }
+
+    #[tokio::test]
+    async fn test_lowercase_branch_ticket_is_uppercased() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/cru-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+
+        let repo = test_repo.open();
+        let title = generate_branch_title(
+            &repo,
+            "main",
+            "feature/cru-310-fix-bottle-stuck",
+            &GeneratorConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(title.starts_with("CRU-310: "));
+        assert!(!title.contains("cru-310"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_from_commits_without_repo() {
+        let commits = vec![CommitInfo {
+            hash: "abc123".to_string(),
+            message: "fix: bottle stuck with remediation system".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        }];
+
+        let title = generate_commits_title("feature/CRU-310-fix-bottle-stuck", &commits, &GeneratorConfig::default())
+            .await
+            .unwrap();
+
+        assert!(title.starts_with("CRU-310: "));
+    }
+
+    #[tokio::test]
+    async fn test_bare_repo_pushed_shas() {
+        let test_repo = TestRepo::new();
+        let old_sha = test_repo.git(&["rev-parse", "HEAD"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        let new_sha = test_repo.git(&["rev-parse", "HEAD"]);
+
+        let bare_dir = tempfile::TempDir::new().unwrap();
+        test_repo.git(&["clone", "-q", "--bare", ".", &bare_dir.path().to_string_lossy()]);
+        let repo = GitRepo::open_bare(bare_dir.path()).unwrap();
+
+        let title = generate_branch_title(&repo, &old_sha, &new_sha, &GeneratorConfig::default())
+            .await
+            .unwrap();
+
+        assert!(title.to_lowercase().contains("bottle stuck"), "{}", title);
+    }
+
+    #[tokio::test]
+    async fn test_output_lists_dropped_commits() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        test_repo.commit_file("wip.txt", "wip", "wip");
+
+        let repo = test_repo.open();
+        let config = GeneratorConfig::default();
+        let branch = "feature/CRU-310-fix-bottle-stuck";
+
+        let output = generate_branch_output(&repo, "main", branch, &config).await.unwrap();
+
+        assert_eq!(output.schema_version, SCHEMA_VERSION);
+        assert_eq!(output.title, generate_branch_title(&repo, "main", branch, &config).await.unwrap());
+        assert!(!output.candidates.is_empty());
+        assert!(output.candidates.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert_eq!(output.dropped_commits.len(), 1);
+        assert_eq!(output.dropped_commits[0].subject, "wip");
+        assert_eq!(output.dropped_commits[0].reason, crate::context::DropReason::TooShort);
+        assert_eq!(output.divergence.map(|divergence| (divergence.ahead, divergence.behind)), Some((2, 0)));
+        assert_eq!(output.titles, None);
+        assert_eq!(output.prompt_size, output.context.prompt_size());
+
+        // The size follows the configured model's chat template
+        let gemma = GeneratorConfig::default().with_model("gemma-2b");
+        let output = generate_branch_output(&repo, "main", branch, &gemma).await.unwrap();
+        assert_eq!(output.prompt_size, output.context.prompt_size_for(&Model::Gemma2b));
+        let explanation = explain_branch_title(&repo, "main", branch, &gemma).unwrap();
+        assert_eq!(explanation.prompt_size, output.prompt_size);
+    }
+
+    #[tokio::test]
+    async fn test_progress_events_follow_generation() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        test_repo.commit_file("wip.txt", "wip", "wip");
+
+        let events = std::sync::Mutex::new(Vec::new());
+        let collect = |event| events.lock().unwrap().push(event);
+        let repo = test_repo.open();
+        let branch = "feature/CRU-310-fix-bottle-stuck";
+        generate_branch_title_with_progress(&repo, "main", branch, &GeneratorConfig::default(), Some(&collect))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events.into_inner().unwrap(),
+            vec![
+                ProgressEvent::GitReadStarted,
+                ProgressEvent::GitReadFinished { commits: 2 },
+                ProgressEvent::CommitsProcessed { analyzed: 2, kept: 1 },
+                ProgressEvent::GenerationStarted,
+                ProgressEvent::GenerationFinished,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_ignored_vendor_dir_does_not_name_the_title() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/session-timeout"]);
+        test_repo.commit_file(".gitattributes", "vendor/** export-ignore\n", "chore: ignore vendored code");
+        test_repo.commit_file("vendor/sdk.js", &"var sdk;\n".repeat(200), "chore: vendor the sdk");
+        test_repo.commit_file("session.rs", &"fn expire() {}\n".repeat(20), "feat: expire idle sessions");
+
+        let repo = test_repo.open();
+        let files = repo.changed_files("main", "feature/session-timeout").unwrap();
+        assert!(files.iter().all(|file| !file.path.starts_with("vendor/")));
+
+        let config = GeneratorConfig::default().with_filenames(true);
+        let title = generate_branch_title(&repo, "main", "feature/session-timeout", &config).await.unwrap();
+        assert!(title.contains("session.rs"), "{}", title);
+    }
+
+    #[tokio::test]
+    async fn test_output_has_technical_and_user_facing_titles() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("remediation.rs", "fn retry() {}", "fix: bottle stuck with remediation system");
+
+        let repo = test_repo.open();
+        let config = GeneratorConfig::default().with_audience_titles(true);
+        let branch = "feature/CRU-310-fix-bottle-stuck";
+
+        let output = generate_branch_output(&repo, "main", branch, &config).await.unwrap();
+        let titles = output.titles.unwrap();
+
+        assert_eq!(titles.user_facing, output.title);
+        assert!(titles.technical.starts_with("CRU-310: Handle "));
+        assert_ne!(titles.technical, titles.user_facing);
+    }
+
+    #[tokio::test]
+    async fn test_output_classifies_each_commit() {
+        let commit = |hash: &str, message: &str| CommitInfo {
+            hash: hash.to_string(),
+            message: message.to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        };
+        let commits = vec![
+            commit("abc123", "fix: bottle stuck with remediation system"),
+            commit("def456", "Merge branch 'main' into feature/CRU-310-fix-bottle-stuck"),
+        ];
+
+        let output = generate_commits_output("feature/CRU-310-fix-bottle-stuck", &commits, &GeneratorConfig::default())
+            .await
+            .unwrap();
+
+        let kept = &output.commits[0];
+        assert_eq!((kept.change_type.clone(), kept.kept, kept.drop_reason), (Some(ChangeType::Fix), true, None));
+        let merge = &output.commits[1];
+        assert_eq!(merge.hash, "def456");
+        assert!(!merge.kept);
+        assert_eq!(merge.drop_reason, Some(crate::context::DropReason::MergeOrRevert));
+
+        let json = serde_json::to_string(&output).unwrap();
+        assert!(json.contains(r#""type":"fix","kept":true,"drop_reason":null"#));
+    }
+
+    #[tokio::test]
+    async fn test_scope_from_ticket_project_key() {
+        let commits = vec![CommitInfo {
+            hash: "abc123".to_string(),
+            message: "fix: bottle stuck with remediation system".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        }];
+        let branch = "feature/CRU-310-fix-bottle-stuck";
+
+        let output = generate_commits_output(branch, &commits, &GeneratorConfig::default().with_scope_from_ticket(true))
+            .await
+            .unwrap();
+        assert_eq!(output.context.scope.as_deref(), Some("cru"));
+        assert!(output.context.to_prompt().contains("Scope: cru"));
+
+        let output = generate_commits_output(branch, &commits, &GeneratorConfig::default()).await.unwrap();
+        assert_eq!(output.context.scope, None);
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_batch_is_titled_as_batch() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "gh-readonly-queue/main/pr-44"]);
+        test_repo.commit_file("fix.txt", "fix", "CRU-310: fix bottle stuck with remediation system");
+        test_repo.commit_file("search.txt", "search", "CRU-311: add search index for bottles");
+        let repo = test_repo.open();
+        let branch = "gh-readonly-queue/main/pr-44";
+
+        // Two tickets are still one change
+        let title = generate_branch_title(&repo, "main", branch, &GeneratorConfig::default()).await.unwrap();
+        assert!(!title.starts_with("Merge"), "{}", title);
+
+        test_repo.commit_file("export.txt", "export", "CRU-312: export bottle reports as CSV");
+        test_repo.commit_file("export.txt", "export v2", "CRU-310: handle empty bottles");
+        let output = generate_branch_output(&repo, "main", branch, &GeneratorConfig::default()).await.unwrap();
+        assert_eq!(output.title, "Merge 3 PRs: CRU-310, CRU-311, and CRU-312");
+        assert_eq!(output.context.batch_tickets, vec!["CRU-310", "CRU-311", "CRU-312"]);
+        assert_eq!(output.context.ticket, None);
+
+        let opt_out = GeneratorConfig::default().with_detect_batches(false);
+        let title = generate_branch_title(&repo, "main", branch, &opt_out).await.unwrap();
+        assert!(!title.starts_with("Merge"), "{}", title);
+    }
+
+    #[tokio::test]
+    async fn test_max_tickets_lists_tickets_and_counts_the_rest() {
+        // Newest first, as read from git
+        let commits: Vec<CommitInfo> = [
+            "CRU-313: export bottle reports as CSV",
+            "CRU-312: retry stuck bottles",
+            "CRU-311: add search index for bottles",
+            "CRU-310: fix bottle stuck with remediation system",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, message)| CommitInfo {
+            hash: format!("c{}", i),
+            message: message.to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        })
+        .collect();
+        let branch = "feature/CRU-310-fix-bottle-stuck";
+
+        let config = GeneratorConfig::default().with_dedupe_ticket(true).with_max_tickets(2);
+        let output = generate_commits_output(branch, &commits, &config).await.unwrap();
+        assert_eq!(output.context.extra_tickets, vec!["CRU-311", "CRU-312", "CRU-313"]);
+        assert!(output.title.starts_with("CRU-310, CRU-311 +2 more: "), "{}", output.title);
+        assert!(output.title.chars().count() <= 72, "{}", output.title);
+
+        let all = generate_commits_title(branch, &commits, &config.clone().with_max_tickets(4)).await.unwrap();
+        assert!(all.starts_with("CRU-310, CRU-311, CRU-312, CRU-313: "), "{}", all);
+
+        // The listed tickets leave the text even without `dedupe_ticket`
+        let listed = GeneratorConfig::default().with_max_tickets(2);
+        let title = generate_commits_title(branch, &commits, &listed).await.unwrap();
+        assert!(title.starts_with("CRU-310, CRU-311 +2 more: "), "{}", title);
+        assert_eq!(title.matches("CRU-310").count(), 1, "{}", title);
+
+        // Without the option a feature branch is still one change, while a merge queue's is a batch
+        let single = generate_commits_title(branch, &commits, &GeneratorConfig::default()).await.unwrap();
+        assert!(!single.starts_with("Merge") && single.contains("CRU-310"), "{}", single);
+        let batch = generate_commits_title("gh-readonly-queue/main/pr-44", &commits, &GeneratorConfig::default())
+            .await
+            .unwrap();
+        assert!(batch.starts_with("Merge 4 PRs"), "{}", batch);
+        assert!(TitleGenerator::new(GeneratorConfig::default().with_max_tickets(0)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_strong_single_commit_subject_is_kept() {
+        let commit = |message: &str| CommitInfo {
+            hash: "abc123".to_string(),
+            message: message.to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        };
+        let branch = "feature/CRU-310-session-tokens";
+        let strong = vec![commit("Handle expired refresh tokens during login")];
+        let config = GeneratorConfig::default().with_prefer_commit_subject(true);
+
+        let output = generate_commits_output(branch, &strong, &config).await.unwrap();
+        assert_eq!(output.title, "CRU-310: Handle expired refresh tokens during login");
+        assert_eq!(output.backend, "commit");
+        let explanation = explain_commits_title(branch, &strong, &config).unwrap();
+        assert_eq!((explanation.backend.as_str(), explanation.template), ("commit", None));
+
+        let synthesized = generate_commits_output(branch, &strong, &GeneratorConfig::default()).await.unwrap();
+        assert_eq!(synthesized.backend, "pattern");
+        assert_ne!(synthesized.title, output.title);
+
+        // A vague subject, or several commits, are still synthesized
+        let vague = generate_commits_output(branch, &[commit("more stuff for tokens")], &config).await.unwrap();
+        assert_eq!(vague.backend, "pattern");
+        let several = [commit("Handle expired refresh tokens during login"), commit("Add token refresh endpoint")];
+        assert_eq!(generate_commits_output(branch, &several, &config).await.unwrap().backend, "pattern");
+
+        // A folded body is not part of the kept subject
+        let detailed = [commit("Handle expired refresh tokens during login\n\nThe old token was reused.")];
+        let bodies = config.clone().with_commit_bodies(true);
+        let output = generate_commits_output(branch, &detailed, &bodies).await.unwrap();
+        assert_eq!(output.title, "CRU-310: Handle expired refresh tokens during login");
+    }
+
+    #[tokio::test]
+    async fn test_keep_refs_ends_title_with_issue_reference() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "fix/login-redirect"]);
+        test_repo.commit_file("login.rs", "login", "fix login redirect loop (#1234)");
+        test_repo.commit_file("session.rs", "session", "handle expired session cookies, refs #1234");
+        let repo = test_repo.open();
+        let branch = "fix/login-redirect";
+
+        let config = GeneratorConfig::default().with_keep_refs(true);
+        let output = generate_branch_output(&repo, "main", branch, &config).await.unwrap();
+        assert!(output.title.ends_with(" (#1234)"), "{}", output.title);
+        assert!(output.title.chars().count() <= config.max_length, "{}", output.title);
+        assert_eq!(output.context.issue_ref.as_deref(), Some("#1234"));
+        assert!(output.context.commits.iter().all(|commit| !commit.contains('#')), "{:?}", output.context.commits);
+
+        let title = generate_branch_title(&repo, "main", branch, &GeneratorConfig::default()).await.unwrap();
+        assert!(!title.contains("(#1234)"), "{}", title);
+    }
+
+    #[test]
+    fn test_clean_branch_commits() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        test_repo.commit_file("wip.txt", "wip", "wip");
+        test_repo.commit_file("retry.txt", "retry", "feat(retry): retry stuck bottles after a timeout");
+        test_repo.commit_file("more.txt", "more", "update some files");
+        test_repo.commit_file("alert.txt", "alert", "feat(alerts)!: page on-call when bottles stay stuck");
+        test_repo.commit_file("log.txt", "log", "chore(logging): log every remediation attempt");
+
+        // More subjects than the summary keeps are all returned
+        let config = GeneratorConfig::default().with_summary_size(2);
+        let subjects =
+            clean_branch_commits(&test_repo.open(), "main", "feature/CRU-310-fix-bottle-stuck", &config).unwrap();
+        // Generic and too-short commits are dropped, conventional prefixes cut
+        assert_eq!(
+            subjects,
+            vec![
+                "log every remediation attempt",
+                "page on-call when bottles stay stuck",
+                "retry stuck bottles after a timeout",
+                "bottle stuck with remediation system",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unicode_ticket_and_scope() {
+        let commits = vec![CommitInfo {
+            hash: "abc123".to_string(),
+            message: "fix: upload stalls on large files".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        }];
+        let branch = "feature/Ä-123-fix-upload";
+        let config = GeneratorConfig::default().with_scope_from_ticket(true);
+
+        let output = generate_commits_output(branch, &commits, &config.clone().with_unicode_tickets(true))
+            .await
+            .unwrap();
+        assert_eq!(output.context.ticket.as_deref(), Some("Ä-123"));
+        assert_eq!(output.context.scope.as_deref(), Some("ä"));
+        assert!(output.title.starts_with("Ä-123: "), "{}", output.title);
+
+        let output = generate_commits_output(branch, &commits, &config).await.unwrap();
+        assert_eq!(output.context.ticket, None);
+    }
+
+    #[tokio::test]
+    async fn test_configured_ticket_prefixes() {
+        let commits = vec![CommitInfo {
+            hash: "abc123".to_string(),
+            message: "fix: cache misses after deploys".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        }];
+        let branch = "feature/PLAT-42-fix-cache";
+
+        let output = generate_commits_output(branch, &commits, &GeneratorConfig::default()).await.unwrap();
+        assert_eq!(output.context.ticket, None);
+
+        let platform = TicketPrefixes::Keys(vec!["PLAT".to_string(), "INFRA".to_string()]);
+        for prefixes in [platform, TicketPrefixes::AnyUppercase] {
+            let config = GeneratorConfig::default().with_ticket_prefixes(prefixes);
+            let output = generate_commits_output(branch, &commits, &config).await.unwrap();
+            assert!(output.title.starts_with("PLAT-42: "), "{}", output.title);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_branch() {
+        let test_repo = TestRepo::new();
+        let repo = test_repo.open();
+
+        let result = generate_branch_title(&repo, "main", "missing", &GeneratorConfig::default()).await;
+        assert!(matches!(result, Err(Error::BranchNotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_docs_only_branch_is_docs_typed() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/install-guide"]);
+        test_repo.commit_file("README.md", "# Test Repo\n\nInstall with cargo.", "Add install steps to readme");
+        test_repo.commit_file("docs/setup.txt", "setup", "Explain setup for contributors");
+        let repo = test_repo.open();
+
+        let output = generate_branch_output(&repo, "main", "feature/install-guide", &GeneratorConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(output.context.change_type, Some(ChangeType::Docs));
+        assert!(output.title.starts_with("Document "), "{}", output.title);
+        assert!(output.context.files.is_empty());
+
+        let by_name = GeneratorConfig::default()
+            .with_detect_file_types(false)
+            .with_detect_scaffolding(false);
+        assert!(!needs_changed_files(&by_name));
+        let output = generate_branch_output(&repo, "main", "feature/install-guide", &by_name).await.unwrap();
+        assert_eq!(output.context.change_type, Some(ChangeType::Feature));
+    }
+
+    #[tokio::test]
+    async fn test_dumped_context_replays_to_the_same_title() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        test_repo.commit_file("test.txt", "test", "test: cover stuck bottles");
+        let repo = test_repo.open();
+        let config = GeneratorConfig::default().with_filenames(true);
+        let branch = "feature/CRU-310-fix-bottle-stuck";
+
+        let dump = dump_branch_context(&repo, "main", branch, &config).unwrap();
+        assert_eq!(dump.base.as_deref(), Some("main"));
+        assert_eq!(dump.commits.len(), 2);
+        assert!(!dump.context.files.is_empty());
+
+        let json = serde_json::to_string_pretty(&dump).unwrap();
+        let replayed: ContextDump = serde_json::from_str(&json).unwrap();
+        assert_eq!(replayed, dump);
+        assert_eq!(
+            generate_context_title(&replayed.context, &config).await.unwrap(),
+            generate_branch_title(&repo, "main", branch, &config).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_test_only_and_ci_only_branches() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/client-retries"]);
+        test_repo.commit_file("client_test.go", "package client", "cover retries in fetch client");
+        test_repo.git(&["checkout", "-q", "main"]);
+        test_repo.git(&["checkout", "-q", "-b", "feature/lint-job"]);
+        test_repo.commit_file(".github/workflows/lint.yml", "on: pull_request", "run clippy on pull requests");
+        let repo = test_repo.open();
+        let config = GeneratorConfig::default();
+
+        let tests = generate_branch_output(&repo, "main", "feature/client-retries", &config).await.unwrap();
+        assert_eq!(tests.context.change_type, Some(ChangeType::Test));
+        assert_eq!(tests.title, "Add tests for retries in fetch client");
+
+        let ci = generate_branch_output(&repo, "main", "feature/lint-job", &config).await.unwrap();
+        assert_eq!(ci.context.change_type, Some(ChangeType::Ci));
+        assert_eq!(ci.title, "Update CI to run clippy on pull requests");
+    }
+
+    #[tokio::test]
+    async fn test_prompt_size_limit_policies() {
+        let topics = ["search", "billing", "invoice", "session", "payment", "export", "upload", "avatar"];
+        let commits: Vec<CommitInfo> = topics
+            .iter()
+            .enumerate()
+            .map(|(i, topic)| CommitInfo {
+                hash: format!("c{}", i),
+                message: format!("rework {} handling across the {} service with extensive retries and logging", topic, topic),
+                author: "Test User".to_string(),
+                author_email: "test@example.com".to_string(),
+                timestamp: 0,
+            })
+            .collect();
+        let branch = "feature/CRU-310-rework-services";
+        let unlimited = generate_commits_output(branch, &commits, &GeneratorConfig::default()).await.unwrap();
+        let limit = unlimited.context.prompt_size() - 200;
+
+        let strict = GeneratorConfig::default()
+            .with_max_prompt_chars(limit)
+            .with_prompt_policy(PromptPolicy::Error);
+        let result = generate_commits_title(branch, &commits, &strict).await;
+        assert!(matches!(result, Err(Error::PromptTooLarge { size, limit: l }) if size > l && l == limit));
+
+        let trimming = GeneratorConfig::default().with_max_prompt_chars(limit);
+        let output = generate_commits_output(branch, &commits, &trimming).await.unwrap();
+        assert!(output.context.prompt_size() <= limit);
+        assert!(output.context.commits.len() < unlimited.context.commits.len());
+        assert_eq!(output.context.commits[0], unlimited.context.commits[0]);
+
+        // The limit applies to the configured model's template, which Gemma makes longer
+        let exact = GeneratorConfig::default()
+            .with_max_prompt_chars(unlimited.context.prompt_size())
+            .with_prompt_policy(PromptPolicy::Error);
+        assert!(generate_commits_title(branch, &commits, &exact).await.is_ok());
+        let gemma = exact.with_model("gemma-2b");
+        let result = generate_commits_title(branch, &commits, &gemma).await;
+        assert!(matches!(result, Err(Error::PromptTooLarge { size, .. }) if size == unlimited.context.prompt_size_for(&Model::Gemma2b)));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_patterns_are_reported_together() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        let repo = test_repo.open();
+        let branch = "feature/CRU-310-fix-bottle-stuck";
+        let config = GeneratorConfig::default()
+            .with_author("(unclosed")
+            .with_exclude_author("[bot")
+            .with_release_pattern("^Release (\\S+)");
+
+        let Err(Error::InvalidPatterns { errors }) = generate_branch_title(&repo, "main", branch, &config).await else {
+            panic!("expected invalid patterns");
+        };
+        let options: Vec<&str> = errors.iter().map(|error| error.option.as_str()).collect();
+        assert_eq!(options, ["--author", "--exclude-author", "--release-pattern"]);
+        assert_eq!(errors[1].pattern, "[bot");
+        assert_eq!(errors[2].message, "no (?P<version>...) group");
+
+        let strict = config.with_strict_patterns(true);
+        let Err(Error::InvalidPatterns { errors }) = generate_branch_title(&repo, "main", branch, &strict).await else {
+            panic!("expected invalid patterns");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].pattern, "(unclosed");
+    }
+
+    #[tokio::test]
+    async fn test_release_please_branch_is_titled_as_release() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["tag", "v1.1.0"]);
+        test_repo.git(&["checkout", "-q", "-b", "release-please--branches--main"]);
+        test_repo.commit_file("CHANGELOG.md", "## 1.2.0\n\n* search indexing", "docs: update changelog for search indexing");
+        test_repo.commit_file("version.txt", "1.2.0", "chore(main): release 1.2.0");
+        let repo = test_repo.open();
+        let branch = "release-please--branches--main";
+
+        let output = generate_branch_output(&repo, "main", branch, &GeneratorConfig::default()).await.unwrap();
+        assert_eq!(output.title, "Release 1.2.0");
+        assert_eq!(output.context.release_version.as_deref(), Some("1.2.0"));
+        assert_eq!(output.context.nearest_tag, None);
+
+        let tagged = GeneratorConfig::default().with_nearest_tag(true);
+        let output = generate_branch_output(&repo, "main", branch, &tagged).await.unwrap();
+        assert!(output.context.to_prompt().contains("Nearest tag: v1.1.0 (2 commits since)"));
+        assert_eq!(output.context.nearest_tag.map(|tag| (tag.name, tag.distance)), Some(("v1.1.0".to_string(), 2)));
+
+        let custom = GeneratorConfig::default().with_release_pattern(r"^Bump version to (?P<version>\S+)");
+        let title = generate_branch_title(&repo, "main", branch, &custom).await.unwrap();
+        assert_ne!(title, "Release 1.2.0");
+
+        let missing_group = GeneratorConfig::default().with_release_pattern(r"^release \S+");
+        let result = generate_branch_title(&repo, "main", branch, &missing_group).await;
+        assert!(matches!(result, Err(Error::InvalidPatterns { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_scaffolding_branches_are_titled_as_initialization() {
+        let test_repo = TestRepo::new();
+        let add_modules = |dir: &str| {
+            std::fs::create_dir(test_repo.path().join(dir)).unwrap();
+            for index in 0..200 {
+                std::fs::write(test_repo.path().join(format!("{}/module_{}.rs", dir, index)), "pub fn run() {}\n").unwrap();
+            }
+            test_repo.git(&["add", "."]);
+        };
+        test_repo.git(&["checkout", "-q", "-b", "feature/everything"]);
+        add_modules("src");
+        test_repo.git(&["commit", "-q", "-m", "Add billing module with invoice export"]);
+        test_repo.git(&["checkout", "-q", "-b", "scaffold/billing-service", "main"]);
+        test_repo.commit_file("Cargo.toml", "[package]", "Add billing service crate");
+        // The same bulk import on an established project is just a big change
+        test_repo.git(&["checkout", "-q", "-b", "established", "main"]);
+        for index in 0..SCAFFOLD_MAX_BASE_FILES {
+            test_repo.commit_file(&format!("lib_{}.rs", index), "pub fn lib() {}", "Add library module");
+        }
+        test_repo.git(&["checkout", "-q", "-b", "feature/vendor-billing"]);
+        add_modules("vendor");
+        test_repo.git(&["commit", "-q", "-m", "Add billing module with invoice export"]);
+        let repo = test_repo.open();
+        let config = GeneratorConfig::default();
+
+        let output = generate_branch_output(&repo, "main", "feature/everything", &config).await.unwrap();
+        assert!(output.context.scaffolding);
+        assert_eq!(output.title, "Initialize project");
+        let title = generate_branch_title(&repo, "main", "scaffold/billing-service", &config).await.unwrap();
+        assert_eq!(title, "Scaffold billing service");
+        let output = generate_branch_output(&repo, "established", "feature/vendor-billing", &config).await.unwrap();
+        assert!(!output.context.scaffolding);
+        assert_ne!(output.title, "Initialize project");
+
+        let opt_out = config.with_detect_scaffolding(false);
+        let title = generate_branch_title(&repo, "main", "feature/everything", &opt_out).await.unwrap();
+        assert_ne!(title, "Initialize project");
+    }
+
+    #[tokio::test]
+    async fn test_require_ticket() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        test_repo.git(&["checkout", "-q", "-b", "feature/search", "main"]);
+        test_repo.commit_file("search.rs", "index", "Add search index (#123)");
+        let repo = test_repo.open();
+        let config = GeneratorConfig::default().with_require_ticket(true);
+
+        let title = generate_branch_title(&repo, "main", "feature/CRU-310-fix-bottle-stuck", &config).await.unwrap();
+        assert!(title.starts_with("CRU-310: "));
+
+        let error = generate_branch_title(&repo, "main", "feature/search", &config).await.unwrap_err();
+        assert_eq!(error.to_string(), "No jira ticket found in branch 'feature/search' or its commits");
+        assert_eq!(error.exit_code(), 4);
+
+        // The commit's GitHub issue only counts once that format is accepted
+        let github = config.with_ticket_formats([TicketFormat::Jira, TicketFormat::Github]);
+        let title = generate_branch_title(&repo, "main", "feature/search", &github).await.unwrap();
+        assert!(title.contains("#123"));
+    }
+
+    #[tokio::test]
+    async fn test_base_branch_ticket_is_not_attributed() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "epic/CRU-300"]);
+        test_repo.commit_file("epic.txt", "epic", "CRU-300: scaffold search epic");
+        test_repo.git(&["checkout", "-q", "-b", "CRU-310/search-filters"]);
+        test_repo.commit_file("filters.txt", "filters", "CRU-300: add search filters to results page");
+        let repo = test_repo.open();
+
+        let output = generate_branch_output(&repo, "epic/CRU-300", "CRU-310/search-filters", &GeneratorConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(output.context.ticket.as_deref(), Some("CRU-310"));
+        assert!(output.title.starts_with("CRU-310: "), "{}", output.title);
+        assert!(!output.title.contains("CRU-300"), "{}", output.title);
+
+        // A branch that only repeats the epic's ticket gets no prefix
+        let commits = repo.get_filtered_commits("main", "CRU-310/search-filters", &CommitFilter::default()).unwrap();
+        let config = GeneratorConfig::default().with_base_branch("epic/CRU-300");
+        let title = generate_commits_title("feature/CRU-300-search-filters", &commits, &config).await.unwrap();
+        assert!(!title.contains("CRU-300"), "{}", title);
+    }
+
+    #[tokio::test]
+    async fn test_ticket_already_squashed_into_base_is_found() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        test_repo.git(&["checkout", "-q", "main"]);
+        test_repo.commit_file("fix.txt", "fix", "CRU-310: Fix bottle stuck (#42)");
+        let squash = test_repo.git(&["rev-parse", "HEAD"]);
+        let repo = test_repo.open();
+        let config = GeneratorConfig::default();
+
+        let merged = find_merged_ticket(&repo, "main", "feature/CRU-310-fix-bottle-stuck", &config)
+            .unwrap()
+            .unwrap();
+        assert_eq!(merged.hash, squash);
+        assert_eq!(
+            merged.to_string(),
+            format!("CRU-310 already merged in {} (CRU-310: Fix bottle stuck (#42))", &squash[..7])
+        );
+
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-311-search", "main~1"]);
+        test_repo.commit_file("search.txt", "search", "add search indexing");
+        assert_eq!(find_merged_ticket(&repo, "main", "feature/CRU-311-search", &config).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_cached_titles_still_go_through_post_process_command() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let context = CleanContext {
+            change_type: Some(ChangeType::Fix),
+            commits: vec!["bottle stuck with remediation system".to_string()],
+            ..Default::default()
+        };
+        let config = GeneratorConfig::default().with_cache_dir(dir.path());
+
+        let plain = generate_context_title(&context, &config).await.unwrap();
+        let shouted = config.clone().with_post_process_command("tr a-z A-Z");
+        assert_eq!(generate_context_title(&context, &shouted).await.unwrap(), plain.to_uppercase());
+        assert_eq!(TitleCache::new(dir.path()).list().unwrap().len(), 1);
+
+        // A cache directory that cannot be created does not fail the title
+        let blocked = dir.path().join("blocked");
+        std::fs::write(&blocked, "").unwrap();
+        let title = generate_context_title(&context, &config.with_cache_dir(&blocked)).await.unwrap();
+        assert_eq!(title, plain);
+    }
+
+    #[tokio::test]
+    async fn test_commit_ticket_is_not_the_domain() {
+        let commits = [CommitInfo {
+            hash: "abc123".to_string(),
+            message: "CRU-403 fix cart coupon stacking".to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        }];
+        let config = GeneratorConfig::default();
+
+        let explanation = explain_commits_title("fix/coupons", &commits, &config).unwrap();
+        assert_ne!(explanation.domain.as_deref(), Some("cru-403"));
+        let title = generate_commits_title("fix/coupons", &commits, &config).await.unwrap();
+        assert_eq!(title.matches("CRU-403").count(), 1, "{}", title);
+        let deduped = generate_commits_title("fix/coupons", &commits, &config.with_dedupe_ticket(true)).await.unwrap();
+        assert!(deduped.starts_with("CRU-403: ") && !deduped[9..].contains("CRU-403"), "{}", deduped);
+    }
+
+    #[tokio::test]
+    async fn test_changed_issue_ref_misses_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let commit = |message: &str| CommitInfo {
+            hash: "abc123".to_string(),
+            message: message.to_string(),
+            author: "Test User".to_string(),
+            author_email: "test@example.com".to_string(),
+            timestamp: 0,
+        };
+        let branch = "fix/payment-retry";
+        let config = GeneratorConfig::default().with_keep_refs(true).with_cache_dir(dir.path());
+
+        let first = generate_commits_title(branch, &[commit("fix payment retry loop (#12)")], &config).await.unwrap();
+        assert!(first.ends_with(" (#12)"), "{}", first);
+        let amended = generate_commits_title(branch, &[commit("fix payment retry loop (#13)")], &config).await.unwrap();
+        assert!(amended.ends_with(" (#13)"), "{}", amended);
+        assert_eq!(TitleCache::new(dir.path()).list().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_changed_line_counts_miss_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let changed = |path: &str, lines_changed: usize| ChangedFile {
+            path: path.to_string(),
+            lines_changed,
+            added: false,
+        };
+        let billing = CleanContext {
+            change_type: Some(ChangeType::Feature),
+            commits: vec!["tune retry limits".to_string()],
+            files: vec![changed("src/billing/mod.rs", 100), changed("src/auth/mod.rs", 1)],
+            ..Default::default()
+        };
+        let auth = CleanContext {
+            files: vec![changed("src/billing/mod.rs", 1), changed("src/auth/mod.rs", 100)],
+            ..billing.clone()
+        };
+        let config = GeneratorConfig::default().with_emphasize_dominant(0.6).with_cache_dir(dir.path());
+
+        assert!(generate_context_title(&billing, &config).await.unwrap().contains("billing"));
+        let title = generate_context_title(&auth, &config).await.unwrap();
+        assert!(title.contains("auth") && !title.contains("billing"), "{}", title);
+    }
+}
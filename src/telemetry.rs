@@ -0,0 +1,97 @@
+//! OpenTelemetry export of the generation spans (`otel` feature)
+//!
+//! Each title runs through a `git` span per repository read, a `context`
+//! span and a `generate` span. [`layer`] turns them into OpenTelemetry spans
+//! for any exporter, e.g. OTLP from `opentelemetry-otlp`, so a service built
+//! on the library can track stage latency and errors. A failed stage records
+//! its error on the span and sets the span status to error.
+
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::{Level, Subscriber};
+use tracing_subscriber::{filter::Targets, registry::LookupSpan, Layer};
+
+/// Instrumentation scope of the exported spans
+pub const TRACER_NAME: &str = "pr-title-generator";
+
+/// A tracing layer exporting this crate's spans through `provider`
+///
+/// Spans and events of other crates are left to the rest of the
+/// subscriber. Stage errors are `DEBUG` events, so a global filter above
+/// `DEBUG` for this crate hides them from the exporter.
+pub fn layer<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(TRACER_NAME))
+        .with_filter(Targets::new().with_target("pr_title_generator", Level::DEBUG))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::CommitFilter;
+    use crate::test_support::TestRepo;
+    use crate::{generate_branch_title, GeneratorConfig};
+    use opentelemetry::trace::Status;
+    use opentelemetry_sdk::error::OTelSdkResult;
+    use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Debug, Clone, Default)]
+    struct TestExporter {
+        spans: Arc<Mutex<Vec<SpanData>>>,
+    }
+
+    impl SpanExporter for TestExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            self.spans.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    fn attribute(span: &SpanData, key: &str) -> Option<String> {
+        span.attributes
+            .iter()
+            .find(|attribute| attribute.key.as_str() == key)
+            .map(|attribute| attribute.value.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_stage_spans_are_exported() {
+        let test_repo = TestRepo::new();
+        test_repo.git(&["checkout", "-q", "-b", "feature/CRU-310-fix-bottle-stuck"]);
+        test_repo.commit_file("fix.txt", "fix", "fix: bottle stuck with remediation system");
+        let repo = test_repo.open();
+
+        let exporter = TestExporter::default();
+        let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        generate_branch_title(&repo, "main", "feature/CRU-310-fix-bottle-stuck", &GeneratorConfig::default())
+            .await
+            .unwrap();
+        assert!(repo.get_filtered_commits("main", "feature/missing", &CommitFilter::default()).is_err());
+
+        let spans = exporter.spans.lock().unwrap();
+        let names: Vec<&str> = spans.iter().map(|span| span.name.as_ref()).collect();
+        for name in ["git", "context", "generate"] {
+            assert!(names.contains(&name), "{:?}", names);
+        }
+        assert!(spans.iter().all(|span| span.instrumentation_scope.name() == TRACER_NAME));
+
+        let generate = spans.iter().find(|span| span.name == "generate").unwrap();
+        assert_eq!(attribute(generate, "backend").as_deref(), Some("pattern"));
+        assert_eq!(generate.status, Status::Unset);
+        let context = spans.iter().find(|span| span.name == "context").unwrap();
+        assert_eq!(attribute(context, "branch").as_deref(), Some("feature/CRU-310-fix-bottle-stuck"));
+
+        let failed = spans.last().unwrap();
+        assert_eq!(failed.name, "git");
+        assert_eq!(attribute(failed, "branch").as_deref(), Some("feature/missing"));
+        assert!(matches!(failed.status, Status::Error { .. }), "{:?}", failed.status);
+    }
+}
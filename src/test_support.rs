@@ -0,0 +1,81 @@
+//! Shared fixtures for tests that need a real git repository
+
+use crate::git::GitRepo;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Temporary git repository with `main` checked out and one initial commit
+pub(crate) struct TestRepo {
+    dir: TempDir,
+}
+
+impl TestRepo {
+    pub(crate) fn new() -> Self {
+        let repo = Self {
+            dir: TempDir::new().unwrap(),
+        };
+
+        repo.git(&["init", "-q"]);
+        repo.git(&["symbolic-ref", "HEAD", "refs/heads/main"]);
+        repo.git(&["config", "user.name", "Test User"]);
+        repo.git(&["config", "user.email", "test@example.com"]);
+        repo.commit_file("README.md", "# Test Repo", "Initial commit");
+
+        repo
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub(crate) fn open(&self) -> GitRepo {
+        GitRepo::open(self.path()).unwrap()
+    }
+
+    /// Run a git command in the repository and return its stdout
+    pub(crate) fn git(&self, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(self.path())
+            .output()
+            .unwrap();
+
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    /// Write a file and commit it with author and committer dates at `timestamp`
+    pub(crate) fn commit_file_at(&self, name: &str, content: &str, message: &str, timestamp: i64) {
+        std::fs::write(self.path().join(name), content).unwrap();
+        self.git(&["add", "."]);
+
+        let date = format!("@{} +0000", timestamp);
+        let status = Command::new("git")
+            .args(["commit", "-q", "-m", message])
+            .env("GIT_AUTHOR_DATE", &date)
+            .env("GIT_COMMITTER_DATE", &date)
+            .current_dir(self.path())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git commit failed");
+    }
+
+    /// Write a file and commit it with the given message
+    pub(crate) fn commit_file(&self, name: &str, content: &str, message: &str) {
+        let file_path = self.path().join(name);
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(file_path, content).unwrap();
+
+        self.git(&["add", "."]);
+        self.git(&["commit", "-q", "-m", message]);
+    }
+}
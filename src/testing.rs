@@ -0,0 +1,109 @@
+//! Test doubles for code built on top of `TitleGenerator`
+//!
+//! Enabled with the `test-util` feature so downstream crates can test
+//! title handling without a model or a git repository.
+
+use crate::ml::{GenerationRequest, TitleBackend};
+use crate::{Error, Result};
+
+/// Backend that returns a canned title or echoes the context
+///
+/// ```
+/// use pr_title_generator::{context::CleanContext, ml::TitleGenerator, testing::MockBackend};
+///
+/// let generator = TitleGenerator::with_backend(MockBackend::new("My Title"));
+/// let context = CleanContext::default();
+///
+/// let runtime = tokio::runtime::Runtime::new().unwrap();
+/// let title = runtime.block_on(generator.generate_title(&context)).unwrap();
+/// assert_eq!(title, "My Title");
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockBackend {
+    response: MockResponse,
+}
+
+#[derive(Debug, Clone)]
+enum MockResponse {
+    Canned(String),
+    Echo,
+    Failing(String),
+}
+
+impl MockBackend {
+    /// Always return `title` as the raw title
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            response: MockResponse::Canned(title.into()),
+        }
+    }
+
+    /// Return the context description, or the first commit when there is none
+    pub fn echo() -> Self {
+        Self {
+            response: MockResponse::Echo,
+        }
+    }
+
+    /// Fail every request with a `ModelError` carrying `message`, like a model that did not load
+    pub fn failing(message: impl Into<String>) -> Self {
+        Self {
+            response: MockResponse::Failing(message.into()),
+        }
+    }
+}
+
+impl TitleBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn generate(&self, request: &GenerationRequest<'_>) -> Result<String> {
+        let title = match &self.response {
+            MockResponse::Canned(title) => title.clone(),
+            MockResponse::Echo => request
+                .context
+                .description
+                .clone()
+                .or_else(|| request.context.commits.first().cloned())
+                .unwrap_or_default(),
+            MockResponse::Failing(message) => {
+                return Err(Error::ModelError {
+                    message: message.clone(),
+                })
+            }
+        };
+
+        Ok(title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{context::CleanContext, ml::TitleGenerator};
+
+    #[tokio::test]
+    async fn test_canned_title_is_post_processed() {
+        let generator = TitleGenerator::with_backend(MockBackend::new("fix login crash on startup"));
+        let context = CleanContext {
+            ticket: Some("CRU-310".to_string()),
+            ..Default::default()
+        };
+
+        let title = generator.generate_title(&context).await.unwrap();
+        assert_eq!(title, "CRU-310: Fix login crash on startup");
+    }
+
+    #[tokio::test]
+    async fn test_echo_returns_context_description() {
+        let generator = TitleGenerator::with_backend(MockBackend::echo());
+        let context = CleanContext {
+            description: Some("add search indexing".to_string()),
+            ..Default::default()
+        };
+
+        let title = generator.generate_title(&context).await.unwrap();
+        assert_eq!(title, "Add search indexing");
+    }
+}
@@ -0,0 +1,307 @@
+//! Ticket reference formats recognized in branch names and commits
+
+use crate::Result;
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+
+/// Issue tracker reference styles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TicketFormat {
+    /// Jira keys such as `CRU-310`, also used by Bitbucket
+    #[default]
+    Jira,
+    /// Azure DevOps work items such as `AB#1234`
+    Azure,
+    /// GitHub issues such as `#123` or `GH-123`
+    Github,
+}
+
+impl TicketFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TicketFormat::Jira => "jira",
+            TicketFormat::Azure => "azure",
+            TicketFormat::Github => "github",
+        }
+    }
+
+    /// Build the extractor for this format
+    pub fn extractor(self) -> Result<Box<dyn TicketExtractor>> {
+        self.configured_extractor(false, &TicketPrefixes::default())
+    }
+
+    /// Build the extractor for this format, accepting non-ASCII Jira keys such as `Ä-123`
+    ///
+    /// Azure and GitHub references are numbers, so only Jira keys change.
+    pub fn unicode_extractor(self) -> Result<Box<dyn TicketExtractor>> {
+        self.configured_extractor(true, &TicketPrefixes::default())
+    }
+
+    /// Build the extractor for this format, counting the Jira keys `prefixes` allows
+    pub fn configured_extractor(self, unicode: bool, prefixes: &TicketPrefixes) -> Result<Box<dyn TicketExtractor>> {
+        Ok(match self {
+            TicketFormat::Jira => {
+                let jira = if unicode { JiraTickets::unicode()? } else { JiraTickets::new()? };
+                Box::new(jira.with_prefixes(prefixes.clone()))
+            }
+            TicketFormat::Azure => Box::new(AzureTickets::new()?),
+            TicketFormat::Github => Box::new(GitHubTickets::new()?),
+        })
+    }
+}
+
+/// Jira project keys counted as tickets unless `--ticket-prefixes` names others
+pub const DEFAULT_TICKET_PREFIXES: [&str; 6] = ["CRU", "JIRA", "TASK", "BUG", "FEATURE", "FIX"];
+
+/// Keys that are also branch words, so only their uppercase form is a ticket,
+/// e.g. `FIX-12` but not `fix-12`
+const BRANCH_WORD_KEYS: [&str; 4] = ["TASK", "BUG", "FEATURE", "FIX"];
+
+/// Standards and encodings written like Jira keys, e.g. `UTF-8` or `SHA-256`,
+/// which [`TicketPrefixes::AnyUppercase`] never counts
+pub const NON_TICKET_KEYS: [&str; 12] = ["UTF", "UCS", "SHA", "MD", "ISO", "RFC", "IEEE", "CVE", "CWE", "PEP", "HTTP", "TLS"];
+
+/// Jira project keys that count as tickets; other `WORD-123` matches are noise
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TicketPrefixes {
+    /// Keys on this list in uppercase or lowercase, e.g. `PLAT` for `plat-42`;
+    /// a trailing `-` is ignored, and branch words such as `fix-12` only
+    /// count in uppercase
+    Keys(Vec<String>),
+    /// Any key written in uppercase, e.g. `PLAT-42` but not `plat-42`, `v-2`
+    /// or one of the [`NON_TICKET_KEYS`] such as `UTF-8`
+    AnyUppercase,
+}
+
+impl Default for TicketPrefixes {
+    fn default() -> Self {
+        TicketPrefixes::Keys(DEFAULT_TICKET_PREFIXES.iter().map(|key| key.to_string()).collect())
+    }
+}
+
+impl TicketPrefixes {
+    /// Whether the key of `ticket`, as written, is allowed
+    pub fn allows(&self, ticket: &str) -> bool {
+        let key = ticket.split('-').next().unwrap_or_default();
+        let uppercase = !key.is_empty() && !key.chars().any(char::is_lowercase);
+        match self {
+            TicketPrefixes::Keys(keys) => {
+                let canonical = key.to_uppercase();
+                let lowercase = key.chars().all(char::is_lowercase) && !BRANCH_WORD_KEYS.contains(&canonical.as_str());
+                (uppercase || lowercase)
+                    && keys
+                        .iter()
+                        .any(|allowed| allowed.trim_end_matches('-').to_uppercase() == canonical)
+            }
+            TicketPrefixes::AnyUppercase => uppercase && !NON_TICKET_KEYS.contains(&key),
+        }
+    }
+}
+
+/// Finds a ticket reference in text and returns it in canonical form
+pub trait TicketExtractor: Send + Sync {
+    /// Return the first meaningful ticket in `text`
+    fn extract(&self, text: &str) -> Option<String>;
+
+    /// Return every meaningful ticket in `text`, in order of appearance
+    fn extract_all(&self, text: &str) -> Vec<String> {
+        self.extract(text).into_iter().collect()
+    }
+}
+
+/// Jira-style keys with a known project prefix, normalized to uppercase
+pub struct JiraTickets {
+    pattern: Regex,
+    unicode: bool,
+    prefixes: TicketPrefixes,
+}
+
+impl JiraTickets {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            pattern: Regex::new(r"[A-Za-z]+-\d+")?,
+            unicode: false,
+            prefixes: TicketPrefixes::default(),
+        })
+    }
+
+    /// Also accept keys with non-ASCII letters, e.g. `Ä-123` or `開発-42`
+    ///
+    /// Text is normalized to NFC first. Such a key counts when it is written
+    /// in uppercase or in a script without case, so words like `größe-2`
+    /// are not taken for tickets; `GRÖSSE-2` still is.
+    pub fn unicode() -> Result<Self> {
+        Ok(Self {
+            pattern: Regex::new(r"\p{L}+-\d+")?,
+            unicode: true,
+            prefixes: TicketPrefixes::default(),
+        })
+    }
+
+    /// Count the keys `prefixes` allows instead of [`DEFAULT_TICKET_PREFIXES`]
+    pub fn with_prefixes(mut self, prefixes: TicketPrefixes) -> Self {
+        self.prefixes = prefixes;
+        self
+    }
+
+    /// Check if a ticket number looks meaningful (not just random numbers)
+    fn is_meaningful_ticket(&self, ticket: &str) -> bool {
+        self.prefixes.allows(ticket) || (self.unicode && is_non_ascii_key(ticket))
+    }
+}
+
+impl TicketExtractor for JiraTickets {
+    fn extract(&self, text: &str) -> Option<String> {
+        self.extract_all(text).into_iter().next()
+    }
+
+    fn extract_all(&self, text: &str) -> Vec<String> {
+        let text: String = if self.unicode { text.nfc().collect() } else { text.to_string() };
+        self.pattern
+            .find_iter(&text)
+            .map(|m| m.as_str())
+            .filter(|ticket| self.is_meaningful_ticket(ticket))
+            .map(str::to_uppercase)
+            .collect()
+    }
+}
+
+/// Whether the key of `ticket` has a non-ASCII letter and no lowercase ones
+fn is_non_ascii_key(ticket: &str) -> bool {
+    let key = ticket.split('-').next().unwrap_or_default();
+    !key.is_ascii() && !key.chars().any(char::is_lowercase)
+}
+
+/// Azure DevOps work items, normalized to `AB#<id>`
+pub struct AzureTickets {
+    pattern: Regex,
+}
+
+impl AzureTickets {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            pattern: Regex::new(r"(?i)\bAB#(\d+)\b")?,
+        })
+    }
+}
+
+impl TicketExtractor for AzureTickets {
+    fn extract(&self, text: &str) -> Option<String> {
+        self.pattern
+            .captures(text)
+            .map(|captures| format!("AB#{}", &captures[1]))
+    }
+
+    fn extract_all(&self, text: &str) -> Vec<String> {
+        self.pattern
+            .captures_iter(text)
+            .map(|captures| format!("AB#{}", &captures[1]))
+            .collect()
+    }
+}
+
+/// GitHub issue references, normalized to `#<number>`
+pub struct GitHubTickets {
+    pattern: Regex,
+}
+
+impl GitHubTickets {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            pattern: Regex::new(r"(?i)(?:#|\bGH-)(\d+)\b")?,
+        })
+    }
+}
+
+impl TicketExtractor for GitHubTickets {
+    fn extract(&self, text: &str) -> Option<String> {
+        self.pattern
+            .captures(text)
+            .map(|captures| format!("#{}", &captures[1]))
+    }
+
+    fn extract_all(&self, text: &str) -> Vec<String> {
+        self.pattern
+            .captures_iter(text)
+            .map(|captures| format!("#{}", &captures[1]))
+            .collect()
+    }
+}
+
+/// Project key of a Jira-style ticket, e.g. `CRU` for `CRU-310`
+pub fn project_key(ticket: &str) -> Option<&str> {
+    let (key, number) = ticket.split_once('-')?;
+    let is_key = !key.is_empty() && key.chars().all(char::is_alphabetic);
+    let is_number = !number.is_empty() && number.chars().all(|c| c.is_ascii_digit());
+    (is_key && is_number).then_some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_azure_work_items_are_canonical() {
+        let azure = AzureTickets::new().unwrap();
+
+        assert_eq!(azure.extract("feature/ab#1234-login-page"), Some("AB#1234".to_string()));
+        // A bare `#1234` is more likely a GitHub issue or PR than a work item
+        assert_eq!(azure.extract("Fix login redirect #1234"), None);
+        assert_eq!(azure.extract("Fix login redirect (AB#1234)"), Some("AB#1234".to_string()));
+        assert_eq!(azure.extract("feature/login-page"), None);
+    }
+
+    #[test]
+    fn test_github_issue_references() {
+        let github = GitHubTickets::new().unwrap();
+
+        assert_eq!(github.extract("fix/gh-42-login"), Some("#42".to_string()));
+        assert_eq!(github.extract("Closes #42"), Some("#42".to_string()));
+        assert_eq!(github.extract_all("Closes #42 and gh-43"), vec!["#42", "#43"]);
+    }
+
+    #[test]
+    fn test_non_ascii_jira_keys_need_unicode_mode() {
+        let ascii = JiraTickets::new().unwrap();
+        let unicode = JiraTickets::unicode().unwrap();
+
+        assert_eq!(ascii.extract("feature/Ä-123-fix-upload"), None);
+        assert_eq!(unicode.extract("feature/Ä-123-fix-upload"), Some("Ä-123".to_string()));
+        // Decomposed `A` and combining diaeresis come out composed
+        assert_eq!(unicode.extract("feature/A\u{308}-123-fix-upload"), Some("\u{c4}-123".to_string()));
+        assert_eq!(unicode.extract("fix/開発-42-login"), Some("開発-42".to_string()));
+        assert_eq!(unicode.extract("fix/größe-2-layout"), None);
+        assert_eq!(unicode.extract("feature/cru-310-fix-bottle"), Some("CRU-310".to_string()));
+    }
+
+    #[test]
+    fn test_ticket_prefixes_are_configurable() {
+        let default = JiraTickets::new().unwrap();
+        assert_eq!(default.extract("feature/PLAT-42-cache"), None);
+        assert_eq!(default.extract("feature/cru-310-fix"), Some("CRU-310".to_string()));
+        assert_eq!(default.extract("feature/Cru-310-fix"), None);
+        // Lowercase branch words are not tickets, even with an allowed key
+        assert_eq!(default.extract_all("fix-123 and bug-42 but BUG-42"), vec!["BUG-42"]);
+
+        let platform = TicketPrefixes::Keys(vec!["PLAT".to_string(), "infra-".to_string()]);
+        let custom = JiraTickets::new().unwrap().with_prefixes(platform);
+        assert_eq!(custom.extract_all("PLAT-42, infra-7 and CRU-310"), vec!["PLAT-42", "INFRA-7"]);
+
+        let any = JiraTickets::new().unwrap().with_prefixes(TicketPrefixes::AnyUppercase);
+        assert_eq!(any.extract_all("upgrade-2 for PLAT-42 and Ops-3"), vec!["PLAT-42"]);
+        assert_eq!(any.extract_all("UTF-8 paths, SHA-256 sums and ISO-8601 dates in OPS-9"), vec!["OPS-9"]);
+        assert_eq!(any.extract("RFC-3339 timestamps"), None);
+    }
+
+    #[test]
+    fn test_project_key() {
+        assert_eq!(project_key("CRU-310"), Some("CRU"));
+        assert_eq!(project_key("開発-42"), Some("開発"));
+        assert_eq!(project_key("AB#1234"), None);
+        assert_eq!(project_key("#42"), None);
+    }
+}
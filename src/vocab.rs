@@ -0,0 +1,162 @@
+//! Domain terms learned from a repository's commit history (`--learn-vocab`)
+//!
+//! The built-in domains only know common areas such as `auth` or `api`. This
+//! pass counts the words of up to [`HISTORY_LIMIT`] commit subjects and keeps
+//! the ones that recur across commits, such as `bottler` or `ledger`, as extra
+//! domains for the pattern backend. The terms are cached in the git directory
+//! for the commit they were learned at, so only the first run per base commit
+//! walks the history.
+
+use crate::git::GitRepo;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use tracing::debug;
+
+/// Commit subjects read to learn the vocabulary
+pub const HISTORY_LIMIT: usize = 1000;
+
+/// Cache in the git directory, e.g. `.git/pr-title-vocab.json`
+pub const VOCAB_FILE_NAME: &str = "pr-title-vocab.json";
+
+/// Subjects a word has to appear in to count as a domain term
+const MIN_SUBJECTS: usize = 3;
+
+/// Terms kept, most frequent first
+const MAX_TERMS: usize = 50;
+
+/// Shorter words are rarely domains and often abbreviations of anything
+const MIN_TERM_LENGTH: usize = 4;
+
+/// Words common to every repository's history, in their base form
+const COMMON_WORDS: [&str; 64] = [
+    "about", "after", "again", "also", "before", "being", "branch", "build", "bump", "change", "check", "chore",
+    "clean", "cleanup", "code", "commit", "correct", "docs", "document", "each", "enable", "disable", "feat",
+    "feature", "file", "first", "from", "handle", "have", "implement", "improve", "initial", "into", "just", "make",
+    "master", "merge", "minor", "more", "move", "only", "other", "pull", "readme", "refactor", "release", "remove",
+    "rename", "replace", "request", "revert", "should", "some", "support", "test", "that", "their", "then", "there",
+    "this", "update", "version", "when", "with",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedVocabulary {
+    /// Commit the terms were learned at
+    commit: String,
+    terms: Vec<String>,
+}
+
+/// Domain terms of the history of `reference`, most frequent first
+///
+/// A cache that cannot be read or written only costs the history walk.
+pub fn learn_vocabulary(repo: &GitRepo, reference: &str) -> Result<Vec<String>> {
+    let Some(head) = repo.history(reference, 1)?.into_iter().next() else {
+        return Ok(Vec::new());
+    };
+    let path = repo.git_dir().join(VOCAB_FILE_NAME);
+    let cached = fs::read_to_string(&path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<CachedVocabulary>(&text).ok())
+        .filter(|cached| cached.commit == head.hash);
+    if let Some(cached) = cached {
+        return Ok(cached.terms);
+    }
+
+    let subjects: Vec<String> = repo
+        .history(reference, HISTORY_LIMIT)?
+        .iter()
+        .map(|commit| commit.subject())
+        .collect();
+    let terms = vocabulary(&subjects);
+    debug!(subjects = subjects.len(), ?terms, "learned domain vocabulary");
+
+    let cached = CachedVocabulary {
+        commit: head.hash,
+        terms,
+    };
+    if let Err(error) = serde_json::to_string(&cached).map(|json| fs::write(&path, json)) {
+        debug!(%error, "could not cache the domain vocabulary");
+    }
+    Ok(cached.terms)
+}
+
+/// Words appearing in at least `MIN_SUBJECTS` of `subjects`, most frequent first
+fn vocabulary(subjects: &[String]) -> Vec<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for subject in subjects {
+        let subject = subject.to_lowercase();
+        let words: HashSet<&str> = subject
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| is_candidate_term(word))
+            .collect();
+        for word in words {
+            *counts.entry(word.to_string()).or_default() += 1;
+        }
+    }
+
+    let mut terms: Vec<(String, usize)> = counts.into_iter().filter(|(_, count)| *count >= MIN_SUBJECTS).collect();
+    terms.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+    terms.into_iter().take(MAX_TERMS).map(|(term, _)| term).collect()
+}
+
+/// Whether a lowercased word could name a domain rather than an action or filler
+fn is_candidate_term(word: &str) -> bool {
+    if word.chars().count() < MIN_TERM_LENGTH || word.chars().any(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    // `fixes`, `updated` and `handling` are as common as their base forms
+    let stems = ["", "s", "es", "d", "ed", "ing"]
+        .iter()
+        .filter_map(|suffix| word.strip_suffix(suffix));
+    !stems.into_iter().any(|stem| COMMON_WORDS.contains(&stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestRepo;
+    use crate::{explain_branch_title, GeneratorConfig};
+
+    fn subjects(subjects: &[&str]) -> Vec<String> {
+        subjects.iter().map(|subject| subject.to_string()).collect()
+    }
+
+    #[test]
+    fn test_recurring_terms_are_learned() {
+        let terms = vocabulary(&subjects(&[
+            "Tune bottler throughput",
+            "Fix bottler retries after a restart",
+            "Add bottler metrics",
+            "Update README",
+            "Updated the README again",
+            "Updates to the bottling docs",
+        ]));
+
+        assert_eq!(terms, vec!["bottler"]);
+    }
+
+    #[tokio::test]
+    async fn test_history_term_becomes_the_domain() {
+        let test_repo = TestRepo::new();
+        for (i, subject) in ["tune bottler throughput", "bottler retries after a restart", "add bottler metrics"]
+            .iter()
+            .enumerate()
+        {
+            test_repo.commit_file(&format!("bottler{}.rs", i), subject, subject);
+        }
+        test_repo.git(&["checkout", "-q", "-b", "stall-fix"]);
+        test_repo.commit_file("queue.rs", "queue", "fix: stall when the bottler queue overflows");
+        let repo = test_repo.open();
+
+        let plain = explain_branch_title(&repo, "main", "stall-fix", &GeneratorConfig::default()).unwrap();
+        assert_eq!(plain.domain.as_deref(), Some("stall"));
+
+        let learned = GeneratorConfig::default().with_learn_vocab(true);
+        let explanation = explain_branch_title(&repo, "main", "stall-fix", &learned).unwrap();
+        assert_eq!(explanation.domain.as_deref(), Some("bottler"));
+
+        let cache = fs::read_to_string(repo.git_dir().join(VOCAB_FILE_NAME)).unwrap();
+        assert!(cache.contains(r#""terms":["bottler"]"#), "{}", cache);
+        assert_eq!(learn_vocabulary(&repo, "main").unwrap(), vec!["bottler"]);
+    }
+}